@@ -0,0 +1,45 @@
+//! Prints canonical commitment test vectors as JSON.
+//!
+//! ```text
+//! cargo run -p private_state_toolkit --example gen_vectors --features client \
+//!     > programs/private_state_toolkit/vectors/commitment.json
+//! ```
+//!
+//! Nonces are decimal strings (u64::MAX doesn't fit a JSON/JS number) and
+//! payloads and commitments are lowercase hex. `tests/vectors.rs` checks the
+//! checked-in file against `compute_commitment`.
+
+use private_state_toolkit::client::compute_commitment;
+use serde_json::json;
+
+/// Largest payload covered: a full Solana transaction packet.
+const MAX_PAYLOAD_LEN: usize = 1232;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn main() {
+    let max_payload: Vec<u8> = (0..MAX_PAYLOAD_LEN).map(|i| i as u8).collect();
+    let cases: [(&str, u64, &[u8]); 6] = [
+        ("empty payload, nonce 0", 0, b""),
+        ("empty payload, max nonce", u64::MAX, b""),
+        ("short payload", 5, b"hello"),
+        ("single zero byte", 1, &[0]),
+        ("max-length payload, nonce 0", 0, &max_payload),
+        ("max-length payload, max nonce", u64::MAX, &max_payload),
+    ];
+
+    let vectors: Vec<_> = cases
+        .iter()
+        .map(|(name, nonce, payload)| {
+            json!({
+                "name": name,
+                "nonce": nonce.to_string(),
+                "payload": hex(payload),
+                "commitment": hex(&compute_commitment(*nonce, payload)),
+            })
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&vectors).unwrap());
+}
@@ -0,0 +1,214 @@
+//! # Instruction Builders
+//!
+//! Plain `Instruction` constructors for the read-only validation hooks, for
+//! callers that don't use Anchor's generated CPI module (e.g. a program built
+//! on bare `solana-program`, or an off-chain client).
+//!
+//! A non-Anchor program gates on PST by building the instruction and invoking
+//! it with the private state account and the PST program:
+//!
+//! ```rust,ignore
+//! let ix = private_state_toolkit::builders::assert_state(
+//!     &private_state_toolkit::ID,
+//!     private_state.key,
+//!     expected_commitment,
+//!     expected_nonce,
+//! );
+//! invoke(&ix, &[private_state.clone(), pst_program.clone()])?;
+//! // If we reach here, the state is valid - proceed with gated action
+//! ```
+//!
+//! The instruction data is the 8-byte Anchor discriminator followed by the
+//! Borsh-encoded arguments:
+//!
+//! ```
+//! use anchor_lang::prelude::Pubkey;
+//! use private_state_toolkit::builders;
+//!
+//! let private_state = Pubkey::new_unique();
+//! let ix = builders::assert_state(&private_state_toolkit::ID, &private_state, [7; 32], 5);
+//!
+//! assert_eq!(ix.program_id, private_state_toolkit::ID);
+//! assert_eq!(ix.accounts.len(), 1);
+//! assert_eq!(ix.accounts[0].pubkey, private_state);
+//! assert!(!ix.accounts[0].is_signer && !ix.accounts[0].is_writable);
+//! assert_eq!(ix.data.len(), 8 + 32 + 8);
+//! assert_eq!(ix.data[8..40], [7; 32]);
+//! assert_eq!(ix.data[40..], 5u64.to_le_bytes());
+//! ```
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::InstructionData;
+
+/// Builds an `assert_state` instruction.
+pub fn assert_state(
+    program_id: &Pubkey,
+    private_state: &Pubkey,
+    expected_commitment: [u8; 32],
+    expected_nonce: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::AssertState {
+            expected_commitment,
+            expected_nonce,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `assert_commitment` instruction.
+pub fn assert_commitment(
+    program_id: &Pubkey,
+    private_state: &Pubkey,
+    expected_commitment: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::AssertCommitment {
+            expected_commitment,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `assert_state_strict` instruction.
+pub fn assert_state_strict(
+    program_id: &Pubkey,
+    private_state: &Pubkey,
+    expected_commitment: [u8; 32],
+    expected_nonce: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::AssertStateStrict {
+            expected_commitment,
+            expected_nonce,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `verify_payload` instruction.
+pub fn verify_payload(
+    program_id: &Pubkey,
+    private_state: &Pubkey,
+    payload: Vec<u8>,
+    claimed_nonce: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::VerifyPayload {
+            payload,
+            claimed_nonce,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `assert_state_cmp` instruction (`op` is one of `NONCE_CMP_*`).
+pub fn assert_state_cmp(
+    program_id: &Pubkey,
+    private_state: &Pubkey,
+    expected_commitment: [u8; 32],
+    nonce_bound: u64,
+    op: u8,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::AssertStateCmp {
+            expected_commitment,
+            nonce_bound,
+            op,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `assert_state_signed` instruction; `requester` must sign.
+pub fn assert_state_signed(
+    program_id: &Pubkey,
+    private_state: &Pubkey,
+    requester: &Pubkey,
+    expected_commitment: [u8; 32],
+    expected_nonce: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertStateSigned {
+            private_state: *private_state,
+            requester: *requester,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::AssertStateSigned {
+            expected_commitment,
+            expected_nonce,
+            expected_requester: *requester,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `get_state` instruction (the `PrivateStateView` comes back as
+/// return data).
+pub fn get_state(program_id: &Pubkey, private_state: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::GetState {}.data(),
+    }
+}
+
+/// Builds a `snapshot` instruction (the `StateSnapshot` comes back as
+/// return data).
+pub fn snapshot(program_id: &Pubkey, private_state: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::AssertState {
+            private_state: *private_state,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::Snapshot {}.data(),
+    }
+}
+
+/// Builds the Anchor `CpiContext` for `assert_state` from raw account infos.
+///
+/// ```rust,ignore
+/// let cpi_ctx = assert_state_cpi_ctx(pst_program, private_state);
+/// private_state_toolkit::cpi::assert_state(cpi_ctx, commitment, nonce)?;
+/// ```
+#[cfg(feature = "cpi")]
+pub fn assert_state_cpi_ctx<'a, 'b, 'c, 'info>(
+    pst_program: AccountInfo<'info>,
+    private_state: AccountInfo<'info>,
+) -> CpiContext<'a, 'b, 'c, 'info, crate::cpi::accounts::AssertState<'info>> {
+    CpiContext::new(
+        pst_program,
+        crate::cpi::accounts::AssertState { private_state },
+    )
+}
@@ -0,0 +1,90 @@
+//! # Off-chain Commitment Helpers
+//!
+//! Canonical Rust implementation of the PST commitment scheme for clients:
+//! ```text
+//! commitment = sha256(nonce_le_u64 || encrypted_payload)
+//! ```
+//!
+//! The nonce is always encoded as 8 little-endian bytes and prepended to the
+//! payload, matching the TypeScript SDK's `commitment()` byte-for-byte.
+//!
+//! Enabled with the `client` feature so it never ships in the on-chain program.
+//!
+//! ## Known Vectors
+//!
+//! The full set, including max-length payloads and `u64::MAX` nonces, is in
+//! `vectors/commitment.json` (regenerate with `examples/gen_vectors.rs`).
+//!
+//! ```
+//! use private_state_toolkit::client::compute_commitment;
+//!
+//! fn hex(bytes: &[u8]) -> String {
+//!     bytes.iter().map(|b| format!("{:02x}", b)).collect()
+//! }
+//!
+//! assert_eq!(
+//!     hex(&compute_commitment(0, b"")),
+//!     "af5570f5a1810b7af78caf4bc70a660f0df51e42baf91d4de5b2328de0e83dfc"
+//! );
+//! assert_eq!(
+//!     hex(&compute_commitment(5, b"hello")),
+//!     "fe745503750fdbf3e6ef676d16d85ee0d63626c594222f7e991908bdffef7ac9"
+//! );
+//! ```
+
+use anchor_lang::solana_program::hash::hashv;
+
+/// Length of the encoded nonce prefix in a commitment preimage.
+pub const NONCE_LEN: usize = 8;
+
+/// Computes `sha256(nonce (u64 LE) || encrypted_payload)`.
+pub fn compute_commitment(nonce: u64, encrypted_payload: &[u8]) -> [u8; 32] {
+    hashv(&[&nonce.to_le_bytes(), encrypted_payload]).to_bytes()
+}
+
+/// Builds the exact bytes that are hashed into a commitment.
+///
+/// ```
+/// use private_state_toolkit::client::commitment_preimage;
+///
+/// assert_eq!(commitment_preimage(1, b"ab"), [1, 0, 0, 0, 0, 0, 0, 0, b'a', b'b']);
+/// ```
+pub fn commitment_preimage(nonce: u64, encrypted_payload: &[u8]) -> Vec<u8> {
+    let mut preimage = Vec::with_capacity(NONCE_LEN + encrypted_payload.len());
+    preimage.extend_from_slice(&nonce.to_le_bytes());
+    preimage.extend_from_slice(encrypted_payload);
+    preimage
+}
+
+/// Splits a commitment preimage back into `(nonce, encrypted_payload)`.
+///
+/// Returns `None` if the preimage is shorter than the nonce prefix.
+///
+/// ```
+/// use private_state_toolkit::client::{commitment_preimage, parse_commitment_preimage};
+///
+/// let preimage = commitment_preimage(42, b"payload");
+/// assert_eq!(parse_commitment_preimage(&preimage), Some((42, &b"payload"[..])));
+/// assert_eq!(parse_commitment_preimage(&[0; 7]), None);
+/// ```
+pub fn parse_commitment_preimage(preimage: &[u8]) -> Option<(u64, &[u8])> {
+    if preimage.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, payload) = preimage.split_at(NONCE_LEN);
+    let nonce = u64::from_le_bytes(nonce.try_into().ok()?);
+    Some((nonce, payload))
+}
+
+/// Checks that `commitment` was computed from `nonce` and `encrypted_payload`.
+///
+/// ```
+/// use private_state_toolkit::client::{compute_commitment, verify_commitment};
+///
+/// let commitment = compute_commitment(3, b"state");
+/// assert!(verify_commitment(&commitment, 3, b"state"));
+/// assert!(!verify_commitment(&commitment, 4, b"state"));
+/// ```
+pub fn verify_commitment(commitment: &[u8; 32], nonce: u64, encrypted_payload: &[u8]) -> bool {
+    compute_commitment(nonce, encrypted_payload) == *commitment
+}
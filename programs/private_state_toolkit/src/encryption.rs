@@ -0,0 +1,305 @@
+//! # Off-chain AES-256-GCM Helpers
+//!
+//! Encrypts application state locally before its commitment goes on-chain:
+//! ```text
+//! sealed     = AES-256-GCM(key, aead_nonce, plaintext)   // ciphertext || tag
+//! commitment = sha256(nonce_le_u64 || pack(aead_nonce, sealed))
+//! ```
+//!
+//! Built on the `aes-gcm` crate. `pack` produces the same
+//! `iv || tag || ciphertext` bytes as the TypeScript SDK's `encryptPayload`,
+//! so either side can decrypt and commit the other's payloads.
+//!
+//! Reusing an AEAD nonce under one key breaks GCM completely. `encrypt` draws a
+//! random nonce from the OS; `StateCipher` instead derives it from the
+//! account's epoch and nonce (`derive_nonce`) and refuses any nonce the chain
+//! has already accepted.
+//!
+//! Enabled with the `client` feature so it never ships in the on-chain program.
+//!
+//! ## Known Vectors
+//!
+//! AES-256 cases from the GCM specification (McGrew & Viega, test cases 13-15):
+//!
+//! ```
+//! use private_state_toolkit::encryption::{decrypt, encrypt_with_nonce};
+//!
+//! fn hex(bytes: &[u8]) -> String {
+//!     bytes.iter().map(|b| format!("{:02x}", b)).collect()
+//! }
+//! fn unhex(s: &str) -> Vec<u8> {
+//!     (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+//! }
+//!
+//! // Test case 13: empty plaintext, the output is just the tag
+//! assert_eq!(
+//!     hex(&encrypt_with_nonce(&[0; 32], &[0; 12], b"").unwrap()),
+//!     "530f8afbc74536b9a963b4f1c4cb738b"
+//! );
+//!
+//! // Test case 14: one zero block
+//! assert_eq!(
+//!     hex(&encrypt_with_nonce(&[0; 32], &[0; 12], &[0; 16]).unwrap()),
+//!     "cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919"
+//! );
+//!
+//! // Test case 15: four blocks
+//! let key: [u8; 32] =
+//!     unhex("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308")
+//!         .try_into()
+//!         .unwrap();
+//! let nonce: [u8; 12] = unhex("cafebabefacedbaddecaf888").try_into().unwrap();
+//! let plaintext = unhex(concat!(
+//!     "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72",
+//!     "1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255",
+//! ));
+//! let sealed = encrypt_with_nonce(&key, &nonce, &plaintext).unwrap();
+//! assert_eq!(
+//!     hex(&sealed),
+//!     concat!(
+//!         "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa",
+//!         "8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662898015ad",
+//!         "b094dac5d93471bdec1a502270e3cc6c",
+//!     )
+//! );
+//! assert_eq!(decrypt(&key, &nonce, &sealed).unwrap(), plaintext);
+//! ```
+
+use std::fmt;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+
+use crate::PrivateState;
+
+/// AES-256 key length in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// AEAD nonce (GCM IV) length in bytes.
+pub const AEAD_NONCE_LEN: usize = 12;
+
+/// GCM authentication tag length in bytes.
+pub const TAG_LEN: usize = 16;
+
+/// Errors returned by the encryption helpers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// The nonce was already accepted on-chain or used by this `StateCipher`
+    NonceReused,
+    /// The account's epoch does not fit the 4 bytes `derive_nonce` gives it
+    EpochOutOfRange,
+    /// The plaintext exceeds AES-GCM's length limit
+    PlaintextTooLong,
+    /// The input is too short to hold a tag (or a packed nonce and tag)
+    Malformed,
+    /// The tag does not match: wrong key or nonce, or tampered data
+    AuthenticationFailed,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonceReused => write!(f, "AEAD nonce already used for this key"),
+            Self::EpochOutOfRange => write!(f, "epoch does not fit in a derived nonce"),
+            Self::PlaintextTooLong => write!(f, "plaintext exceeds the AES-GCM length limit"),
+            Self::Malformed => write!(f, "encrypted payload is too short"),
+            Self::AuthenticationFailed => write!(f, "authentication tag mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// Encrypts `plaintext` under a fresh random nonce.
+///
+/// Returns `(aead_nonce, ciphertext || tag)`.
+///
+/// ```
+/// use private_state_toolkit::encryption::{decrypt, encrypt, TAG_LEN};
+///
+/// let key = [7u8; 32];
+/// let (nonce, sealed) = encrypt(&key, b"balance=10").unwrap();
+/// assert_eq!(sealed.len(), b"balance=10".len() + TAG_LEN);
+/// assert_eq!(decrypt(&key, &nonce, &sealed).unwrap(), b"balance=10");
+///
+/// let (other, _) = encrypt(&key, b"balance=10").unwrap();
+/// assert_ne!(nonce, other);
+/// ```
+pub fn encrypt(
+    key: &[u8; KEY_LEN],
+    plaintext: &[u8],
+) -> Result<([u8; AEAD_NONCE_LEN], Vec<u8>), EncryptionError> {
+    let nonce: [u8; AEAD_NONCE_LEN] = Aes256Gcm::generate_nonce(&mut OsRng).into();
+    let sealed = encrypt_with_nonce(key, &nonce, plaintext)?;
+    Ok((nonce, sealed))
+}
+
+/// Encrypts `plaintext` under a caller-chosen nonce, returning `ciphertext || tag`.
+///
+/// The caller must never reuse `nonce` with the same key; prefer `encrypt`
+/// or `StateCipher`.
+pub fn encrypt_with_nonce(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; AEAD_NONCE_LEN],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(nonce), plaintext)
+        .map_err(|_| EncryptionError::PlaintextTooLong)
+}
+
+/// Verifies and decrypts `ciphertext || tag`.
+///
+/// ```
+/// use private_state_toolkit::encryption::{decrypt, encrypt, EncryptionError};
+///
+/// let key = [7u8; 32];
+/// let (nonce, mut sealed) = encrypt(&key, b"state").unwrap();
+/// sealed[0] ^= 1;
+/// assert_eq!(decrypt(&key, &nonce, &sealed), Err(EncryptionError::AuthenticationFailed));
+/// assert_eq!(decrypt(&key, &nonce, &[0; 15]), Err(EncryptionError::Malformed));
+/// ```
+pub fn decrypt(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; AEAD_NONCE_LEN],
+    sealed: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    if sealed.len() < TAG_LEN {
+        return Err(EncryptionError::Malformed);
+    }
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), sealed)
+        .map_err(|_| EncryptionError::AuthenticationFailed)
+}
+
+/// Derives the AEAD nonce for a payload committed at on-chain `nonce` in `epoch`.
+///
+/// The epoch as u32 little-endian followed by the nonce as u64 little-endian,
+/// so every (epoch, nonce) pair maps to a distinct AEAD nonce even though
+/// `reset` restarts the nonce at 0.
+///
+/// ```
+/// use private_state_toolkit::encryption::derive_nonce;
+///
+/// assert_eq!(derive_nonce(0, 1), [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+/// assert_ne!(derive_nonce(0, 1), derive_nonce(1, 1));
+/// ```
+pub fn derive_nonce(epoch: u32, nonce: u64) -> [u8; AEAD_NONCE_LEN] {
+    let mut aead_nonce = [0u8; AEAD_NONCE_LEN];
+    aead_nonce[..4].copy_from_slice(&epoch.to_le_bytes());
+    aead_nonce[4..].copy_from_slice(&nonce.to_le_bytes());
+    aead_nonce
+}
+
+/// Packs a nonce and `ciphertext || tag` as `iv || tag || ciphertext`.
+///
+/// The layout of the TypeScript SDK's `packEncryptedPayload`, and the bytes
+/// that go into `client::compute_commitment`.
+///
+/// ```
+/// use private_state_toolkit::encryption::{encrypt, pack, unpack};
+///
+/// let (nonce, sealed) = encrypt(&[7u8; 32], b"state").unwrap();
+/// let packed = pack(&nonce, &sealed);
+/// assert_eq!(&packed[..12], &nonce);
+/// assert_eq!(&packed[12..28], &sealed[5..]);
+/// assert_eq!(unpack(&packed), Some((nonce, sealed)));
+/// ```
+pub fn pack(nonce: &[u8; AEAD_NONCE_LEN], sealed: &[u8]) -> Vec<u8> {
+    let split = sealed.len().saturating_sub(TAG_LEN);
+    let (ciphertext, tag) = sealed.split_at(split);
+    let mut packed = Vec::with_capacity(AEAD_NONCE_LEN + sealed.len());
+    packed.extend_from_slice(nonce);
+    packed.extend_from_slice(tag);
+    packed.extend_from_slice(ciphertext);
+    packed
+}
+
+/// Splits an SDK-packed payload back into `(nonce, ciphertext || tag)`.
+///
+/// Returns `None` if it is shorter than a nonce and a tag.
+pub fn unpack(packed: &[u8]) -> Option<([u8; AEAD_NONCE_LEN], Vec<u8>)> {
+    if packed.len() < AEAD_NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (nonce, rest) = packed.split_at(AEAD_NONCE_LEN);
+    let (tag, ciphertext) = rest.split_at(TAG_LEN);
+    let mut sealed = ciphertext.to_vec();
+    sealed.extend_from_slice(tag);
+    Some((nonce.try_into().ok()?, sealed))
+}
+
+/// Encrypts successive states of one account under one key.
+///
+/// Each payload is encrypted under `derive_nonce(epoch, nonce)` for the
+/// on-chain nonce it will be committed at. Seeded from the account with
+/// `for_state`, the cipher refuses any nonce at or below the one the chain
+/// has already accepted, so processes that share a key and read the account
+/// first never reuse a committed nonce. It also refuses one it has used
+/// itself, so a retried update cannot reuse one either.
+///
+/// ```
+/// use anchor_lang::AnchorDeserialize;
+/// use private_state_toolkit::encryption::{EncryptionError, StateCipher};
+/// use private_state_toolkit::{PrivateState, PRIVATE_STATE_SPACE};
+///
+/// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+/// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+/// state.nonce = 4;
+///
+/// let mut cipher = StateCipher::for_state([7u8; 32], &state).unwrap();
+/// assert_eq!(cipher.encrypt(4, b"count=4"), Err(EncryptionError::NonceReused));
+///
+/// let sealed = cipher.encrypt(5, b"count=5").unwrap();
+/// assert_eq!(cipher.decrypt(5, &sealed).unwrap(), b"count=5");
+/// assert_eq!(cipher.encrypt(5, b"retry"), Err(EncryptionError::NonceReused));
+///
+/// state.epoch = u64::from(u32::MAX) + 1;
+/// assert!(StateCipher::for_state([7u8; 32], &state).is_err());
+/// ```
+pub struct StateCipher {
+    key: [u8; KEY_LEN],
+    epoch: u32,
+    last_nonce: Option<u64>,
+}
+
+impl StateCipher {
+    /// Cipher for an account that does not exist yet (its initial commitment).
+    pub fn new(key: [u8; KEY_LEN]) -> Self {
+        Self {
+            key,
+            epoch: 0,
+            last_nonce: None,
+        }
+    }
+
+    /// Cipher for the next updates of `state`, refusing nonces it already accepted.
+    pub fn for_state(key: [u8; KEY_LEN], state: &PrivateState) -> Result<Self, EncryptionError> {
+        let epoch = u32::try_from(state.epoch).map_err(|_| EncryptionError::EpochOutOfRange)?;
+        Ok(Self {
+            key,
+            epoch,
+            last_nonce: Some(state.nonce),
+        })
+    }
+
+    /// Highest on-chain nonce that is committed or was encrypted for.
+    pub fn last_nonce(&self) -> Option<u64> {
+        self.last_nonce
+    }
+
+    /// Encrypts the state committed at on-chain `nonce`, returning `ciphertext || tag`.
+    pub fn encrypt(&mut self, nonce: u64, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if self.last_nonce.is_some_and(|last| nonce <= last) {
+            return Err(EncryptionError::NonceReused);
+        }
+        let sealed = encrypt_with_nonce(&self.key, &derive_nonce(self.epoch, nonce), plaintext)?;
+        self.last_nonce = Some(nonce);
+        Ok(sealed)
+    }
+
+    /// Decrypts the state committed at on-chain `nonce` in this cipher's epoch.
+    pub fn decrypt(&self, nonce: u64, sealed: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        decrypt(&self.key, &derive_nonce(self.epoch, nonce), sealed)
+    }
+}
@@ -11,22 +11,80 @@
 //! commitment = sha256(nonce || encrypted_payload)
 //! ```
 //!
+//! Accounts may instead declare keccak256 via `hash_algo`; the program records
+//! the agreed algorithm and only recomputes commitments in `verify_payload`.
+//!
 //! On-chain accounts store only:
+//! - version (1 byte)
 //! - authority (32 bytes)
 //! - commitment (32 bytes)
 //! - nonce (8 bytes)
 //! - policy (1 byte)
+//! - commitment_len (1 byte)
+//! - allow_reset (1 byte)
+//! - pending_authority (33 bytes)
+//! - bump (1 byte)
+//! - max_nonce (8 bytes)
+//! - min_update_slots (8 bytes)
+//! - last_update_slot (8 bytes)
+//! - frozen (1 byte)
+//! - delegate (33 bytes)
+//! - hash_algo (1 byte)
+//! - update_count (8 bytes)
+//! - epoch (8 bytes)
+//! - recent (128 bytes)
+//! - recent_head (1 byte)
+//! - window_start_slot (8 bytes)
+//! - updates_in_window (2 bytes)
+//! - max_updates_per_window (2 bytes)
+//! - window_len_slots (8 bytes)
+//! - quiet (1 byte)
+//! - expiry_slot (8 bytes)
+//! - sealed (1 byte)
+//! - fee_lamports (8 bytes)
+//! - fee_destination (32 bytes)
+//! - reject_noop (1 byte)
+//! - recovery (33 bytes)
+//! - inactivity_slots (8 bytes)
+//! - created_slot (8 bytes)
+//! - freeze_reason (1 byte)
+//! - treasury (32 bytes)
+//! - treasury_bps (2 bytes)
 //!
-//! Total: 81 bytes per account
+//! Total: 477 bytes per account (`PRIVATE_STATE_SPACE`)
 //!
 //! ## Features
 //!
 //! - **CPI Composability**: Other programs can validate state via `assert_state`
-//! - **Update Policies**: StrictSequential (turn-based) or AllowSkips (async/offline)
-//! - **Authority Transfer**: Change account ownership
+//! - **Deterministic Addresses**: Optional PDAs derived from authority + label
+//! - **Update Policies**: StrictSequential (turn-based), AllowSkips (async/offline) or
+//!   StrictSequentialWithCap (turn-based with a fixed number of turns)
+//! - **Authority Transfer**: Change account ownership, directly or via propose/accept
 //! - **Policy Changes**: Runtime update policy modification
+//! - **Freeze**: Pause all mutations during disputes while asserts keep working
+//! - **Guardians**: Optional M-of-N guardian set that can update the state
+//! - **Delegation**: A second key that may update, without transfer/policy powers
+//! - **Events**: Structured Anchor events for indexers alongside `msg!` logs
+//! - **Versioned Layout**: `migrate` upgrades older accounts in place
+//! - **Client Helpers**: Canonical off-chain commitment and AES-256-GCM code behind `client`
+//! - **Merkle Registry**: Many states as off-chain leaves under one on-chain root
+//! - **Instruction Builders**: `builders` assembles validation instructions for non-Anchor callers
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::instruction::{
+    get_stack_height, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT,
+};
+use anchor_lang::solana_program::sysvar::instructions::get_instruction_relative;
+use anchor_lang::Discriminator;
+
+pub mod builders;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "client")]
+pub mod encryption;
 
 declare_id!("4FeUYtneSbfieLwjUT1ceHtv8nDXFk2autCZFyDhpkeD");
 
@@ -39,7 +97,8 @@ pub mod private_state_toolkit {
     /// # Arguments
     ///
     /// * `initial_commitment` - SHA-256 hash of (nonce || encrypted_payload)
-    /// * `policy` - Update policy: 0 = StrictSequential, 1 = AllowSkips
+    /// * `policy` - Update policy (see `UpdatePolicy`)
+    /// * `options` - Remaining settings (see `InitOptions`)
     ///
     /// # Example Flow
     ///
@@ -48,19 +107,140 @@ pub mod private_state_toolkit {
     /// 3. Client calls this instruction with commitment
     /// 4. On-chain account stores: authority, commitment, nonce=0, policy
     pub fn initialize(
+        ctx: Context<Initialize>,
+        initial_commitment: [u8; 32],
+        policy: UpdatePolicy,
+        options: InitOptions,
+    ) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let account = ctx.accounts.private_state.key();
+        init_state(
+            &mut ctx.accounts.private_state,
+            account,
+            authority,
+            initial_commitment,
+            0,
+            policy as u8,
+            &options,
+        )
+    }
+
+    /// `initialize`, but starting the nonce at `initial_nonce` instead of 0.
+    ///
+    /// Imports an existing off-chain state history without replaying it: the
+    /// policy then applies from `initial_nonce` as usual (under StrictSequential
+    /// the next update must use `initial_nonce + 1`).
+    ///
+    /// Fails with `NonceExhaustionRisk` above `NONCE_CEILING`, and with
+    /// `MaxNonceReached` above `max_nonce` under StrictSequentialWithCap.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_commitment` - SHA-256 hash of (initial_nonce || encrypted_payload)
+    /// * `policy` - Update policy (see `UpdatePolicy`)
+    /// * `initial_nonce` - Nonce the imported state is at
+    /// * `options` - Remaining settings (see `InitOptions`)
+    pub fn initialize_at(
+        ctx: Context<Initialize>,
+        initial_commitment: [u8; 32],
+        policy: UpdatePolicy,
+        initial_nonce: u64,
+        options: InitOptions,
+    ) -> Result<()> {
+        require!(
+            initial_nonce <= NONCE_CEILING,
+            PrivateStateError::NonceExhaustionRisk
+        );
+        require!(
+            !matches!(policy, UpdatePolicy::StrictSequentialWithCap)
+                || initial_nonce <= options.max_nonce,
+            PrivateStateError::MaxNonceReached
+        );
+        let authority = ctx.accounts.authority.key();
+        let account = ctx.accounts.private_state.key();
+        init_state(
+            &mut ctx.accounts.private_state,
+            account,
+            authority,
+            initial_commitment,
+            initial_nonce,
+            policy as u8,
+            &options,
+        )
+    }
+
+    /// `initialize` with the policy as a raw byte, for older clients.
+    ///
+    /// Fails with `InvalidPolicy` unless `policy` is 0 = StrictSequential,
+    /// 1 = AllowSkips or 2 = StrictSequentialWithCap.
+    pub fn initialize_raw(
         ctx: Context<Initialize>,
         initial_commitment: [u8; 32],
         policy: u8,
+        options: InitOptions,
     ) -> Result<()> {
-        validate_policy(policy)?;
-        let state = &mut ctx.accounts.private_state;
-        state.authority = ctx.accounts.authority.key();
-        state.commitment = initial_commitment;
-        state.nonce = 0;
-        state.policy = policy;
+        let authority = ctx.accounts.authority.key();
+        let account = ctx.accounts.private_state.key();
+        init_state(
+            &mut ctx.accounts.private_state,
+            account,
+            authority,
+            initial_commitment,
+            0,
+            policy,
+            &options,
+        )
+    }
 
-        log_commitment(state.nonce, &state.commitment, state.policy);
-        Ok(())
+    /// Initializes a private state account at a PDA derived from authority + label.
+    ///
+    /// Seeds: `[b"pst", authority, label]`. Apps can re-derive a user's account
+    /// from the wallet and a namespace label instead of storing a random pubkey.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - 16-byte namespace label chosen by the app (used only in the seeds)
+    /// * `initial_commitment` - SHA-256 hash of (nonce || encrypted_payload)
+    /// * `policy` - Update policy: 0 = StrictSequential, 1 = AllowSkips,
+    ///   2 = StrictSequentialWithCap
+    /// * `options` - Remaining settings (see `InitOptions`)
+    ///
+    /// Fails with `AlreadyInitialized` if the PDA already holds an account, so
+    /// a retried initialization gets a clear error instead of the system
+    /// program's. There is deliberately no `init_if_needed` path.
+    pub fn initialize_pda(
+        ctx: Context<InitializePda>,
+        label: [u8; 16],
+        initial_commitment: [u8; 32],
+        policy: u8,
+        options: InitOptions,
+    ) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let bump = ctx.bumps.private_state;
+        let seeds: &[&[u8]] = &[b"pst", authority.as_ref(), label.as_ref(), &[bump]];
+        create_pda_account(
+            &ctx.accounts.authority,
+            &ctx.accounts.private_state,
+            &ctx.accounts.system_program,
+            PRIVATE_STATE_SPACE,
+            seeds,
+        )?;
+
+        // The new account is all zeros until init_state fills it in
+        let info = &ctx.accounts.private_state;
+        let mut state = PrivateState::deserialize(&mut &info.try_borrow_data()?[8..])?;
+        state.bump = bump;
+        init_state(
+            &mut state,
+            info.key(),
+            authority,
+            initial_commitment,
+            0,
+            policy,
+            &options,
+        )?;
+        let mut data = info.try_borrow_mut_data()?;
+        state.try_serialize(&mut &mut data[..])
     }
 
     /// Updates the private state with a new commitment.
@@ -74,11 +254,35 @@ pub mod private_state_toolkit {
     /// * `old_commitment` - Current commitment stored on-chain (must match)
     /// * `new_commitment` - New commitment to store
     /// * `next_nonce` - New nonce value (must satisfy policy)
+    /// * `memo` - Optional correlation tag (e.g. a hash of an off-chain request
+    ///   id), emitted in `StateUpdated` but never stored or validated
     ///
     /// # Policy Validation
     ///
     /// - **StrictSequential**: `next_nonce` must equal `current_nonce + 1`
+    ///   (`NonceOverflow` if `current_nonce` is `u64::MAX`)
     /// - **AllowSkips**: `next_nonce` must be greater than `current_nonce`
+    /// - **StrictSequentialWithCap**: as StrictSequential, but fails with
+    ///   `MaxNonceReached` once `current_nonce` reaches `max_nonce`
+    ///
+    /// Under every policy `next_nonce` must not exceed `NONCE_CEILING`
+    /// (`NonceExhaustionRisk`).
+    ///
+    /// # Health Warning
+    ///
+    /// If the account is close to its nonce cap, rate limit or expiry after the
+    /// update (see `PrivateState::health_conditions`), `StateHealthWarning` is
+    /// emitted alongside `StateUpdated`. Every instruction that advances the
+    /// nonce does the same.
+    ///
+    /// # Update Delay
+    ///
+    /// Fails with `UpdateTooSoon` unless at least `min_update_slots` slots have
+    /// passed since the last update (or since initialization, for the first one).
+    ///
+    /// Accounts with a non-zero `fee_lamports` charge the authority that fee,
+    /// paid to `fee_destination` (see `set_fee`). With `reject_noop` set, an
+    /// unchanged commitment fails with `NoOpUpdate` (see `set_reject_noop`).
     ///
     /// # Security
     ///
@@ -89,300 +293,5655 @@ pub mod private_state_toolkit {
         old_commitment: [u8; 32],
         new_commitment: [u8; 32],
         next_nonce: u64,
+        memo: Option<[u8; 32]>,
     ) -> Result<()> {
+        collect_update_fee(ctx.accounts)?;
         let state = &mut ctx.accounts.private_state;
-
-        // Verify caller knows the current state by checking commitment
-        require!(
-            state.commitment == old_commitment,
-            PrivateStateError::CommitmentMismatch
-        );
-
-        // Enforce nonce rules based on the account's policy
-        match UpdatePolicy::try_from(state.policy)? {
-            UpdatePolicy::StrictSequential => {
-                // Turn-based: nonce must increment by exactly 1
-                require!(
-                    next_nonce == state.nonce.saturating_add(1),
-                    PrivateStateError::NonceNotSequential
-                );
-            }
-            UpdatePolicy::AllowSkips => {
-                // Async-friendly: nonce just needs to increase
-                require!(
-                    next_nonce > state.nonce,
-                    PrivateStateError::NonceNotMonotonic
-                );
-            }
-        }
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_new_commitment(state, &new_commitment)?;
 
         // Update on-chain state
         state.commitment = new_commitment;
         state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
 
-        log_commitment(state.nonce, &state.commitment, state.policy);
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo,
+        });
         Ok(())
     }
 
-    /// Transfers authority of the private state account to a new owner.
+    /// Like `update`, but also requires the caller's view of the current nonce.
+    ///
+    /// Fails with `CurrentNonceMismatch` unless `expected_current_nonce` equals
+    /// the stored nonce. Commitments can legitimately repeat, so a desynced
+    /// client can match `old_commitment` while believing it is at a different
+    /// nonce; this catches that before the transition is applied.
     ///
     /// # Arguments
     ///
-    /// * `new_authority` - Public key of the new authority
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    /// * `expected_current_nonce` - Nonce the caller believes is stored (must match)
+    pub fn update_checked(
+        ctx: Context<Update>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+        expected_current_nonce: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.private_state.nonce == expected_current_nonce,
+            PrivateStateError::CurrentNonceMismatch
+        );
+        update(ctx, old_commitment, new_commitment, next_nonce, None)
+    }
+
+    /// Like `update`, but bound to a tag over the exact transition.
     ///
-    /// # Use Cases
+    /// `auth_tag` must equal `update_auth_tag(private_state, new_commitment,
+    /// next_nonce)`:
     ///
-    /// - Transfer ownership between users
-    /// - Upgrade to multi-sig authority
-    /// - Transfer to a program-derived address (PDA)
-    pub fn transfer_authority(
-        ctx: Context<TransferAuthority>,
-        new_authority: Pubkey,
+    /// ```text
+    /// auth_tag = sha256(private_state (32 bytes) || new_commitment (32 bytes)
+    ///                   || next_nonce (u64 LE, 8 bytes))
+    /// ```
+    ///
+    /// A program signing for a PDA authority computes the tag from the update
+    /// it means to authorize, so accounts or arguments swapped elsewhere in the
+    /// transaction fail with `AuthTagMismatch`. The tag is logged for audits.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    /// * `auth_tag` - Binding tag over the transition (see above)
+    pub fn update_bound(
+        ctx: Context<Update>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+        auth_tag: [u8; 32],
     ) -> Result<()> {
-        let state = &mut ctx.accounts.private_state;
-        state.authority = new_authority;
-        Ok(())
+        let expected = update_auth_tag(
+            &ctx.accounts.private_state.key(),
+            &new_commitment,
+            next_nonce,
+        );
+        require!(
+            constant_time_eq(&expected, &auth_tag),
+            PrivateStateError::AuthTagMismatch
+        );
+        msg!("auth_tag: {}", to_hex(&auth_tag));
+        update(ctx, old_commitment, new_commitment, next_nonce, None)
     }
 
-    /// Changes the update policy at runtime.
+    /// Re-randomizes the commitment without changing the underlying content.
     ///
-    /// # Arguments
+    /// The client recomputes the commitment over the same plaintext with a fresh
+    /// blinding factor (e.g. a new AES-GCM IV), so two observations of the same
+    /// state look unrelated. The program cannot verify that the content is
+    /// unchanged; this instruction standardizes the operation and labels it as a
+    /// reblind (`StateUpdated { reblind: true }`) so indexers can tell it apart
+    /// from a content change.
     ///
-    /// * `policy` - New policy: 0 = StrictSequential, 1 = AllowSkips
+    /// # Arguments
     ///
-    /// # Use Cases
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - Re-blinded commitment (must differ from the old one)
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    pub fn reblind(
+        ctx: Context<Update>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+    ) -> Result<()> {
+        collect_update_fee(ctx.accounts)?;
+        let state = &mut ctx.accounts.private_state;
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_commitment_padding(&new_commitment, state.commitment_len)?;
+        require!(
+            new_commitment != old_commitment,
+            PrivateStateError::ReblindUnchanged
+        );
+
+        state.commitment = new_commitment;
+        state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
+
+        msg!("reblind");
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: true,
+            memo: None,
+        });
+        Ok(())
+    }
+
+    /// Advances the nonce by one without changing the commitment (a heartbeat).
     ///
-    /// - Switch from strict mode to allow offline updates
-    /// - Tighten policy after initial flexible setup
-    /// - Adapt to changing application requirements
-    pub fn set_policy(ctx: Context<SetPolicy>, policy: u8) -> Result<()> {
-        validate_policy(policy)?;
+    /// Proves the authority still holds its key and refreshes
+    /// `last_update_slot`, subject to the same policy, delay, rate limit and
+    /// fee as `update`. The deliberate counterpart to `reject_noop`: emits
+    /// `StateTouched` instead of `StateUpdated`, so consumers can tell
+    /// liveness beats from real changes.
+    pub fn touch(ctx: Context<Update>) -> Result<()> {
+        collect_update_fee(ctx.accounts)?;
         let state = &mut ctx.accounts.private_state;
-        let old_policy = state.policy;
-        state.policy = policy;
-        msg!("policy: {} -> {}", old_policy, policy);
+        let next_nonce = sequential_nonce(state.nonce)?;
+        let current = state.commitment;
+        validate_transition(state, &current, next_nonce)?;
+        stamp_update_slot(state)?;
+        state.nonce = next_nonce;
+        state.update_count = state.update_count.saturating_add(1);
+
+        finish_update(state);
+        emit!(StateTouched {
+            account: state.key(),
+            commitment: state.commitment,
+            nonce: state.nonce,
+            slot: state.last_update_slot,
+        });
         Ok(())
     }
 
-    /// Validates that a private state account matches expected commitment and nonce.
+    /// Switches the update policy and applies an update in one instruction.
     ///
-    /// **This is the CPI composability hook.** Other programs can call this instruction
-    /// via Cross-Program Invocation (CPI) to gate actions on private state freshness
-    /// without needing the encryption key or seeing the plaintext.
+    /// Avoids the half-configured window between a separate `set_policy` and
+    /// `update`. The nonce rule is enforced against `new_policy`, so switching
+    /// to AllowSkips lets this very update jump ahead.
     ///
     /// # Arguments
     ///
-    /// * `expected_commitment` - The commitment value to check
-    /// * `expected_nonce` - The nonce value to check
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy `new_policy`)
+    /// * `new_policy` - Policy to apply before validating the nonce
+    pub fn update_with_policy(
+        ctx: Context<Update>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+        new_policy: u8,
+    ) -> Result<()> {
+        validate_policy(new_policy)?;
+        collect_update_fee(ctx.accounts)?;
+        let state = &mut ctx.accounts.private_state;
+        require!(
+            !state.sealed || new_policy == state.policy,
+            PrivateStateError::StateSealed
+        );
+        let old_policy = state.policy;
+        state.policy = new_policy;
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_new_commitment(state, &new_commitment)?;
+
+        state.commitment = new_commitment;
+        state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
+
+        if old_policy != new_policy {
+            msg!("policy: {} -> {}", old_policy, new_policy);
+            emit!(PolicyChanged {
+                account: state.key(),
+                old_policy,
+                new_policy,
+            });
+        }
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo: None,
+        });
+        Ok(())
+    }
+
+    /// Updates the state, enforcing that the new commitment extends the old one.
     ///
-    /// # Design Properties
+    /// Requires `sha256(old_commitment || next_nonce (u64 LE) || link_proof)` to
+    /// equal `new_commitment`, so the history forms a hash chain. An auditor
+    /// replaying `StateUpdated` events can detect a forked or rewritten history;
+    /// the plaintext stays off-chain.
     ///
-    /// - **Read-only**: Does not mutate state (cheap, safe for CPI)
-    /// - **Deterministic**: Same inputs always produce same result
-    /// - **No decryption**: Caller doesn't need encryption key
+    /// # Arguments
     ///
-    /// # Example CPI Usage
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment (must equal the chained hash)
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    /// * `link_proof` - Client-chosen 32 bytes mixed into the chain link
     ///
-    /// ```rust,ignore
-    /// let cpi_ctx = CpiContext::new(pst_program, AssertState { private_state });
-    /// private_state_toolkit::cpi::assert_state(cpi_ctx, commitment, nonce)?;
-    /// // If we reach here, the state is valid - proceed with gated action
-    /// ```
-    pub fn assert_state(
-        ctx: Context<AssertState>,
-        expected_commitment: [u8; 32],
-        expected_nonce: u64,
+    /// For accounts with a shortened `commitment_len`, only that prefix of the
+    /// chained hash is compared.
+    pub fn update_chained(
+        ctx: Context<Update>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+        link_proof: [u8; 32],
     ) -> Result<()> {
-        let state = &ctx.accounts.private_state;
+        collect_update_fee(ctx.accounts)?;
+        let state = &mut ctx.accounts.private_state;
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_new_commitment(state, &new_commitment)?;
 
-        // Verify commitment matches
+        let link = hashv(&[&old_commitment, &next_nonce.to_le_bytes(), &link_proof]).to_bytes();
+        let len = state.commitment_len as usize;
         require!(
-            state.commitment == expected_commitment,
-            PrivateStateError::CommitmentMismatch
+            link[..len] == new_commitment[..len],
+            PrivateStateError::ChainLinkMismatch
         );
 
-        // Verify nonce matches
-        require!(
-            state.nonce == expected_nonce,
-            PrivateStateError::NonceMismatch
-        );
+        state.commitment = new_commitment;
+        state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
 
-        log_commitment(state.nonce, &state.commitment, state.policy);
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo: None,
+        });
         Ok(())
     }
-}
 
-// ============================================================================
-// Account Structures
-// ============================================================================
+    /// Updates the state, signed by either the authority or its delegate.
+    ///
+    /// Identical to `update` except for who may sign, and the update fee (if
+    /// any) is paid by the signer. This is the only mutation a delegate can
+    /// perform; transfers, policy changes and `set_delegate` still require the
+    /// authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    pub fn update_as_delegate(
+        ctx: Context<UpdateAsDelegate>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+    ) -> Result<()> {
+        charge_update_fee(
+            &ctx.accounts.private_state,
+            &ctx.accounts.updater.to_account_info(),
+            ctx.accounts.fee_destination.as_ref(),
+            ctx.accounts.system_program.as_ref(),
+        )?;
+        let state = &mut ctx.accounts.private_state;
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_new_commitment(state, &new_commitment)?;
 
-/// The on-chain private state account.
-///
-/// **Total size: 81 bytes** (8-byte discriminator + 73 bytes data)
-///
-/// This is the only data stored on-chain. The actual encrypted application
-/// state lives off-chain with the client.
-#[account]
-pub struct PrivateState {
-    /// Authority that can update this account (32 bytes)
-    pub authority: Pubkey,
+        state.commitment = new_commitment;
+        state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
 
-    /// SHA-256 commitment hash (32 bytes)
-    /// Computed as: sha256(nonce || encrypted_payload)
-    pub commitment: [u8; 32],
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo: None,
+        });
+        Ok(())
+    }
 
-    /// Monotonically increasing nonce (8 bytes)
-    /// Prevents replay attacks and ensures ordering
-    pub nonce: u64,
+    /// Applies several commitment transitions atomically.
+    ///
+    /// Lets a client flush accumulated offline changes in one transaction. Each
+    /// transition is checked against the policy in turn, starting from
+    /// `old_commitment`; the last one becomes the stored state. The batch counts
+    /// as a single update for `min_update_slots`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `transitions` - `(new_commitment, nonce)` pairs in order (at most
+    ///   `MAX_BATCH_TRANSITIONS`)
+    ///
+    /// Emits one `StateUpdated` event per applied transition.
+    pub fn batch_update(
+        ctx: Context<Update>,
+        old_commitment: [u8; 32],
+        transitions: Vec<Transition>,
+    ) -> Result<()> {
+        require!(!transitions.is_empty(), PrivateStateError::EmptyBatch);
+        require!(
+            transitions.len() <= MAX_BATCH_TRANSITIONS,
+            PrivateStateError::BatchTooLarge
+        );
 
-    /// Update policy (1 byte)
-    /// 0 = StrictSequential, 1 = AllowSkips
-    pub policy: u8,
-}
+        collect_update_fee(ctx.accounts)?;
+        let state = &mut ctx.accounts.private_state;
+        stamp_update_slot(state)?;
 
-// ============================================================================
-// Instruction Contexts
-// ============================================================================
+        let mut previous = old_commitment;
+        for transition in &transitions {
+            validate_transition(state, &previous, transition.nonce)?;
+            validate_new_commitment(state, &transition.new_commitment)?;
 
-/// Accounts for the initialize instruction.
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    /// The private state account to create
-    /// Space: 8 (discriminator) + 32 (authority) + 32 (commitment) + 8 (nonce) + 1 (policy)
-    #[account(init, payer = authority, space = 8 + 32 + 32 + 8 + 1)]
-    pub private_state: Account<'info, PrivateState>,
+            state.commitment = transition.new_commitment;
+            state.nonce = transition.nonce;
+            push_recent(state, transition.new_commitment);
+            state.update_count = state.update_count.saturating_add(1);
 
-    /// The authority who owns this account (pays for creation)
-    #[account(mut)]
-    pub authority: Signer<'info>,
+            emit!(StateUpdated {
+                account: state.key(),
+                old_commitment: previous,
+                new_commitment: state.commitment,
+                nonce: state.nonce,
+                policy: state.policy,
+                reblind: false,
+                memo: None,
+            });
+            previous = transition.new_commitment;
+        }
 
-    /// System program for account creation
-    pub system_program: Program<'info, System>,
-}
+        finish_update(state);
+        Ok(())
+    }
 
-/// Accounts for the update instruction.
-#[derive(Accounts)]
-pub struct Update<'info> {
-    /// The private state account to update
-    /// has_one = authority ensures only the authority can update
-    #[account(mut, has_one = authority)]
+    /// Updates the state with the approval of a (weighted) guardian set.
+    ///
+    /// Same checks as `update`, but instead of the authority's signature it needs
+    /// distinct guardians (from the account's `GuardianSet`) whose weights add up
+    /// to at least `weight_threshold`, signing as remaining accounts. With unit
+    /// weights this is plain M-of-N. Signers outside the set are ignored. On an
+    /// account that charges a fee, `fee_payer` must sign and pays it.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    ///
+    /// # Remaining Accounts
+    ///
+    /// The guardian signers (at most `MAX_GUARDIANS`, no duplicates).
+    pub fn multisig_update(
+        ctx: Context<MultisigUpdate>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_GUARDIANS,
+            PrivateStateError::BatchTooLarge
+        );
+        let guardian_set = &ctx.accounts.guardian_set;
+        // At most MAX_GUARDIANS weights of u16::MAX each, so a u32 sum cannot overflow
+        let mut approvals = 0u32;
+        for (i, info) in ctx.remaining_accounts.iter().enumerate() {
+            require!(info.is_signer, ErrorCode::AccountNotSigner);
+            require!(
+                ctx.remaining_accounts[..i].iter().all(|prev| prev.key != info.key),
+                PrivateStateError::DuplicateAccount
+            );
+            if let Some(guardian) = guardian_set.guardians.iter().find(|g| g.key == *info.key) {
+                approvals += u32::from(guardian.weight);
+            }
+        }
+        require!(
+            approvals >= u32::from(guardian_set.weight_threshold),
+            PrivateStateError::GuardianThresholdNotMet
+        );
+
+        if ctx.accounts.private_state.fee_lamports > 0 {
+            let Some(fee_payer) = &ctx.accounts.fee_payer else {
+                return err!(ErrorCode::AccountNotEnoughKeys);
+            };
+            charge_update_fee(
+                &ctx.accounts.private_state,
+                &fee_payer.to_account_info(),
+                ctx.accounts.fee_destination.as_ref(),
+                ctx.accounts.system_program.as_ref(),
+            )?;
+        }
+        let state = &mut ctx.accounts.private_state;
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_new_commitment(state, &new_commitment)?;
+
+        state.commitment = new_commitment;
+        state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
+
+        msg!(
+            "multisig approvals: {}/{}",
+            approvals,
+            guardian_set.weight_threshold
+        );
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo: None,
+        });
+        Ok(())
+    }
+
+    /// Updates the state on behalf of one of several offline devices.
+    ///
+    /// A scalar nonce can't tell concurrent edits from stale ones, so the
+    /// account also keeps a vector clock (`CausalClock` PDA, created on first
+    /// use). `device_clock` must not be dominated by the stored clock: an update
+    /// whose counters are all at or below the stored ones fails with
+    /// `CausalConflict`, while dominating or concurrent clocks are accepted and
+    /// merged (see `merge_device_clocks`). The nonce rules of `update` still
+    /// apply, so multi-device accounts usually run under AllowSkips.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_commitment` - Current commitment stored on-chain (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    /// * `device_clock` - The editing device's view of every device's counter
+    pub fn update_causal(
+        ctx: Context<UpdateCausal>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+        device_clock: Vec<DeviceClock>,
+    ) -> Result<()> {
+        let private_state = ctx.accounts.private_state.key();
+        let stored = if ctx.accounts.causal_clock.data_is_empty() {
+            let seeds: &[&[u8]] = &[
+                b"causal_clock",
+                private_state.as_ref(),
+                &[ctx.bumps.causal_clock],
+            ];
+            create_pda_account(
+                &ctx.accounts.authority,
+                &ctx.accounts.causal_clock,
+                &ctx.accounts.system_program,
+                CAUSAL_CLOCK_SPACE,
+                seeds,
+            )?;
+            Vec::new()
+        } else {
+            require_keys_eq!(
+                *ctx.accounts.causal_clock.owner,
+                crate::ID,
+                ErrorCode::ConstraintOwner
+            );
+            let data = ctx.accounts.causal_clock.try_borrow_data()?;
+            CausalClock::try_deserialize(&mut &data[..])?.device_clocks
+        };
+        let device_clocks = merge_device_clocks(&stored, &device_clock)?;
+
+        charge_update_fee(
+            &ctx.accounts.private_state,
+            &ctx.accounts.authority.to_account_info(),
+            ctx.accounts.fee_destination.as_ref(),
+            Some(&ctx.accounts.system_program),
+        )?;
+        let state = &mut ctx.accounts.private_state;
+        validate_transition(state, &old_commitment, next_nonce)?;
+        stamp_update_slot(state)?;
+        validate_new_commitment(state, &new_commitment)?;
+
+        state.commitment = new_commitment;
+        state.nonce = next_nonce;
+        push_recent(state, new_commitment);
+        state.update_count = state.update_count.saturating_add(1);
+
+        let clock = CausalClock {
+            private_state,
+            device_clocks,
+        };
+        let mut data = ctx.accounts.causal_clock.try_borrow_mut_data()?;
+        clock.try_serialize(&mut &mut data[..])?;
+
+        msg!("causal update: {} devices", clock.device_clocks.len());
+        finish_update(state);
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo: None,
+        });
+        Ok(())
+    }
+
+    /// Transfers authority of the private state account to a new owner.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_authority` - Public key of the new authority
+    ///
+    /// # Use Cases
+    ///
+    /// - Transfer ownership between users
+    /// - Upgrade to multi-sig authority
+    /// - Transfer to a program-derived address (PDA)
+    pub fn transfer_authority(
+        ctx: Context<TransferAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        let old_authority = state.authority;
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Transfers authority only if it is still held by `expected_current`.
+    ///
+    /// For multi-party handoffs where transfers may be submitted concurrently:
+    /// the swap fails with `AuthorityMismatch` instead of acting on an account
+    /// whose ownership already moved.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_current` - Authority the caller believes holds the account
+    /// * `new_authority` - Public key of the new authority
+    pub fn compare_and_swap_authority(
+        ctx: Context<TransferAuthority>,
+        expected_current: Pubkey,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        require_keys_eq!(
+            state.authority,
+            expected_current,
+            PrivateStateError::AuthorityMismatch
+        );
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority: expected_current,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Hands the account to `new_authority` on behalf of a lost authority key.
+    ///
+    /// Callable only by the `recovery` key set at initialization, and only once
+    /// more than `inactivity_slots` slots have passed since the last update, so
+    /// the recovery key can't seize an account that is still in use
+    /// (`AccountStillActive`). Like every authority change, this clears the
+    /// recovery key; the new authority designates its own with `set_recovery`.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_authority` - Public key of the new authority
+    pub fn recover_authority(ctx: Context<RecoverAuthority>, new_authority: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        let idle = Clock::get()?.slot.saturating_sub(state.last_update_slot);
+        require!(
+            idle > state.inactivity_slots,
+            PrivateStateError::AccountStillActive
+        );
+
+        let old_authority = state.authority;
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+        msg!("recovered after {} idle slots", idle);
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Transfers authority to a program-derived address, verifying it first.
+    ///
+    /// Recomputes the address with `Pubkey::create_program_address(seeds ||
+    /// [bump], program_id)` and fails with `InvalidPda` if that is not a valid
+    /// PDA, so authority can't be handed to an address no program can sign for.
+    ///
+    /// # Arguments
+    ///
+    /// * `program_id` - Program that will sign for the PDA
+    /// * `seeds` - PDA seeds, excluding the bump
+    /// * `bump` - Bump seed appended to `seeds`
+    pub fn transfer_authority_to_pda(
+        ctx: Context<TransferAuthority>,
+        program_id: Pubkey,
+        seeds: Vec<Vec<u8>>,
+        bump: u8,
+    ) -> Result<()> {
+        let new_authority = derive_pda(&program_id, &seeds, bump)?;
+
+        let state = &mut ctx.accounts.private_state;
+        let old_authority = state.authority;
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+
+        msg!("authority transferred to pda: {}", new_authority);
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Hands authority from a program-derived address back to `new_authority`.
+    ///
+    /// The reverse of `transfer_authority_to_pda`. The PDA authority must sign,
+    /// which only its owning program can do (via `invoke_signed` with the same
+    /// seeds), and the seeds must re-derive the stored authority under
+    /// `program_id`, otherwise this fails with `InvalidPda`.
+    ///
+    /// `pst_consumer::release_custody` shows the owning program's side.
+    ///
+    /// # Arguments
+    ///
+    /// * `program_id` - Program that signs for the PDA
+    /// * `seeds` - PDA seeds, excluding the bump
+    /// * `bump` - Bump seed appended to `seeds`
+    /// * `new_authority` - Public key of the new authority (e.g. a wallet)
+    pub fn transfer_authority_from_pda(
+        ctx: Context<TransferAuthority>,
+        program_id: Pubkey,
+        seeds: Vec<Vec<u8>>,
+        bump: u8,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let pda = derive_pda(&program_id, &seeds, bump)?;
+        let state = &mut ctx.accounts.private_state;
+        require_keys_eq!(state.authority, pda, PrivateStateError::InvalidPda);
+
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+
+        msg!("authority released from pda: {}", pda);
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority: pda,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Proposes a new authority (step 1 of a two-step transfer).
+    ///
+    /// Nothing changes hands until the proposed key signs
+    /// `accept_authority_transfer`, so a mistyped pubkey can't brick the account.
+    /// Proposing again replaces any earlier proposal.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_authority` - Public key that may accept the transfer
+    pub fn propose_authority_transfer(
+        ctx: Context<TransferAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.pending_authority = Some(new_authority);
+        msg!("authority transfer proposed: {}", new_authority);
+        Ok(())
+    }
+
+    /// Accepts a proposed transfer (step 2), signed by the pending authority.
+    ///
+    /// Promotes `pending_authority` to `authority` and clears the proposal.
+    pub fn accept_authority_transfer(ctx: Context<AcceptAuthorityTransfer>) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        let old_authority = state.authority;
+        let new_authority = ctx.accounts.new_authority.key();
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority,
+            new_authority,
+        });
+        Ok(())
+    }
+
+    /// Cancels a pending authority transfer (current authority only).
+    pub fn cancel_authority_transfer(ctx: Context<TransferAuthority>) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        require!(
+            state.pending_authority.is_some(),
+            PrivateStateError::NoPendingAuthority
+        );
+        state.pending_authority = None;
+        msg!("authority transfer cancelled");
+        Ok(())
+    }
+
+    /// Advances the nonce of a group of related accounts by one, in lockstep.
+    ///
+    /// Commitments are left unchanged. Use this to keep linked sub-states of one
+    /// logical entity at the same nonce height; if any account cannot advance,
+    /// the whole group reverts. Each account's update fee is charged to the
+    /// authority, so fee-charging accounts in one group must share the passed
+    /// `fee_destination`.
+    ///
+    /// # Remaining Accounts
+    ///
+    /// The writable `PrivateState` accounts to bump (at most `MAX_BATCH_ACCOUNTS`),
+    /// each owned by this program and belonging to `authority`.
+    pub fn bump_group<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BumpGroup<'info>>,
+    ) -> Result<()> {
+        validate_batch(ctx.remaining_accounts)?;
+
+        let authority = ctx.accounts.authority.key();
+        for info in ctx.remaining_accounts {
+            let mut state = load_authorized_state(info, &authority)?;
+            charge_update_fee(
+                &state,
+                &ctx.accounts.authority.to_account_info(),
+                ctx.accounts.fee_destination.as_ref(),
+                ctx.accounts.system_program.as_ref(),
+            )?;
+            let next_nonce = sequential_nonce(state.nonce)?;
+            let current = state.commitment;
+            validate_transition(&state, &current, next_nonce)?;
+            stamp_update_slot(&mut state)?;
+            state.nonce = next_nonce;
+            state.update_count = state.update_count.saturating_add(1);
+
+            finish_update(&state);
+            emit!(StateUpdated {
+                account: state.key(),
+                old_commitment: current,
+                new_commitment: current,
+                nonce: state.nonce,
+                policy: state.policy,
+                reblind: false,
+                memo: None,
+            });
+            state.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// Resets the state to a fresh commitment at nonce 0, keeping the account.
+    ///
+    /// Closing and recreating would change the pubkey consumers reference; this
+    /// wipes the state in place instead. `epoch` is incremented so consumers
+    /// relying on a monotonic nonce can tell that the nonce sequence restarted
+    /// (see `assert_state_with_epoch`).
+    ///
+    /// # Arguments
+    ///
+    /// * `new_commitment` - Fresh commitment at nonce 0
+    ///
+    /// Fails with `ResetNotAllowed` if resets are disabled via `set_allow_reset`.
+    pub fn reset(ctx: Context<Reset>, new_commitment: [u8; 32]) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        require!(state.allow_reset, PrivateStateError::ResetNotAllowed);
+        validate_commitment_padding(&new_commitment, state.commitment_len)?;
+
+        let old_commitment = state.commitment;
+        state.commitment = new_commitment;
+        state.nonce = 0;
+        state.epoch = state.epoch.saturating_add(1);
+        reset_recent(state, new_commitment);
+
+        msg!("reset to epoch: {}", state.epoch);
+        log_state(state);
+        emit!(StateReset {
+            account: state.key(),
+            old_commitment,
+            new_commitment,
+            epoch: state.epoch,
+        });
+        Ok(())
+    }
+
+    /// Transfers authority and resets the state for a clean handoff.
+    ///
+    /// Atomically sets `authority = new_authority`, `commitment = initial_commitment`
+    /// and `nonce = 0`, so the new owner does not inherit the previous owner's
+    /// nonce height or commitment. Like `reset`, this starts a new `epoch`.
+    /// Signed by the current authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_authority` - Public key of the new authority
+    /// * `initial_commitment` - Fresh commitment for the new owner at nonce 0
+    ///
+    /// # Opt-out
+    ///
+    /// Fails with `ResetNotAllowed` if the account has disabled resets via
+    /// `set_allow_reset(false)`.
+    pub fn transfer_and_reset(
+        ctx: Context<TransferAuthority>,
+        new_authority: Pubkey,
+        initial_commitment: [u8; 32],
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        require!(state.allow_reset, PrivateStateError::ResetNotAllowed);
+        require!(!state.sealed, PrivateStateError::StateSealed);
+        validate_commitment_padding(&initial_commitment, state.commitment_len)?;
+
+        let old_authority = state.authority;
+        let old_commitment = state.commitment;
+        state.authority = new_authority;
+        state.pending_authority = None;
+        state.delegate = None;
+        state.recovery = None;
+        state.commitment = initial_commitment;
+        state.nonce = 0;
+        state.epoch = state.epoch.saturating_add(1);
+        reset_recent(state, initial_commitment);
+
+        msg!("authority reset to: {}", new_authority);
+        log_state(state);
+        emit!(AuthorityTransferred {
+            account: state.key(),
+            old_authority,
+            new_authority,
+        });
+        emit!(StateUpdated {
+            account: state.key(),
+            old_commitment,
+            new_commitment: initial_commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            reblind: false,
+            memo: None,
+        });
+        emit!(StateReset {
+            account: state.key(),
+            old_commitment,
+            new_commitment: initial_commitment,
+            epoch: state.epoch,
+        });
+        Ok(())
+    }
+
+    /// Lets a second key submit updates via `update_as_delegate`.
+    ///
+    /// Replaces any existing delegate. Cleared automatically when authority
+    /// changes hands.
+    ///
+    /// # Arguments
+    ///
+    /// * `delegate` - Key allowed to update (must not be the default pubkey)
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Pubkey) -> Result<()> {
+        require_keys_neq!(delegate, Pubkey::default(), PrivateStateError::InvalidDelegate);
+        require!(
+            !ctx.accounts.private_state.sealed,
+            PrivateStateError::StateSealed
+        );
+        ctx.accounts.private_state.delegate = Some(delegate);
+        msg!("delegate set: {}", delegate);
+        Ok(())
+    }
+
+    /// Removes the delegate, if any.
+    ///
+    /// Still allowed on sealed accounts, since it only narrows who can update.
+    pub fn revoke_delegate(ctx: Context<SetDelegate>) -> Result<()> {
+        ctx.accounts.private_state.delegate = None;
+        msg!("delegate revoked");
+        Ok(())
+    }
+
+    /// Permanently seals the account's configuration.
+    ///
+    /// One-way: afterwards `set_policy` (and other policy changes), `set_delegate`,
+    /// `set_guardians`, `set_weighted_guardians`, `set_commitment_len`,
+    /// `set_allow_reset`, `set_rate_limit`, `set_allowed_callers`, `set_expiry`,
+    /// `set_fee`, `set_close_split`, `set_recovery`, `reset` and
+    /// `transfer_and_reset` fail with `StateSealed`, giving consumers a fixed set of rules for the
+    /// account's remaining life. Updates and authority transfers keep working.
+    pub fn seal(ctx: Context<Seal>) -> Result<()> {
+        ctx.accounts.private_state.sealed = true;
+        msg!("sealed");
+        Ok(())
+    }
+
+    /// Freezes the account, blocking every mutation until `unfreeze`.
+    ///
+    /// While frozen, updates, policy changes and authority transfers fail with
+    /// `StateFrozen`. `assert_state` and the other read-only asserts keep working,
+    /// so CPI gating is unaffected while a dispute is arbitrated off-chain.
+    ///
+    /// `freeze_reason` is an operator-defined category (dispute, compliance,
+    /// compromise, ...). The program stores it and emits it in `StateFrozen`
+    /// without interpreting it.
+    pub fn freeze(ctx: Context<SetFrozen>, freeze_reason: u8) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.frozen = true;
+        state.freeze_reason = freeze_reason;
+        msg!("frozen: reason {}", freeze_reason);
+        emit!(StateFrozen {
+            account: state.key(),
+            reason: freeze_reason,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    /// Lifts a freeze set by `freeze` and clears its reason.
+    pub fn unfreeze(ctx: Context<SetFrozen>) -> Result<()> {
+        ctx.accounts.private_state.frozen = false;
+        ctx.accounts.private_state.freeze_reason = 0;
+        msg!("unfrozen");
+        Ok(())
+    }
+
+    /// Enables or disables resets (`reset`, `transfer_and_reset`) for this account.
+    ///
+    /// Resets are allowed by default; accounts whose consumers rely on a
+    /// never-decreasing nonce can opt out.
+    ///
+    /// # Arguments
+    ///
+    /// * `allow_reset` - Whether reset operations are permitted
+    pub fn set_allow_reset(ctx: Context<SetAllowReset>, allow_reset: bool) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.allow_reset = allow_reset;
+        msg!("allow_reset: {}", allow_reset);
+        Ok(())
+    }
+
+    /// Enables or disables quiet mode for this account.
+    ///
+    /// `log_commitment` prints the first `LOG_PREFIX_LEN` commitment bytes on
+    /// every instruction, including each `assert_state` CPI, which helps
+    /// correlate activity. In
+    /// quiet mode nothing is logged and the assert family emits the opaque
+    /// `StateAssertedQuiet` (no commitment bytes) instead of `StateAsserted`.
+    /// Off by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `quiet` - Whether to suppress commitment logging
+    pub fn set_quiet(ctx: Context<SetQuiet>, quiet: bool) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.quiet = quiet;
+        msg!("quiet: {}", quiet);
+        Ok(())
+    }
+
+    /// Makes every commitment-replacing update reject an unchanged commitment.
+    ///
+    /// Such updates only bump the nonce, invalidating consumer caches keyed on
+    /// it without any real state change. Applies to `update` and its variants,
+    /// `update_as_delegate`, `batch_update`, `multisig_update` and
+    /// `update_causal`; `touch` and `bump_group` stay allowed as explicit
+    /// heartbeats. Off by default, since some apps use no-op updates as
+    /// heartbeats.
+    ///
+    /// # Arguments
+    ///
+    /// * `reject_noop` - Whether updates fail with `NoOpUpdate` on an
+    ///   unchanged commitment
+    pub fn set_reject_noop(ctx: Context<SetRejectNoop>, reject_noop: bool) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.reject_noop = reject_noop;
+        msg!("reject_noop: {}", reject_noop);
+        Ok(())
+    }
+
+    /// Sets or extends the slot after which the state stops passing asserts.
+    ///
+    /// The assert family fails with `StateExpired` once the current slot is
+    /// past `expiry_slot`; updates keep working, so the owner can renew an
+    /// expired pass and then push a fresh commitment.
+    ///
+    /// # Arguments
+    ///
+    /// * `expiry_slot` - Last valid slot (0 = never expires)
+    pub fn set_expiry(ctx: Context<SetExpiry>, expiry_slot: u64) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.expiry_slot = expiry_slot;
+        msg!("expiry_slot: {}", expiry_slot);
+        Ok(())
+    }
+
+    /// Changes the per-update fee and where it is paid.
+    ///
+    /// Every instruction that advances the nonce transfers `fee_lamports` from
+    /// the signer paying for the update (usually the authority) to
+    /// `fee_destination`, failing with `InsufficientFee` if it can't cover it.
+    /// Fixed for good once the account is sealed.
+    ///
+    /// # Arguments
+    ///
+    /// * `fee_lamports` - Lamports per update (0 = free)
+    /// * `fee_destination` - Wallet that receives the fees
+    pub fn set_fee(ctx: Context<SetFee>, fee_lamports: u64, fee_destination: Pubkey) -> Result<()> {
+        validate_fee(fee_lamports, &fee_destination)?;
+        let state = &mut ctx.accounts.private_state;
+        state.fee_lamports = fee_lamports;
+        state.fee_destination = fee_destination;
+        msg!("fee: {} lamports to {}", fee_lamports, fee_destination);
+        Ok(())
+    }
+
+    /// Configures the treasury share taken by `close_state_split`.
+    ///
+    /// Fixed for good once the account is sealed, so consumers and operators
+    /// can rely on the split an account will be closed with.
+    ///
+    /// # Arguments
+    ///
+    /// * `treasury` - Wallet that receives the treasury share
+    /// * `treasury_bps` - Treasury share in basis points (at most `BPS_DENOMINATOR`)
+    pub fn set_close_split(
+        ctx: Context<SetCloseSplit>,
+        treasury: Pubkey,
+        treasury_bps: u16,
+    ) -> Result<()> {
+        validate_close_split(&treasury, treasury_bps)?;
+        let state = &mut ctx.accounts.private_state;
+        state.treasury = treasury;
+        state.treasury_bps = treasury_bps;
+        msg!("close split: {} bps to {}", treasury_bps, treasury);
+        Ok(())
+    }
+
+    /// Changes (or removes) the key allowed to `recover_authority`.
+    ///
+    /// Authority changes clear the recovery key so a previous owner's key
+    /// can't reclaim the account; the new authority sets its own here. Fixed
+    /// for good once the account is sealed.
+    ///
+    /// # Arguments
+    ///
+    /// * `recovery` - Recovery key, or `None` to disable recovery
+    /// * `inactivity_slots` - Idle slots before recovery is allowed (non-zero
+    ///   with a recovery key)
+    pub fn set_recovery(
+        ctx: Context<SetRecovery>,
+        recovery: Option<Pubkey>,
+        inactivity_slots: u64,
+    ) -> Result<()> {
+        validate_recovery(recovery, inactivity_slots)?;
+        let state = &mut ctx.accounts.private_state;
+        state.recovery = recovery;
+        state.inactivity_slots = inactivity_slots;
+        msg!("recovery: {:?} after {} idle slots", recovery, inactivity_slots);
+        Ok(())
+    }
+
+    /// Caps how many updates the account accepts per window of slots.
+    ///
+    /// Stops an authority from churning the commitment every slot and
+    /// invalidating `(commitment, nonce)` pairs consumers are about to assert.
+    /// Every update instruction counts once (a `batch_update` counts as one).
+    /// Setting either value to 0 disables the limit (the default).
+    ///
+    /// # Arguments
+    ///
+    /// * `max_updates_per_window` - Updates allowed per window
+    /// * `window_len_slots` - Window length in slots
+    pub fn set_rate_limit(
+        ctx: Context<SetRateLimit>,
+        max_updates_per_window: u16,
+        window_len_slots: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        state.max_updates_per_window = max_updates_per_window;
+        state.window_len_slots = window_len_slots;
+        state.window_start_slot = Clock::get()?.slot;
+        state.updates_in_window = 0;
+        msg!(
+            "rate limit: {} per {} slots",
+            max_updates_per_window,
+            window_len_slots
+        );
+        Ok(())
+    }
+
+    /// Permanently attaches an immutable metadata blob to the account.
+    ///
+    /// Creates the write-once `MetaData` PDA at `[b"meta", private_state]`. Once set
+    /// it can never change: a second call fails with `MetadataAlreadySet`.
+    ///
+    /// # Arguments
+    ///
+    /// * `blob` - Opaque metadata, e.g. a document hash and type tag
+    ///
+    /// # Use Cases
+    ///
+    /// - Notarization: bind a document hash to the account forever
+    /// - Permanent attestations that must outlive any state updates
+    pub fn set_metadata(ctx: Context<SetMetadata>, blob: [u8; METADATA_LEN]) -> Result<()> {
+        let private_state = ctx.accounts.private_state.key();
+        let seeds: &[&[u8]] = &[b"meta", private_state.as_ref(), &[ctx.bumps.metadata]];
+        create_pda_account(
+            &ctx.accounts.authority,
+            &ctx.accounts.metadata,
+            &ctx.accounts.system_program,
+            8 + 32 + METADATA_LEN,
+            seeds,
+        )?;
+
+        let metadata = MetaData {
+            private_state,
+            blob,
+        };
+        let mut data = ctx.accounts.metadata.try_borrow_mut_data()?;
+        metadata.try_serialize(&mut &mut data[..])?;
+
+        msg!("metadata set: {}", private_state);
+        Ok(())
+    }
+
+    /// Sets (or replaces) the guardian set allowed to call `multisig_update`.
+    ///
+    /// The set lives in the `GuardianSet` PDA at `[b"guardians", private_state]`,
+    /// created on first call. The authority keeps its own update powers.
+    ///
+    /// # Arguments
+    ///
+    /// * `guardians` - Distinct guardian keys (at most `MAX_GUARDIANS`)
+    /// * `threshold` - Approvals required, between 1 and `guardians.len()`
+    ///
+    /// Stored as a weighted set with every weight 1 (see `set_weighted_guardians`).
+    pub fn set_guardians(
+        ctx: Context<SetGuardians>,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        let guardians = guardians
+            .into_iter()
+            .map(|key| WeightedGuardian { key, weight: 1 })
+            .collect();
+        write_guardian_set(ctx, guardians, threshold as u16)
+    }
+
+    /// Sets (or replaces) a weighted guardian set for `multisig_update`.
+    ///
+    /// Generalizes `set_guardians` for members with different stakes: a
+    /// multisig update needs the weights of its guardian signers to add up to
+    /// `weight_threshold`.
+    ///
+    /// # Arguments
+    ///
+    /// * `guardians` - Distinct guardian keys with non-zero weights (at most
+    ///   `MAX_GUARDIANS`)
+    /// * `weight_threshold` - Total weight required, between 1 and the sum of
+    ///   all weights
+    pub fn set_weighted_guardians(
+        ctx: Context<SetGuardians>,
+        guardians: Vec<WeightedGuardian>,
+        weight_threshold: u16,
+    ) -> Result<()> {
+        write_guardian_set(ctx, guardians, weight_threshold)
+    }
+
+    /// Records that a consumer program gates on this private state.
+    ///
+    /// Opt-in discoverability metadata: creates the `ConsumerLink` PDA at
+    /// `[b"consumer_link", private_state, consumer_program]` so operators can
+    /// see who depends on a state before they `reset` or `close` it. Any signer
+    /// may register (and pays rent); the program enforces nothing based on it.
+    pub fn register_consumer(ctx: Context<RegisterConsumer>) -> Result<()> {
+        let private_state = ctx.accounts.private_state.key();
+        let consumer_program = ctx.accounts.consumer_program.key();
+        let seeds: &[&[u8]] = &[
+            b"consumer_link",
+            private_state.as_ref(),
+            consumer_program.as_ref(),
+            &[ctx.bumps.consumer_link],
+        ];
+        create_pda_account(
+            &ctx.accounts.registrar,
+            &ctx.accounts.consumer_link,
+            &ctx.accounts.system_program,
+            8 + 32 + 32 + 32 + 8,
+            seeds,
+        )?;
+
+        let link = ConsumerLink {
+            private_state,
+            consumer_program,
+            registrar: ctx.accounts.registrar.key(),
+            registered_slot: Clock::get()?.slot,
+        };
+        let mut data = ctx.accounts.consumer_link.try_borrow_mut_data()?;
+        link.try_serialize(&mut &mut data[..])?;
+
+        msg!("consumer registered: {}", consumer_program);
+        Ok(())
+    }
+
+    /// Removes a `ConsumerLink` and returns its rent to the registrar.
+    pub fn deregister_consumer(ctx: Context<DeregisterConsumer>) -> Result<()> {
+        msg!(
+            "consumer deregistered: {}",
+            ctx.accounts.consumer_link.consumer_program
+        );
+        Ok(())
+    }
+
+    /// Closes a private state account and returns its rent to the authority.
+    ///
+    /// The final nonce and commitment prefix are logged before closing so
+    /// off-chain indexers can record the terminal state.
+    ///
+    /// # Warning
+    ///
+    /// The program can't know whether consumers still gate on this account;
+    /// once closed, every `assert_state` against it fails.
+    pub fn close(ctx: Context<Close>) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        msg!("closing: {}", state.key());
+        log_state(state);
+        Ok(())
+    }
+
+    /// Closes a private state account, splitting its lamports with a treasury.
+    ///
+    /// Like `close`, but the `treasury_bps` basis points configured with
+    /// `set_close_split` of the account's lamports (rent plus anything it
+    /// received on top) go to the configured `treasury` first; the authority
+    /// gets the rest, including any rounding remainder (see `split_lamports`).
+    pub fn close_state_split(ctx: Context<CloseSplit>) -> Result<()> {
+        let state_info = ctx.accounts.private_state.to_account_info();
+        let treasury_bps = ctx.accounts.private_state.treasury_bps;
+        let (to_treasury, to_authority) = split_lamports(state_info.lamports(), treasury_bps)?;
+
+        **state_info.try_borrow_mut_lamports()? -= to_treasury;
+        **ctx.accounts.treasury.try_borrow_mut_lamports()? += to_treasury;
+
+        let state = &ctx.accounts.private_state;
+        msg!(
+            "closing: {} ({} to treasury, {} to authority)",
+            state.key(),
+            to_treasury,
+            to_authority
+        );
+        log_state(state);
+        Ok(())
+    }
+
+    /// Returns the account's current data length in bytes.
+    ///
+    /// Works on any layout version. Anything below `PRIVATE_STATE_SPACE` is an
+    /// older layout that `migrate` will grow.
+    pub fn state_size(ctx: Context<StateSize>) -> Result<u64> {
+        Ok(ctx.accounts.private_state.data_len() as u64)
+    }
+
+    /// Upgrades an account to the current layout (`CURRENT_STATE_VERSION`).
+    ///
+    /// Reallocs the account to `PRIVATE_STATE_SPACE` (the authority tops up
+    /// rent), fills new fields with defaults and bumps `version`. Handles both
+    /// the original unversioned 81-byte layout and older versioned layouts,
+    /// reading only the fields the account's version had (see
+    /// `PrivateState::try_deserialize_versioned`).
+    /// A no-op for accounts that are already current.
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let info = ctx.accounts.private_state.to_account_info();
+        let legacy = if info.data_len() == LEGACY_STATE_SPACE {
+            let data = info.try_borrow_data()?;
+            require!(
+                data[..8] == PrivateState::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            Some(LegacyPrivateState::deserialize(&mut &data[8..])?)
+        } else {
+            None
+        };
+
+        grow_account(
+            &info,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            PRIVATE_STATE_SPACE,
+        )?;
+
+        let mut state = match legacy {
+            Some(old) => PrivateState {
+                version: 0,
+                authority: old.authority,
+                commitment: old.commitment,
+                nonce: old.nonce,
+                policy: old.policy,
+                commitment_len: FULL_COMMITMENT_LEN,
+                allow_reset: true,
+                pending_authority: None,
+                bump: 0,
+                max_nonce: 0,
+                min_update_slots: 0,
+                last_update_slot: 0,
+                frozen: false,
+                delegate: None,
+                hash_algo: HASH_ALGO_SHA256,
+                update_count: 0,
+                epoch: 0,
+                recent: [[0; 32]; RECENT_COMMITMENTS],
+                recent_head: 0,
+                window_start_slot: 0,
+                updates_in_window: 0,
+                max_updates_per_window: 0,
+                window_len_slots: 0,
+                quiet: false,
+                expiry_slot: 0,
+                sealed: false,
+                fee_lamports: 0,
+                fee_destination: Pubkey::default(),
+                reject_noop: false,
+                recovery: None,
+                inactivity_slots: 0,
+                created_slot: 0,
+                freeze_reason: 0,
+                treasury: Pubkey::default(),
+                treasury_bps: 0,
+            },
+            None => PrivateState::try_deserialize_versioned(&info.try_borrow_data()?)?,
+        };
+        require_keys_eq!(
+            state.authority,
+            ctx.accounts.authority.key(),
+            ErrorCode::ConstraintHasOne
+        );
+
+        if state.version == CURRENT_STATE_VERSION {
+            msg!("already at version {}", CURRENT_STATE_VERSION);
+            return Ok(());
+        }
+        let from = state.version;
+        if from < 5 {
+            // Seed the history introduced in v5 with the current commitment
+            let commitment = state.commitment;
+            reset_recent(&mut state, commitment);
+        }
+        state.version = CURRENT_STATE_VERSION;
+        let mut data = info.try_borrow_mut_data()?;
+        let mut rest: &mut [u8] = &mut data[..];
+        state.try_serialize(&mut rest)?;
+        // Leave no stale bytes for the next migration to misread
+        rest.fill(0);
+
+        msg!("migrated: v{} -> v{}", from, CURRENT_STATE_VERSION);
+        Ok(())
+    }
+
+    /// Changes the update policy at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - New policy (StrictSequentialWithCap uses the `max_nonce`
+    ///   set at initialize)
+    ///
+    /// # Use Cases
+    ///
+    /// - Switch from strict mode to allow offline updates
+    /// - Tighten policy after initial flexible setup
+    /// - Adapt to changing application requirements
+    pub fn set_policy(ctx: Context<SetPolicy>, policy: UpdatePolicy) -> Result<()> {
+        change_policy(&mut ctx.accounts.private_state, policy as u8);
+        Ok(())
+    }
+
+    /// `set_policy` with the policy as a raw byte, for older clients.
+    ///
+    /// Fails with `InvalidPolicy` unless `policy` is 0 = StrictSequential,
+    /// 1 = AllowSkips or 2 = StrictSequentialWithCap.
+    pub fn set_policy_raw(ctx: Context<SetPolicy>, policy: u8) -> Result<()> {
+        validate_policy(policy)?;
+        change_policy(&mut ctx.accounts.private_state, policy);
+        Ok(())
+    }
+
+    /// Sets how many leading bytes of the 32-byte commitment are significant.
+    ///
+    /// For commitment schemes with shorter outputs (e.g. 20-byte RIPEMD-160), the
+    /// digest sits in the first `commitment_len` bytes and the rest must be zero.
+    /// Assertions then compare only the significant prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `commitment_len` - Significant length, 1..=32 (32 = full SHA-256 width)
+    ///
+    /// # Validation
+    ///
+    /// The stored commitment must already be canonical for the new length, and
+    /// every later update must keep the trailing padding zeroed.
+    pub fn set_commitment_len(ctx: Context<SetCommitmentLen>, commitment_len: u8) -> Result<()> {
+        let state = &mut ctx.accounts.private_state;
+        validate_commitment_padding(&state.commitment, commitment_len)?;
+        let old_len = state.commitment_len;
+        state.commitment_len = commitment_len;
+        msg!("commitment_len: {} -> {}", old_len, commitment_len);
+        Ok(())
+    }
+
+    /// Changes the update policy of many private state accounts at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - New policy for every account: 0 = StrictSequential, 1 = AllowSkips,
+    ///   2 = StrictSequentialWithCap
+    ///
+    /// # Remaining Accounts
+    ///
+    /// The writable `PrivateState` accounts to change (at most `MAX_BATCH_ACCOUNTS`).
+    /// Each one must be owned by this program and belong to `authority`; if any
+    /// account fails validation the whole batch reverts.
+    pub fn set_policy_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetPolicyMany<'info>>,
+        policy: u8,
+    ) -> Result<()> {
+        validate_policy(policy)?;
+        validate_batch(ctx.remaining_accounts)?;
+
+        let authority = ctx.accounts.authority.key();
+        for info in ctx.remaining_accounts {
+            let mut state = load_authorized_state(info, &authority)?;
+            require!(!state.sealed, PrivateStateError::StateSealed);
+            let old_policy = state.policy;
+            state.policy = policy;
+            msg!("{} policy: {} -> {}", info.key(), old_policy, policy);
+            emit!(PolicyChanged {
+                account: info.key(),
+                old_policy,
+                new_policy: policy,
+            });
+            state.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// Validates that a private state account matches expected commitment and nonce.
+    ///
+    /// **This is the CPI composability hook.** Other programs can call this instruction
+    /// via Cross-Program Invocation (CPI) to gate actions on private state freshness
+    /// without needing the encryption key or seeing the plaintext.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    ///
+    /// # Design Properties
+    ///
+    /// - **Read-only**: Does not mutate state (cheap, safe for CPI)
+    /// - **Deterministic**: Same inputs always produce same result
+    /// - **No decryption**: Caller doesn't need encryption key
+    ///
+    /// Like the rest of the assert family, fails with `StateExpired` once the
+    /// account is past its `expiry_slot`.
+    ///
+    /// # Example CPI Usage
+    ///
+    /// ```rust,ignore
+    /// let cpi_ctx = CpiContext::new(pst_program, AssertState { private_state });
+    /// private_state_toolkit::cpi::assert_state(cpi_ctx, commitment, nonce)?;
+    /// // If we reach here, the state is valid - proceed with gated action
+    /// ```
+    pub fn assert_state(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+
+        // Verify commitment matches (significant prefix only)
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+
+        // Verify nonce matches
+        require!(
+            state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Like `assert_state`, but only passes for a healthy, live account.
+    ///
+    /// `assert_state` keeps gating while an account is frozen for a dispute;
+    /// strict consumers call this instead to express "only gate on a state
+    /// nobody is contesting" in a single CPI. Checks run in this order and the
+    /// first failure wins:
+    ///
+    /// 1. `StateFrozen` - the account is frozen
+    /// 2. `StateExpired` - the current slot is past `expiry_slot`
+    /// 3. `CommitmentMismatch` - the commitment differs
+    /// 4. `NonceMismatch` - the nonce differs
+    ///
+    /// Sealed accounts pass: sealing fixes the rules, it doesn't invalidate
+    /// the state.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    pub fn assert_state_strict(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require!(!state.frozen, PrivateStateError::StateFrozen);
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates that `payload` is the data behind the stored commitment.
+    ///
+    /// Recomputes `hash(claimed_nonce || payload)` on-chain with the account's
+    /// `hash_algo` and compares it with the stored commitment, so a consumer
+    /// holding the encrypted payload need not trust a client-side hash.
+    ///
+    /// Fails with `PayloadTooLarge` above `MAX_VERIFY_PAYLOAD_LEN` bytes (to
+    /// bound compute), `StateExpired` past `expiry_slot`, `NonceMismatch` if
+    /// `claimed_nonce` is not the current nonce, or `CommitmentMismatch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The encrypted payload (iv || tag || ciphertext)
+    /// * `claimed_nonce` - The nonce the payload was committed at
+    pub fn verify_payload(
+        ctx: Context<AssertState>,
+        payload: Vec<u8>,
+        claimed_nonce: u64,
+    ) -> Result<()> {
+        require!(
+            payload.len() <= MAX_VERIFY_PAYLOAD_LEN,
+            PrivateStateError::PayloadTooLarge
+        );
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            state.nonce == claimed_nonce,
+            PrivateStateError::NonceMismatch
+        );
+        let preimage: [&[u8]; 2] = [&claimed_nonce.to_le_bytes(), &payload];
+        let computed = match state.hash_algo {
+            HASH_ALGO_KECCAK256 => keccak::hashv(&preimage).to_bytes(),
+            _ => hashv(&preimage).to_bytes(),
+        };
+        require!(
+            commitment_matches(state, &computed),
+            PrivateStateError::CommitmentMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates commitment and nonce, bound to a specific signer.
+    ///
+    /// A consumer passes the key that authorized the gated action as
+    /// `requester`; the assertion only succeeds if that key signed the
+    /// transaction, so one user's proof cannot be replayed for another.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    /// * `expected_requester` - Key that must have signed as `requester`
+    pub fn assert_state_signed(
+        ctx: Context<AssertStateSigned>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+        expected_requester: Pubkey,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.requester.key(),
+            expected_requester,
+            PrivateStateError::UnauthorizedRequester
+        );
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Dry-run of `assert_state` that never fails on a mismatch.
+    ///
+    /// Returns a bitmask via return data (u8) of `CHECK_COMMITMENT_MISMATCH`,
+    /// `CHECK_NONCE_MISMATCH` and `CHECK_EXPIRED`, or 0 if `assert_state`
+    /// would succeed.
+    /// Clients simulate it to explain a predictable failure before paying for
+    /// a gated action.
+    pub fn check_state(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+    ) -> Result<u8> {
+        let state = &ctx.accounts.private_state;
+        let mut flags = 0;
+        if !commitment_matches(state, &expected_commitment) {
+            flags |= CHECK_COMMITMENT_MISMATCH;
+        }
+        if state.nonce != expected_nonce {
+            flags |= CHECK_NONCE_MISMATCH;
+        }
+        if state.is_expired(Clock::get()?.slot) {
+            flags |= CHECK_EXPIRED;
+        }
+        Ok(flags)
+    }
+
+    /// Returns every restricted state the account is in as one bitmask.
+    ///
+    /// A u8 of `STATUS_FROZEN`, `STATUS_SEALED` and `STATUS_EXPIRED` via return
+    /// data (0 = unrestricted), so consumers can run one health check instead
+    /// of inferring the account's lifecycle from failing asserts.
+    pub fn status(ctx: Context<AssertState>) -> Result<u8> {
+        Ok(ctx.accounts.private_state.status(Clock::get()?.slot))
+    }
+
+    /// Validates only the commitment, ignoring the nonce entirely.
+    ///
+    /// For consumers that gate on "does the state currently equal this value"
+    /// (e.g. a credential's current value) without tracking nonces. Read-only,
+    /// so it stays cheap inside a CPI.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    pub fn assert_commitment(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates the commitment and that the nonce is at least `min_nonce`.
+    ///
+    /// Freshness-only variant of `assert_state` for consumers that need a lower
+    /// bound rather than an exact nonce, e.g. under AllowSkips where the exact
+    /// nonce is hard to track across async updates.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `min_nonce` - Lowest acceptable nonce (inclusive)
+    pub fn assert_state_min_nonce(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        min_nonce: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            state.nonce >= min_nonce,
+            PrivateStateError::NonceBelowMinimum
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates the commitment and that the nonce lies in `[min_nonce, max_nonce]`.
+    ///
+    /// For batched or async consumers that know the state should be "somewhere
+    /// in this window" rather than at an exact nonce. Generalizes `assert_state`
+    /// (`min_nonce == max_nonce`) and `assert_state_min_nonce` (`max_nonce ==
+    /// u64::MAX`).
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `min_nonce` - Lowest acceptable nonce (inclusive)
+    /// * `max_nonce` - Highest acceptable nonce (inclusive)
+    pub fn assert_state_range(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        min_nonce: u64,
+        max_nonce: u64,
+    ) -> Result<()> {
+        require!(min_nonce <= max_nonce, PrivateStateError::InvalidRange);
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            (min_nonce..=max_nonce).contains(&state.nonce),
+            PrivateStateError::NonceOutOfRange
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates the commitment and compares the nonce with `nonce_bound`.
+    ///
+    /// One primitive for every single-bound nonce check; the consumer picks
+    /// the comparison at call time with `op` (`NONCE_CMP_*`): the check is
+    /// `state.nonce <op> nonce_bound`. Fails with `InvalidComparisonOp` for an
+    /// unknown `op` and `NonceComparisonFailed` if the comparison is false.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `nonce_bound` - Right-hand side of the comparison
+    /// * `op` - 0 = eq, 1 = ge, 2 = gt, 3 = le, 4 = lt
+    pub fn assert_state_cmp(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        nonce_bound: u64,
+        op: u8,
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        let passes = compare_nonce(state.nonce, op, nonce_bound)?;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(passes, PrivateStateError::NonceComparisonFailed);
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates commitment and nonce within a specific reset epoch.
+    ///
+    /// A reset restarts the nonce at 0, so the same `(commitment, nonce)` pair
+    /// could recur in a later epoch. Pinning the epoch closes that replay-style
+    /// hole for consumers. Read-only, so it stays cheap inside a CPI.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    /// * `expected_epoch` - The epoch the account must be in
+    pub fn assert_state_with_epoch(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+        expected_epoch: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            state.epoch == expected_epoch,
+            PrivateStateError::EpochMismatch
+        );
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates commitment and nonce, and that the state was updated recently.
+    ///
+    /// Same checks as `assert_state`, plus `current_slot - last_update_slot <=
+    /// max_age_slots`, so consumers can demand proof of recent activity.
+    /// An account that was never updated is measured from its initialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    /// * `max_age_slots` - Oldest acceptable last update, in slots
+    pub fn assert_state_fresh(
+        ctx: Context<AssertState>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+        max_age_slots: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+        let age = Clock::get()?.slot.saturating_sub(state.last_update_slot);
+        require!(age <= max_age_slots, PrivateStateError::StateStale);
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates that the state has not been updated for at least `min_quiet_slots`.
+    ///
+    /// The inverse of `assert_state_fresh`: checks `current_slot -
+    /// last_update_slot >= min_quiet_slots`, for "settled state" gating where a
+    /// recent change should block an action. An account that was never updated
+    /// is measured from its initialization.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_quiet_slots` - Slots that must have passed since the last update
+    ///
+    /// Fails with `StateNotQuiescent` otherwise.
+    pub fn assert_quiescent(ctx: Context<AssertState>, min_quiet_slots: u64) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        let quiet = Clock::get()?.slot.saturating_sub(state.last_update_slot);
+        require!(
+            quiet >= min_quiet_slots,
+            PrivateStateError::StateNotQuiescent
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates that the last update rotated `old_commitment` to `new_commitment`.
+    ///
+    /// Checks the previous entry of the `recent` ring against `old_commitment`
+    /// and the current commitment against `new_commitment` (significant prefix
+    /// only), proving exactly that rotation step happened. Lets parties to a
+    /// coordinated key rotation confirm the precise transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_commitment` - Commitment the last update must have replaced
+    /// * `new_commitment` - Commitment the account must hold now
+    ///
+    /// Fails with `RotationOldMismatch` (also when no update happened since
+    /// initialization or the last reset) or `RotationNewMismatch`.
+    pub fn assert_rotation(
+        ctx: Context<AssertState>,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+
+        let len = state.commitment_len as usize;
+        let previous = state.previous_commitment();
+        require!(
+            previous != [0; 32] && constant_time_eq(&previous[..len], &old_commitment[..len]),
+            PrivateStateError::RotationOldMismatch
+        );
+        require!(
+            commitment_matches(state, &new_commitment),
+            PrivateStateError::RotationNewMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates that at least `k` of several accounts were updated recently.
+    ///
+    /// Counts the accounts whose `current_slot - last_update_slot <=
+    /// max_age_slots` (expired accounts don't count) and fails with
+    /// `FreshnessThresholdNotMet` if fewer than `k` qualify. Models
+    /// multi-credential gating that tolerates some stale credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age_slots` - Oldest acceptable last update, in slots
+    /// * `k` - Minimum number of fresh accounts
+    ///
+    /// # Remaining Accounts
+    ///
+    /// The other `PrivateState` accounts to check after `private_state` (at
+    /// most `MAX_BATCH_ACCOUNTS` in total, no duplicates).
+    ///
+    /// Returns the number of fresh accounts via return data.
+    pub fn assert_k_of_n_fresh<'info>(
+        ctx: Context<'_, '_, '_, 'info, AssertState<'info>>,
+        max_age_slots: u64,
+        k: u8,
+    ) -> Result<u8> {
+        let first = ctx.accounts.private_state.to_account_info();
+        let accounts: Vec<&AccountInfo> =
+            std::iter::once(&first).chain(ctx.remaining_accounts).collect();
+        require!(
+            accounts.len() <= MAX_BATCH_ACCOUNTS,
+            PrivateStateError::BatchTooLarge
+        );
+
+        let slot = Clock::get()?.slot;
+        let mut fresh = 0u8;
+        for (i, info) in accounts.iter().enumerate() {
+            require!(
+                accounts[..i].iter().all(|prev| prev.key != info.key),
+                PrivateStateError::DuplicateAccount
+            );
+            require_keys_eq!(*info.owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+            let data = info.try_borrow_data()?;
+            let state = PrivateState::try_deserialize(&mut &data[..])?;
+            if !state.is_expired(slot)
+                && slot.saturating_sub(state.last_update_slot) <= max_age_slots
+            {
+                fresh += 1;
+            }
+        }
+        msg!("fresh: {}/{}", fresh, accounts.len());
+        require!(fresh >= k, PrivateStateError::FreshnessThresholdNotMet);
+        Ok(fresh)
+    }
+
+    /// Returns the smallest nonce the next `update` may use under the current policy.
+    ///
+    /// Returned via return data (u64 LE), so clients can simulate this instead of
+    /// computing the value themselves. For all policies (exact for the strict
+    /// ones, floor for AllowSkips) this is `current_nonce + 1`.
+    ///
+    /// Fails with `NonceOverflow` if the nonce is already `u64::MAX`, with
+    /// `NonceExhaustionRisk` if it has reached `NONCE_CEILING`, or with
+    /// `MaxNonceReached` if a capped account is at its ceiling, since no further
+    /// update is possible.
+    pub fn next_nonce(ctx: Context<AssertState>) -> Result<u64> {
+        let state = &ctx.accounts.private_state;
+        require!(
+            state.nonce < NONCE_CEILING,
+            PrivateStateError::NonceExhaustionRisk
+        );
+        let next = match UpdatePolicy::try_from(state.policy)? {
+            UpdatePolicy::StrictSequential | UpdatePolicy::AllowSkips => state.nonce.checked_add(1),
+            UpdatePolicy::StrictSequentialWithCap => {
+                require!(
+                    state.nonce < state.max_nonce,
+                    PrivateStateError::MaxNonceReached
+                );
+                state.nonce.checked_add(1)
+            }
+        };
+        next.ok_or_else(|| PrivateStateError::NonceOverflow.into())
+    }
+
+    /// Returns the current nonce via return data (u64 LE).
+    ///
+    /// The cheapest read: no checks at all, for consumers that only need the
+    /// nonce to plan a follow-up `assert_state`.
+    pub fn peek_nonce(ctx: Context<AssertState>) -> Result<u64> {
+        Ok(ctx.accounts.private_state.nonce)
+    }
+
+    /// Returns the current commitment, nonce, policy, hash algorithm, update
+    /// count, epoch, recent commitments and creation slot via return data.
+    ///
+    /// Unlike `assert_state`, the caller does not need to know the values in
+    /// advance: a consumer program can read them over CPI and apply its own
+    /// rules (e.g. "nonce must be even").
+    pub fn get_state(ctx: Context<AssertState>) -> Result<PrivateStateView> {
+        let state = &ctx.accounts.private_state;
+        Ok(PrivateStateView {
+            commitment: state.commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            hash_algo: state.hash_algo,
+            update_count: state.update_count,
+            epoch: state.epoch,
+            recent: state.recent_commitments(),
+            created_slot: state.created_slot,
+        })
+    }
+
+    /// Returns every public field of the account as one `StateSnapshot`.
+    ///
+    /// Unlike `get_state`, the snapshot tracks `PrivateState` field for field,
+    /// so clients decode one struct instead of parsing the account by hand.
+    pub fn snapshot(ctx: Context<AssertState>) -> Result<StateSnapshot> {
+        Ok(StateSnapshot::from(&*ctx.accounts.private_state))
+    }
+
+    /// Returns the recent-commitment ring as packed bytes via return data.
+    ///
+    /// Layout (`HISTORY_LEN` bytes, see `PrivateState::history`): `recent_head`,
+    /// then the `RECENT_COMMITMENTS` entries oldest to newest, so clients can
+    /// rebuild recent history from one simulate call without decoding the raw
+    /// ring.
+    pub fn read_history(ctx: Context<AssertState>) -> Result<[u8; HISTORY_LEN]> {
+        Ok(ctx.accounts.private_state.history())
+    }
+
+    /// Validates that the account's update policy is one of an allowed set.
+    ///
+    /// Lets a consumer accept a whitelist of update disciplines (e.g. strict
+    /// ordering only) rather than pinning a single policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed` - Acceptable policy values (at most `MAX_ALLOWED_SET` entries)
+    pub fn assert_policy_in(ctx: Context<AssertState>, allowed: Vec<u8>) -> Result<()> {
+        require!(
+            allowed.len() <= MAX_ALLOWED_SET,
+            PrivateStateError::AllowedSetTooLarge
+        );
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            allowed.contains(&state.policy),
+            PrivateStateError::PolicyNotAllowed
+        );
+        Ok(())
+    }
+
+    /// Validates that the account's authority is one of an allowed set.
+    ///
+    /// Lets a consumer trust accounts issued by any of several known issuer keys
+    /// without pinning a single authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed` - Acceptable authorities (at most `MAX_ALLOWED_SET` entries)
+    pub fn assert_authority_in(ctx: Context<AssertState>, allowed: Vec<Pubkey>) -> Result<()> {
+        require!(
+            allowed.len() <= MAX_ALLOWED_SET,
+            PrivateStateError::AllowedSetTooLarge
+        );
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            allowed.contains(&state.authority),
+            PrivateStateError::AuthorityNotAllowed
+        );
+        Ok(())
+    }
+
+    /// Validates that the stored commitment is one of a set of commitments.
+    ///
+    /// For consumers that keep a rotating set of acceptable states (e.g. the
+    /// current and immediately prior commitment) to tolerate a short lag
+    /// window. The nonce must also equal `expected_nonce`, unless it is
+    /// `ANY_NONCE`.
+    ///
+    /// Fails with `AllowedSetTooLarge` above `MAX_ALLOWED_SET` entries,
+    /// `StateExpired` past `expiry_slot`, `CommitmentNotInSet` if no entry
+    /// matches, or `NonceMismatch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `commitments` - Acceptable commitments (at most `MAX_ALLOWED_SET` entries)
+    /// * `expected_nonce` - The nonce value to check, or `ANY_NONCE` to skip it
+    pub fn assert_state_in_set(
+        ctx: Context<AssertState>,
+        commitments: Vec<[u8; 32]>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        require!(
+            commitments.len() <= MAX_ALLOWED_SET,
+            PrivateStateError::AllowedSetTooLarge
+        );
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        // Check every entry so compute usage doesn't reveal which one matched
+        let found = commitments
+            .iter()
+            .fold(false, |found, c| found | commitment_matches(state, c));
+        require!(found, PrivateStateError::CommitmentNotInSet);
+        require!(
+            expected_nonce == ANY_NONCE || state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Validates that two accounts hold commitments that pair to an expected value.
+    ///
+    /// Checks `sha256(lo || hi) == expected`, where `lo`/`hi` are the two stored
+    /// commitments in ascending byte order. Sorting makes the pairing
+    /// order-independent, so either account may be passed first.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - The pairing commitment, e.g. from a matchmaking protocol
+    pub fn assert_pair(ctx: Context<AssertPair>, expected: [u8; 32]) -> Result<()> {
+        let a = &ctx.accounts.state_a;
+        let b = &ctx.accounts.state_b;
+        require_not_expired(a)?;
+        require_not_expired(b)?;
+        require_keys_neq!(a.key(), b.key(), PrivateStateError::DuplicateAccount);
+
+        let (lo, hi) = if a.commitment <= b.commitment {
+            (&a.commitment, &b.commitment)
+        } else {
+            (&b.commitment, &a.commitment)
+        };
+        require!(
+            hashv(&[lo, hi]).to_bytes() == expected,
+            PrivateStateError::PairMismatch
+        );
+        Ok(())
+    }
+
+    /// Verifies that the account's current authority signed an off-chain message.
+    ///
+    /// The signature itself is checked by the native Ed25519 program, which must run
+    /// as the instruction immediately before this one. This instruction introspects
+    /// that preceding instruction and confirms it verified `signature` over `message`
+    /// for `state.authority`.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - 32-byte message (typically a hash of the off-chain intent)
+    /// * `signature` - Ed25519 signature over `message` by the authority
+    ///
+    /// # Use Cases
+    ///
+    /// - Confirm the owner authorized an off-chain action without being a tx signer
+    /// - Hybrid authorization flows keyed to the on-chain authority identity
+    pub fn assert_authority_signed(
+        ctx: Context<AssertAuthoritySigned>,
+        message: [u8; 32],
+        signature: [u8; 64],
+    ) -> Result<()> {
+        let state = &ctx.accounts.private_state;
+        let ix = get_instruction_relative(-1, &ctx.accounts.instructions)
+            .map_err(|_| PrivateStateError::MissingSignatureVerification)?;
+        verify_ed25519_ix(&ix, &state.authority, &message, &signature)?;
+
+        msg!("authority signature verified: {}", state.authority);
+        Ok(())
+    }
+
+    /// Sets (or replaces) the programs allowed to call `assert_state_restricted`.
+    ///
+    /// The list lives in the `CallerAllowlist` PDA at
+    /// `[b"allowed_callers", private_state]`, created on first call. Plain
+    /// `assert_state` stays open to every program.
+    ///
+    /// # Arguments
+    ///
+    /// * `callers` - Distinct consumer program ids (at most `MAX_ALLOWED_CALLERS`)
+    pub fn set_allowed_callers(
+        ctx: Context<SetAllowedCallers>,
+        callers: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            callers.len() <= MAX_ALLOWED_CALLERS,
+            PrivateStateError::AllowedSetTooLarge
+        );
+        require!(
+            callers
+                .iter()
+                .enumerate()
+                .all(|(i, c)| callers[..i].iter().all(|p| p != c)),
+            PrivateStateError::DuplicateAccount
+        );
+
+        let private_state = ctx.accounts.private_state.key();
+        if ctx.accounts.allowed_callers.data_is_empty() {
+            let seeds: &[&[u8]] = &[
+                b"allowed_callers",
+                private_state.as_ref(),
+                &[ctx.bumps.allowed_callers],
+            ];
+            create_pda_account(
+                &ctx.accounts.authority,
+                &ctx.accounts.allowed_callers,
+                &ctx.accounts.system_program,
+                CALLER_ALLOWLIST_SPACE,
+                seeds,
+            )?;
+        } else {
+            require_keys_eq!(
+                *ctx.accounts.allowed_callers.owner,
+                crate::ID,
+                ErrorCode::ConstraintOwner
+            );
+        }
+
+        let allowlist = CallerAllowlist {
+            private_state,
+            callers,
+        };
+        let mut data = ctx.accounts.allowed_callers.try_borrow_mut_data()?;
+        allowlist.try_serialize(&mut &mut data[..])?;
+
+        msg!("allowed callers: {}", allowlist.callers.len());
+        Ok(())
+    }
+
+    /// Validates commitment and nonce for approved consumer programs only.
+    ///
+    /// Like `assert_state`, but fails with `CallerNotAllowed` unless it is
+    /// invoked via CPI by a program in the account's `CallerAllowlist`, e.g.
+    /// for a licensed credential.
+    ///
+    /// # Identifying the Caller
+    ///
+    /// Solana doesn't pass the invoking program to a callee. Instead:
+    ///
+    /// 1. `get_stack_height()` must be exactly one above
+    ///    `TRANSACTION_LEVEL_STACK_HEIGHT`, i.e. this is a direct CPI from a
+    ///    top-level instruction. Top-level calls have no caller, and deeper
+    ///    CPIs can't be attributed to their immediate caller, so both fail.
+    /// 2. At that depth the caller is the program of the currently executing
+    ///    top-level instruction, read from the instructions sysvar with
+    ///    `get_instruction_relative(0, ..)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    pub fn assert_state_restricted(
+        ctx: Context<AssertStateRestricted>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let caller = cpi_caller(&ctx.accounts.instructions)?;
+        require!(
+            caller.is_some_and(|c| ctx.accounts.allowed_callers.callers.contains(&c)),
+            PrivateStateError::CallerNotAllowed
+        );
+
+        let state = &ctx.accounts.private_state;
+        require_not_expired(state)?;
+        require!(
+            commitment_matches(state, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            state.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        emit_asserted(state);
+        Ok(())
+    }
+
+    /// Creates a `MultiState` account holding several namespaced states.
+    ///
+    /// One account (and one rent deposit) instead of a `PrivateState` per small
+    /// private value. Every entry follows the same `policy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Update policy applied to every entry
+    /// * `max_nonce` - Per-entry nonce ceiling for StrictSequentialWithCap
+    /// * `entries` - Initial `(namespace, commitment, nonce)` entries, with
+    ///   distinct namespaces (at most `MAX_MULTI_ENTRIES`)
+    pub fn initialize_multi(
+        ctx: Context<InitializeMulti>,
+        policy: u8,
+        max_nonce: u64,
+        entries: Vec<MultiStateEntry>,
+    ) -> Result<()> {
+        validate_policy(policy)?;
+        require!(
+            entries.len() <= MAX_MULTI_ENTRIES,
+            PrivateStateError::TooManyNamespaces
+        );
+        require!(
+            entries
+                .iter()
+                .enumerate()
+                .all(|(i, e)| entries[..i].iter().all(|p| p.namespace != e.namespace)),
+            PrivateStateError::DuplicateNamespace
+        );
+
+        let multi = &mut ctx.accounts.multi_state;
+        multi.authority = ctx.accounts.authority.key();
+        multi.policy = policy;
+        multi.max_nonce = max_nonce;
+        multi.entries = entries;
+
+        msg!("multi state initialized: {} entries", multi.entries.len());
+        Ok(())
+    }
+
+    /// Updates one namespace of a `MultiState`.
+    ///
+    /// The same checks as `update`, applied to the entry for `namespace`.
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - Entry to update
+    /// * `old_commitment` - Current commitment of the entry (must match)
+    /// * `new_commitment` - New commitment to store
+    /// * `next_nonce` - New nonce value (must satisfy policy)
+    pub fn update_multi(
+        ctx: Context<UpdateMulti>,
+        namespace: u8,
+        old_commitment: [u8; 32],
+        new_commitment: [u8; 32],
+        next_nonce: u64,
+    ) -> Result<()> {
+        let multi = &mut ctx.accounts.multi_state;
+        let (policy, max_nonce) = (multi.policy, multi.max_nonce);
+        let entry = multi
+            .entries
+            .iter_mut()
+            .find(|e| e.namespace == namespace)
+            .ok_or(PrivateStateError::NamespaceNotFound)?;
+        require!(
+            constant_time_eq(&entry.commitment, &old_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        validate_nonce_rule(policy, entry.nonce, max_nonce, next_nonce)?;
+
+        entry.commitment = new_commitment;
+        entry.nonce = next_nonce;
+
+        log_commitment(entry.nonce, &entry.commitment, policy);
+        emit!(MultiStateUpdated {
+            account: multi.key(),
+            namespace,
+            old_commitment,
+            new_commitment,
+            nonce: next_nonce,
+        });
+        Ok(())
+    }
+
+    /// Validates one namespace of a `MultiState` (the `assert_state` of
+    /// multi-state accounts, CPI-friendly and read-only).
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - Entry to check
+    /// * `expected_commitment` - The commitment value to check
+    /// * `expected_nonce` - The nonce value to check
+    pub fn assert_state_multi(
+        ctx: Context<AssertMulti>,
+        namespace: u8,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let entry = ctx
+            .accounts
+            .multi_state
+            .entries
+            .iter()
+            .find(|e| e.namespace == namespace)
+            .ok_or(PrivateStateError::NamespaceNotFound)?;
+        require!(
+            constant_time_eq(&entry.commitment, &expected_commitment),
+            PrivateStateError::CommitmentMismatch
+        );
+        require!(
+            entry.nonce == expected_nonce,
+            PrivateStateError::NonceMismatch
+        );
+
+        log_commitment(entry.nonce, &entry.commitment, ctx.accounts.multi_state.policy);
+        Ok(())
+    }
+
+    /// Creates an empty `MerkleRegistry`.
+    ///
+    /// An opt-in alternative to one `PrivateState` per user for apps tracking
+    /// thousands of states: each state (e.g. its commitment) becomes a leaf of
+    /// an append-only Merkle tree of depth `MERKLE_DEPTH`, and only the root is
+    /// stored. Consumers gate with `assert_membership` proofs.
+    pub fn initialize_registry(ctx: Context<InitializeRegistry>) -> Result<()> {
+        let zeros = merkle_zero_hashes();
+        let registry = &mut ctx.accounts.registry;
+        registry.authority = ctx.accounts.authority.key();
+        registry.root = zeros[MERKLE_DEPTH];
+        registry.count = 0;
+        registry.filled_subtrees.copy_from_slice(&zeros[..MERKLE_DEPTH]);
+        Ok(())
+    }
+
+    /// Appends `leaf` to the registry and updates the root (authority only).
+    ///
+    /// The leaf's index is the previous `count`. Fails with `RegistryFull` once
+    /// all `2^MERKLE_DEPTH` leaves are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - 32-byte leaf, e.g. a private state's commitment
+    pub fn register_leaf(ctx: Context<RegisterLeaf>, leaf: [u8; 32]) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        require!(
+            registry.count < 1 << MERKLE_DEPTH,
+            PrivateStateError::RegistryFull
+        );
+        let index = registry.count;
+        registry.root = append_merkle_leaf(&mut registry.filled_subtrees, index, leaf);
+        registry.count += 1;
+
+        emit!(LeafRegistered {
+            registry: registry.key(),
+            leaf,
+            index,
+            root: registry.root,
+        });
+        Ok(())
+    }
+
+    /// Validates that `leaf` is in the registry at `index`.
+    ///
+    /// Recomputes the root from `leaf` and its `MERKLE_DEPTH` sibling hashes
+    /// (bottom first) and compares it with the stored root. CPI-friendly and
+    /// read-only. Fails with `InvalidProofLength` unless the proof has exactly
+    /// `MERKLE_DEPTH` entries, which also bounds compute, and with
+    /// `LeafNotInRegistry` if `index` was never registered or the proof does
+    /// not reach the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The leaf being proven
+    /// * `proof` - Sibling hashes from the leaf up to the root
+    /// * `index` - Position of the leaf in the registry
+    pub fn assert_membership(
+        ctx: Context<AssertMembership>,
+        leaf: [u8; 32],
+        proof: Vec<[u8; 32]>,
+        index: u64,
+    ) -> Result<()> {
+        require!(
+            proof.len() == MERKLE_DEPTH,
+            PrivateStateError::InvalidProofLength
+        );
+        let registry = &ctx.accounts.registry;
+        require!(
+            index < registry.count
+                && merkle_root_from_proof(leaf, &proof, index) == registry.root,
+            PrivateStateError::LeafNotInRegistry
+        );
+        msg!("leaf {} is in registry {}", index, registry.key());
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Account Structures
+// ============================================================================
+
+/// The on-chain private state account.
+///
+/// **Total size: 477 bytes** (8-byte discriminator + 469 bytes data)
+///
+/// This is the only data stored on-chain. The actual encrypted application
+/// state lives off-chain with the client.
+///
+/// New fields are appended at the end. `migrate` never reads them from an
+/// older account: shrinking an `Option` leaves stale bytes past the
+/// serialized struct, so it decodes by version and defaults the rest.
+#[account]
+#[derive(InitSpace)]
+pub struct PrivateState {
+    /// Layout version (1 byte)
+    /// Equal to CURRENT_STATE_VERSION for accounts created or migrated by this build
+    pub version: u8,
+
+    /// Authority that can update this account (32 bytes)
+    pub authority: Pubkey,
+
+    /// SHA-256 commitment hash (32 bytes)
+    /// Computed as: sha256(nonce || encrypted_payload)
+    pub commitment: [u8; 32],
+
+    /// Monotonically increasing nonce (8 bytes)
+    /// Prevents replay attacks and ensures ordering
+    pub nonce: u64,
+
+    /// Update policy (1 byte)
+    /// 0 = StrictSequential, 1 = AllowSkips, 2 = StrictSequentialWithCap
+    pub policy: u8,
+
+    /// Number of significant leading commitment bytes (1 byte)
+    /// Trailing bytes are zero padding; 32 for SHA-256
+    pub commitment_len: u8,
+
+    /// Whether reset operations are permitted (1 byte)
+    pub allow_reset: bool,
+
+    /// Authority proposed by a pending two-step transfer (33 bytes)
+    /// None when no transfer is in progress
+    pub pending_authority: Option<Pubkey>,
+
+    /// PDA bump seed (1 byte)
+    /// Only meaningful for accounts created with initialize_pda; 0 otherwise
+    pub bump: u8,
+
+    /// Nonce ceiling (8 bytes)
+    /// Only enforced under StrictSequentialWithCap
+    pub max_nonce: u64,
+
+    /// Minimum number of slots between consecutive updates (8 bytes)
+    /// 0 disables the delay
+    pub min_update_slots: u64,
+
+    /// Slot of the most recent update, or of initialization (8 bytes)
+    pub last_update_slot: u64,
+
+    /// Whether mutations are paused by `freeze` (1 byte)
+    pub frozen: bool,
+
+    /// Key allowed to call update_as_delegate (33 bytes)
+    /// None when no delegate is set
+    pub delegate: Option<Pubkey>,
+
+    /// Hash algorithm the commitments are computed with (1 byte)
+    /// 0 = sha256, 1 = keccak256
+    pub hash_algo: u8,
+
+    /// Number of successful updates, independent of nonce values (8 bytes)
+    /// Under AllowSkips the nonce can jump; this always advances by exactly 1
+    pub update_count: u64,
+
+    /// Number of resets (8 bytes)
+    /// Incremented whenever the nonce restarts at 0
+    pub epoch: u64,
+
+    /// Ring buffer of the most recent commitments, current one included (128 bytes)
+    /// Cleared down to the fresh commitment on reset
+    pub recent: [[u8; 32]; RECENT_COMMITMENTS],
+
+    /// Index of the newest entry in `recent` (1 byte)
+    pub recent_head: u8,
+
+    /// First slot of the current rate-limit window (8 bytes)
+    pub window_start_slot: u64,
+
+    /// Updates accepted in the current window (2 bytes)
+    pub updates_in_window: u16,
+
+    /// Updates allowed per window, 0 = unlimited (2 bytes)
+    pub max_updates_per_window: u16,
+
+    /// Rate-limit window length in slots, 0 = unlimited (8 bytes)
+    pub window_len_slots: u64,
+
+    /// Whether commitment logging is suppressed by `set_quiet` (1 byte)
+    pub quiet: bool,
+
+    /// Last slot at which the state is valid for gating, 0 = never expires (8 bytes)
+    /// Updates are still accepted after expiry
+    pub expiry_slot: u64,
+
+    /// Whether the configuration is permanently locked by `seal` (1 byte)
+    pub sealed: bool,
+
+    /// Lamports the authority pays per update, 0 = free (8 bytes)
+    pub fee_lamports: u64,
+
+    /// Wallet that receives update fees (32 bytes)
+    pub fee_destination: Pubkey,
+
+    /// Whether `update` rejects an unchanged commitment (1 byte)
+    pub reject_noop: bool,
+
+    /// Key allowed to `recover_authority` after inactivity (1 + 32 bytes)
+    pub recovery: Option<Pubkey>,
+
+    /// Slots without an update before recovery is allowed (8 bytes)
+    pub inactivity_slots: u64,
+
+    /// Slot the account was initialized in, never changed (8 bytes)
+    /// 0 for accounts created before v13
+    pub created_slot: u64,
+
+    /// Operator-defined reason passed to the last `freeze` (1 byte)
+    /// 0 while not frozen
+    pub freeze_reason: u8,
+
+    /// Wallet that receives the treasury share in `close_state_split` (32 bytes)
+    pub treasury: Pubkey,
+
+    /// Treasury share of the lamports reclaimed by `close_state_split` (2 bytes)
+    /// In basis points, 0 = everything goes to the authority
+    pub treasury_bps: u16,
+}
+
+impl PrivateState {
+    /// Decodes an account of any versioned layout, reading only the fields its
+    /// `version` had and defaulting the rest.
+    ///
+    /// Anchor writes accounts without clearing the bytes past the struct, so
+    /// after e.g. `revoke_delegate` (a 33-byte `Some` becoming a 1-byte `None`)
+    /// an older account carries stale bytes exactly where newer fields would
+    /// be decoded.
+    ///
+    /// ```
+    /// use anchor_lang::{AccountSerialize, AnchorDeserialize};
+    /// use anchor_lang::prelude::Pubkey;
+    /// use private_state_toolkit::{PrivateState, PRIVATE_STATE_SPACE};
+    ///
+    /// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+    /// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+    /// state.version = 12;
+    /// state.delegate = Some(Pubkey::new_unique());
+    /// state.fee_destination = Pubkey::new_unique();
+    /// state.inactivity_slots = u64::MAX;
+    ///
+    /// // v12 ends at inactivity_slots (no created_slot, freeze_reason,
+    /// // treasury or treasury_bps)
+    /// let write_v12 = |state: &PrivateState, data: &mut [u8]| {
+    ///     let mut bytes = Vec::new();
+    ///     state.try_serialize(&mut bytes).unwrap();
+    ///     bytes.truncate(bytes.len() - (8 + 1 + 32 + 2));
+    ///     data[..bytes.len()].copy_from_slice(&bytes);
+    /// };
+    /// let mut data = [0u8; PRIVATE_STATE_SPACE];
+    /// write_v12(&state, &mut data);
+    /// state.delegate = None; // revoke_delegate
+    /// write_v12(&state, &mut data);
+    ///
+    /// let decoded = PrivateState::try_deserialize_versioned(&data).unwrap();
+    /// assert_eq!(decoded.delegate, None);
+    /// assert_eq!(decoded.inactivity_slots, u64::MAX);
+    /// assert_eq!(decoded.created_slot, 0);
+    /// assert_eq!(decoded.freeze_reason, 0);
+    /// ```
+    pub fn try_deserialize_versioned(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() >= 8 && data[..8] == PrivateState::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        let data = &mut &data[8..];
+        let version: u8 = read_field(data)?;
+        Ok(Self {
+            version,
+            authority: read_field(data)?,
+            commitment: read_field(data)?,
+            nonce: read_field(data)?,
+            policy: read_field(data)?,
+            commitment_len: read_field(data)?,
+            allow_reset: read_field(data)?,
+            pending_authority: read_field(data)?,
+            bump: read_field(data)?,
+            max_nonce: read_field(data)?,
+            min_update_slots: read_field(data)?,
+            last_update_slot: read_field(data)?,
+            frozen: read_field(data)?,
+            delegate: read_field(data)?,
+            hash_algo: read_field_since(data, version, 2)?,
+            update_count: read_field_since(data, version, 3)?,
+            epoch: read_field_since(data, version, 4)?,
+            recent: read_field_since(data, version, 5)?,
+            recent_head: read_field_since(data, version, 5)?,
+            window_start_slot: read_field_since(data, version, 6)?,
+            updates_in_window: read_field_since(data, version, 6)?,
+            max_updates_per_window: read_field_since(data, version, 6)?,
+            window_len_slots: read_field_since(data, version, 6)?,
+            quiet: read_field_since(data, version, 7)?,
+            expiry_slot: read_field_since(data, version, 8)?,
+            sealed: read_field_since(data, version, 9)?,
+            fee_lamports: read_field_since(data, version, 10)?,
+            fee_destination: read_field_since(data, version, 10)?,
+            reject_noop: read_field_since(data, version, 11)?,
+            recovery: read_field_since(data, version, 12)?,
+            inactivity_slots: read_field_since(data, version, 12)?,
+            created_slot: read_field_since(data, version, 13)?,
+            freeze_reason: read_field_since(data, version, 14)?,
+            treasury: read_field_since(data, version, 15)?,
+            treasury_bps: read_field_since(data, version, 15)?,
+        })
+    }
+
+    /// Whether the state currently holds `expected_commitment` at `expected_nonce`.
+    ///
+    /// The same check as `assert_state`, for programs that read the account
+    /// directly instead of calling it over CPI.
+    pub fn matches(&self, expected_commitment: &[u8; 32], expected_nonce: u64) -> bool {
+        commitment_matches(self, expected_commitment) && self.nonce == expected_nonce
+    }
+
+    /// Whether the state is past its `expiry_slot` at `slot`.
+    pub fn is_expired(&self, slot: u64) -> bool {
+        is_past_expiry(self.expiry_slot, slot)
+    }
+
+    /// The `recent` ring buffer ordered newest first.
+    ///
+    /// Slots never written since the last reset (or migration) are all zeros.
+    pub fn recent_commitments(&self) -> [[u8; 32]; RECENT_COMMITMENTS] {
+        let head = self.recent_head as usize;
+        core::array::from_fn(|i| self.recent[(head + RECENT_COMMITMENTS - i) % RECENT_COMMITMENTS])
+    }
+
+    /// The commitment the last update replaced (the entry before `recent_head`).
+    ///
+    /// All zeros if there was no update since initialization or the last reset.
+    ///
+    /// ```
+    /// use anchor_lang::AnchorDeserialize;
+    /// use private_state_toolkit::{PrivateState, PRIVATE_STATE_SPACE};
+    ///
+    /// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+    /// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+    /// assert_eq!(state.previous_commitment(), [0; 32]);
+    ///
+    /// state.recent[3] = [1; 32];
+    /// state.recent[0] = [2; 32];
+    /// assert_eq!(state.previous_commitment(), [1; 32]);
+    /// ```
+    pub fn previous_commitment(&self) -> [u8; 32] {
+        let head = self.recent_head as usize;
+        self.recent[(head + RECENT_COMMITMENTS - 1) % RECENT_COMMITMENTS]
+    }
+
+    /// `recent_head` followed by the `recent` ring ordered oldest first.
+    ///
+    /// The `read_history` return data. Slots never written since the last
+    /// reset come first, as all zeros.
+    ///
+    /// ```
+    /// use anchor_lang::AnchorDeserialize;
+    /// use private_state_toolkit::{PrivateState, HISTORY_LEN, PRIVATE_STATE_SPACE};
+    ///
+    /// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+    /// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+    /// state.recent[0] = [1; 32];
+    /// state.recent[1] = [2; 32];
+    /// state.recent_head = 1;
+    ///
+    /// let history = state.history();
+    /// assert_eq!(history.len(), HISTORY_LEN);
+    /// assert_eq!(history[0], 1);
+    /// assert_eq!(history[1..65], [0; 64]);
+    /// assert_eq!(history[65..97], [1; 32]);
+    /// assert_eq!(history[97..], [2; 32]);
+    /// ```
+    pub fn history(&self) -> [u8; HISTORY_LEN] {
+        let mut history = [0u8; HISTORY_LEN];
+        history[0] = self.recent_head;
+        let newest_first = self.recent_commitments();
+        for (entry, commitment) in history[1..]
+            .chunks_exact_mut(32)
+            .zip(newest_first.iter().rev())
+        {
+            entry.copy_from_slice(commitment);
+        }
+        history
+    }
+
+    /// `HEALTH_*` flags for every limit the account is close to at `slot`.
+    ///
+    /// "Close" means at most `1 / HEALTH_WARNING_FRACTION` of the limit is left:
+    /// nonces below `max_nonce` (StrictSequentialWithCap only), updates left in
+    /// the current rate-limit window, or slots left before `expiry_slot` out of
+    /// the account's lifetime since `created_slot`.
+    ///
+    /// ```
+    /// use anchor_lang::AnchorDeserialize;
+    /// use private_state_toolkit::{
+    ///     PrivateState, UpdatePolicy, HEALTH_NEAR_EXPIRY, HEALTH_NEAR_NONCE_CAP,
+    ///     PRIVATE_STATE_SPACE,
+    /// };
+    ///
+    /// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+    /// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+    /// state.policy = UpdatePolicy::StrictSequentialWithCap as u8;
+    /// state.max_nonce = 100;
+    /// state.nonce = 89;
+    /// assert_eq!(state.health_conditions(0), 0);
+    /// state.nonce = 90;
+    /// assert_eq!(state.health_conditions(0), HEALTH_NEAR_NONCE_CAP);
+    ///
+    /// state.created_slot = 1_000;
+    /// state.expiry_slot = 2_000;
+    /// assert_eq!(state.health_conditions(1_899), HEALTH_NEAR_NONCE_CAP);
+    /// assert_eq!(state.health_conditions(1_900), HEALTH_NEAR_NONCE_CAP | HEALTH_NEAR_EXPIRY);
+    /// ```
+    pub fn health_conditions(&self, slot: u64) -> u8 {
+        let mut conditions = 0;
+        let policy = UpdatePolicy::try_from(self.policy).ok();
+        if matches!(policy, Some(UpdatePolicy::StrictSequentialWithCap))
+            && self.max_nonce > 0
+            && self.max_nonce.saturating_sub(self.nonce) <= self.max_nonce / HEALTH_WARNING_FRACTION
+        {
+            conditions |= HEALTH_NEAR_NONCE_CAP;
+        }
+        if self.max_updates_per_window > 0 && self.window_len_slots > 0 {
+            let limit = u64::from(self.max_updates_per_window);
+            let left = limit.saturating_sub(u64::from(self.updates_in_window));
+            if left <= limit / HEALTH_WARNING_FRACTION {
+                conditions |= HEALTH_NEAR_RATE_LIMIT;
+            }
+        }
+        if self.expiry_slot > 0 {
+            let lifetime = self.expiry_slot.saturating_sub(self.created_slot);
+            if self.expiry_slot.saturating_sub(slot) <= lifetime / HEALTH_WARNING_FRACTION {
+                conditions |= HEALTH_NEAR_EXPIRY;
+            }
+        }
+        conditions
+    }
+
+    /// `STATUS_*` flags for every restricted state the account is in at `slot`.
+    ///
+    /// ```
+    /// use anchor_lang::AnchorDeserialize;
+    /// use private_state_toolkit::{
+    ///     PrivateState, PRIVATE_STATE_SPACE, STATUS_EXPIRED, STATUS_FROZEN, STATUS_SEALED,
+    /// };
+    ///
+    /// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+    /// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+    /// assert_eq!(state.status(100), 0);
+    ///
+    /// state.frozen = true;
+    /// state.expiry_slot = 50;
+    /// assert_eq!(state.status(100), STATUS_FROZEN | STATUS_EXPIRED);
+    ///
+    /// state.frozen = false;
+    /// state.sealed = true;
+    /// assert_eq!(state.status(50), STATUS_SEALED);
+    /// ```
+    pub fn status(&self, slot: u64) -> u8 {
+        let mut status = 0;
+        if self.frozen {
+            status |= STATUS_FROZEN;
+        }
+        if self.sealed {
+            status |= STATUS_SEALED;
+        }
+        if self.is_expired(slot) {
+            status |= STATUS_EXPIRED;
+        }
+        status
+    }
+}
+
+/// Programs allowed to gate on a private state via `assert_state_restricted`.
+///
+/// **Total size: 300 bytes** (`CALLER_ALLOWLIST_SPACE`: 8-byte discriminator +
+/// 292 bytes data, sized for `MAX_ALLOWED_CALLERS`)
+///
+/// PDA seeds: `[b"allowed_callers", private_state]`
+#[account]
+pub struct CallerAllowlist {
+    /// The private state account this list applies to (32 bytes)
+    pub private_state: Pubkey,
+
+    /// Approved consumer program ids (4-byte length + 32 bytes each)
+    pub callers: Vec<Pubkey>,
+}
+
+/// Write-once metadata attached to a private state account.
+///
+/// **Total size: 104 bytes** (8-byte discriminator + 96 bytes data)
+///
+/// PDA seeds: `[b"meta", private_state]`
+#[account]
+pub struct MetaData {
+    /// The private state account this metadata belongs to (32 bytes)
+    pub private_state: Pubkey,
+
+    /// Opaque immutable blob (64 bytes)
+    pub blob: [u8; METADATA_LEN],
+}
+
+/// Weighted guardian set attached to a private state account.
+///
+/// **Total size: 386 bytes** (`GUARDIAN_SET_SPACE`: 8-byte discriminator + 378
+/// bytes data, sized for `MAX_GUARDIANS`)
+///
+/// PDA seeds: `[b"guardians", private_state]`
+#[account]
+pub struct GuardianSet {
+    /// The private state account these guardians control (32 bytes)
+    pub private_state: Pubkey,
+
+    /// Total guardian weight required by multisig_update (2 bytes)
+    pub weight_threshold: u16,
+
+    /// Guardian keys and weights (4-byte length + 34 bytes each)
+    pub guardians: Vec<WeightedGuardian>,
+}
+
+/// Vector clock of the devices editing a private state via `update_causal`.
+///
+/// **Total size: 188 bytes** (`CAUSAL_CLOCK_SPACE`: 8-byte discriminator + 180
+/// bytes data, sized for `MAX_DEVICE_CLOCKS`)
+///
+/// PDA seeds: `[b"causal_clock", private_state]`
+#[account]
+pub struct CausalClock {
+    /// The private state account this clock belongs to (32 bytes)
+    pub private_state: Pubkey,
+
+    /// Per-device counters, sorted by device id (4-byte length + 9 bytes each)
+    pub device_clocks: Vec<DeviceClock>,
+}
+
+/// Opt-in record that a consumer program gates on a private state account.
+///
+/// **Total size: 112 bytes** (8-byte discriminator + 104 bytes data)
+///
+/// PDA seeds: `[b"consumer_link", private_state, consumer_program]`
+#[account]
+pub struct ConsumerLink {
+    /// The private state account being depended on (32 bytes)
+    pub private_state: Pubkey,
+
+    /// The consumer program gating on it (32 bytes)
+    pub consumer_program: Pubkey,
+
+    /// Who registered the link and paid its rent (32 bytes)
+    pub registrar: Pubkey,
+
+    /// Slot at which the link was registered (8 bytes)
+    pub registered_slot: u64,
+}
+
+/// Append-only Merkle tree of private states, stored as its root.
+///
+/// **Total size: 592 bytes** (`MERKLE_REGISTRY_SPACE`: 8-byte discriminator +
+/// 584 bytes data, sized for `MERKLE_DEPTH`)
+#[account]
+pub struct MerkleRegistry {
+    /// Key allowed to register leaves (32 bytes)
+    pub authority: Pubkey,
+
+    /// Current root over all registered leaves (32 bytes)
+    pub root: [u8; 32],
+
+    /// Number of registered leaves, also the next leaf's index (8 bytes)
+    pub count: u64,
+
+    /// Rightmost left-child hash at each level (32 bytes each)
+    /// Lets `register_leaf` update the root without the existing leaves
+    pub filled_subtrees: [[u8; 32]; MERKLE_DEPTH],
+}
+
+/// Several independent namespaced states in one account.
+///
+/// **Total size: 709 bytes** (8-byte discriminator + 701 bytes data, sized for
+/// `MAX_MULTI_ENTRIES`)
+#[account]
+pub struct MultiState {
+    /// The public key authorized to update every entry (32 bytes)
+    pub authority: Pubkey,
+
+    /// Update policy applied to every entry (1 byte)
+    pub policy: u8,
+
+    /// Per-entry nonce ceiling for StrictSequentialWithCap (8 bytes)
+    pub max_nonce: u64,
+
+    /// Namespaced entries (4-byte length + 41 bytes each)
+    pub entries: Vec<MultiStateEntry>,
+}
+
+/// The original, unversioned 81-byte `PrivateState` layout.
+///
+/// Only read by `migrate`; it shares `PrivateState`'s discriminator.
+#[derive(AnchorDeserialize)]
+struct LegacyPrivateState {
+    authority: Pubkey,
+    commitment: [u8; 32],
+    nonce: u64,
+    policy: u8,
+}
+
+// ============================================================================
+// Instruction Contexts
+// ============================================================================
+
+/// Accounts for the initialize instruction.
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// The private state account to create
+    /// Space: PRIVATE_STATE_SPACE
+    #[account(init, payer = authority, space = PRIVATE_STATE_SPACE)]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account (pays for creation)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the initialize_registry instruction.
+#[derive(Accounts)]
+pub struct InitializeRegistry<'info> {
+    /// The registry account to create
+    /// Space: MERKLE_REGISTRY_SPACE
+    #[account(init, payer = authority, space = MERKLE_REGISTRY_SPACE)]
+    pub registry: Account<'info, MerkleRegistry>,
+
+    /// The authority who may register leaves (pays for creation)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the register_leaf instruction.
+#[derive(Accounts)]
+pub struct RegisterLeaf<'info> {
+    /// The registry to append to
+    #[account(mut, has_one = authority)]
+    pub registry: Account<'info, MerkleRegistry>,
+
+    /// The registry's authority (must sign)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the assert_membership instruction.
+#[derive(Accounts)]
+pub struct AssertMembership<'info> {
+    /// The registry to check against (read-only)
+    pub registry: Account<'info, MerkleRegistry>,
+}
+
+/// Accounts for the initialize_multi instruction.
+#[derive(Accounts)]
+pub struct InitializeMulti<'info> {
+    /// The multi-state account to create
+    /// Space: MULTI_STATE_SPACE
+    #[account(init, payer = authority, space = MULTI_STATE_SPACE)]
+    pub multi_state: Account<'info, MultiState>,
+
+    /// The authority who owns this account (pays for creation)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the update_multi instruction.
+#[derive(Accounts)]
+pub struct UpdateMulti<'info> {
+    /// The multi-state account to update
+    #[account(mut, has_one = authority)]
+    pub multi_state: Account<'info, MultiState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the assert_state_multi instruction.
+#[derive(Accounts)]
+pub struct AssertMulti<'info> {
+    /// The multi-state account to validate (read-only)
+    pub multi_state: Account<'info, MultiState>,
+}
+
+/// Accounts for the initialize_pda instruction.
+#[derive(Accounts)]
+#[instruction(label: [u8; 16])]
+pub struct InitializePda<'info> {
+    /// The private state PDA to create
+    /// Space: PRIVATE_STATE_SPACE
+    /// CHECK: Address pinned by seeds; must still be empty (created by the instruction)
+    #[account(
+        mut,
+        seeds = [b"pst", authority.key().as_ref(), label.as_ref()],
+        bump,
+        constraint = private_state.data_is_empty() @ PrivateStateError::AlreadyInitialized
+    )]
+    pub private_state: UncheckedAccount<'info>,
+
+    /// The authority who owns this account (pays for creation)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the update instruction.
+#[derive(Accounts)]
+pub struct Update<'info> {
+    /// The private state account to update
+    /// has_one = authority ensures only the authority can update
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account (pays the update fee, if any)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Receives the update fee; may be omitted while `fee_lamports` is 0
+    /// CHECK: Pinned to the account's fee_destination
+    #[account(
+        mut,
+        address = private_state.fee_destination @ PrivateStateError::InvalidFeeDestination
+    )]
+    pub fee_destination: Option<UncheckedAccount<'info>>,
+
+    /// System program for the fee transfer; may be omitted while `fee_lamports` is 0
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Accounts for the update_as_delegate instruction.
+#[derive(Accounts)]
+pub struct UpdateAsDelegate<'info> {
+    /// The private state account to update
+    /// The signer must be the authority or the (set) delegate
+    #[account(
+        mut,
+        constraint = private_state.authority == updater.key()
+            || private_state.delegate == Some(updater.key())
+            @ PrivateStateError::NotAuthorizedUpdater,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority or delegate submitting the update (pays the update fee, if any)
+    #[account(mut)]
+    pub updater: Signer<'info>,
+
+    /// Receives the update fee; may be omitted while `fee_lamports` is 0
+    /// CHECK: Pinned to the account's fee_destination
+    #[account(
+        mut,
+        address = private_state.fee_destination @ PrivateStateError::InvalidFeeDestination
+    )]
+    pub fee_destination: Option<UncheckedAccount<'info>>,
+
+    /// System program for the fee transfer; may be omitted while `fee_lamports` is 0
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Accounts for the reset instruction.
+#[derive(Accounts)]
+pub struct Reset<'info> {
+    /// The private state account to reset
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the transfer_authority instruction.
+#[derive(Accounts)]
+pub struct TransferAuthority<'info> {
+    /// The private state account whose authority is being transferred
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The current authority (must sign)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the recover_authority instruction.
+#[derive(Accounts)]
+pub struct RecoverAuthority<'info> {
+    /// The private state account being recovered
+    #[account(
+        mut,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = private_state.recovery == Some(recovery.key())
+            @ PrivateStateError::UnauthorizedRecovery
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The recovery key designated at initialization (must sign)
+    pub recovery: Signer<'info>,
+}
+
+/// Accounts for the migrate instruction.
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// The private state account to upgrade
+    /// CHECK: May still use an older layout; owner pinned here, discriminator
+    /// and authority checked by the instruction
+    #[account(mut, owner = crate::ID)]
+    pub private_state: UncheckedAccount<'info>,
+
+    /// The authority who owns this account (pays for the extra rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for the rent top-up
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the state_size instruction.
+#[derive(Accounts)]
+pub struct StateSize<'info> {
+    /// The private state account to measure (read-only)
+    /// CHECK: May use any layout version; only the owner is checked
+    #[account(owner = crate::ID)]
+    pub private_state: UncheckedAccount<'info>,
+}
+
+/// Accounts for the close instruction.
+#[derive(Accounts)]
+pub struct Close<'info> {
+    /// The private state account to close (lamports go to authority)
+    #[account(
+        mut,
+        has_one = authority,
+        close = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account (receives the rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the close_state_split instruction.
+#[derive(Accounts)]
+pub struct CloseSplit<'info> {
+    /// The private state account to close (remaining lamports go to authority)
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = treasury @ PrivateStateError::InvalidTreasury,
+        close = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account (receives the authority share)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The treasury configured with `set_close_split` (receives its share)
+    /// CHECK: Pinned by has_one; only credited with lamports
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+}
+
+/// Accounts for the accept_authority_transfer instruction.
+#[derive(Accounts)]
+pub struct AcceptAuthorityTransfer<'info> {
+    /// The private state account being handed over
+    #[account(
+        mut,
+        constraint = private_state.pending_authority == Some(new_authority.key())
+            @ PrivateStateError::NotPendingAuthority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The proposed authority (must sign to prove it controls the key)
+    pub new_authority: Signer<'info>,
+}
+
+/// Accounts for the set_policy instruction.
+#[derive(Accounts)]
+pub struct SetPolicy<'info> {
+    /// The private state account whose policy is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_allow_reset instruction.
+#[derive(Accounts)]
+pub struct SetAllowReset<'info> {
+    /// The private state account whose reset permission is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_quiet instruction.
+#[derive(Accounts)]
+pub struct SetQuiet<'info> {
+    /// The private state account whose quiet mode is being changed
+    #[account(mut, has_one = authority)]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_reject_noop instruction.
+#[derive(Accounts)]
+pub struct SetRejectNoop<'info> {
+    /// The private state account whose no-op rule is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_expiry instruction.
+#[derive(Accounts)]
+pub struct SetExpiry<'info> {
+    /// The private state account whose expiry is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_fee instruction.
+#[derive(Accounts)]
+pub struct SetFee<'info> {
+    /// The private state account whose update fee is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_close_split instruction.
+#[derive(Accounts)]
+pub struct SetCloseSplit<'info> {
+    /// The private state account whose close split is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_recovery instruction.
+#[derive(Accounts)]
+pub struct SetRecovery<'info> {
+    /// The private state account whose recovery key is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_rate_limit instruction.
+#[derive(Accounts)]
+pub struct SetRateLimit<'info> {
+    /// The private state account whose rate limit is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_delegate and revoke_delegate instructions.
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    /// The private state account whose delegate is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account (delegates cannot sign here)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the seal instruction.
+#[derive(Accounts)]
+pub struct Seal<'info> {
+    /// The private state account being sealed
+    #[account(mut, has_one = authority)]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the freeze and unfreeze instructions.
+#[derive(Accounts)]
+pub struct SetFrozen<'info> {
+    /// The private state account being frozen or unfrozen
+    #[account(mut, has_one = authority)]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_metadata instruction.
+#[derive(Accounts)]
+pub struct SetMetadata<'info> {
+    /// The private state account the metadata is attached to
+    #[account(has_one = authority)]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The metadata PDA to create
+    /// CHECK: Address pinned by seeds; must still be empty (created by the instruction)
+    #[account(
+        mut,
+        seeds = [b"meta", private_state.key().as_ref()],
+        bump,
+        constraint = metadata.data_is_empty() @ PrivateStateError::MetadataAlreadySet
+    )]
+    pub metadata: UncheckedAccount<'info>,
+
+    /// The authority who owns the private state (pays rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the set_guardians instruction.
+#[derive(Accounts)]
+pub struct SetGuardians<'info> {
+    /// The private state account the guardians are attached to
+    #[account(
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The guardian set PDA (created on first call, overwritten afterwards)
+    /// CHECK: Address pinned by seeds; owner checked before overwriting
+    #[account(
+        mut,
+        seeds = [b"guardians", private_state.key().as_ref()],
+        bump
+    )]
+    pub guardian_set: UncheckedAccount<'info>,
+
+    /// The authority who owns the private state (pays rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the update_causal instruction.
+#[derive(Accounts)]
+pub struct UpdateCausal<'info> {
+    /// The private state account to update
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The vector clock PDA (created on first call)
+    /// CHECK: Address pinned by seeds; owner checked before reading
+    #[account(
+        mut,
+        seeds = [b"causal_clock", private_state.key().as_ref()],
+        bump
+    )]
+    pub causal_clock: UncheckedAccount<'info>,
+
+    /// The authority who owns this account (pays rent on first use and the update fee)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Receives the update fee; may be omitted while `fee_lamports` is 0
+    /// CHECK: Pinned to the account's fee_destination
+    #[account(
+        mut,
+        address = private_state.fee_destination @ PrivateStateError::InvalidFeeDestination
+    )]
+    pub fee_destination: Option<UncheckedAccount<'info>>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the register_consumer instruction.
+#[derive(Accounts)]
+pub struct RegisterConsumer<'info> {
+    /// The private state account the consumer depends on
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The consumer program being registered
+    /// CHECK: Only its address is recorded; must be an executable program
+    #[account(executable)]
+    pub consumer_program: UncheckedAccount<'info>,
+
+    /// The consumer link PDA to create
+    /// CHECK: Address pinned by seeds; must still be empty (created by the instruction)
+    #[account(
+        mut,
+        seeds = [
+            b"consumer_link",
+            private_state.key().as_ref(),
+            consumer_program.key().as_ref()
+        ],
+        bump,
+        constraint = consumer_link.data_is_empty() @ PrivateStateError::ConsumerAlreadyRegistered
+    )]
+    pub consumer_link: UncheckedAccount<'info>,
+
+    /// Whoever registers the link (pays rent)
+    #[account(mut)]
+    pub registrar: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the deregister_consumer instruction.
+#[derive(Accounts)]
+pub struct DeregisterConsumer<'info> {
+    /// The consumer link to close (lamports go to the registrar)
+    #[account(mut, has_one = registrar, close = registrar)]
+    pub consumer_link: Account<'info, ConsumerLink>,
+
+    /// The signer who registered the link
+    #[account(mut)]
+    pub registrar: Signer<'info>,
+}
+
+/// Accounts for the multisig_update instruction.
+///
+/// Guardian signers are passed as `remaining_accounts`.
+#[derive(Accounts)]
+pub struct MultisigUpdate<'info> {
+    /// The private state account to update
+    #[account(
+        mut,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The guardian set authorizing the update
+    #[account(
+        seeds = [b"guardians", private_state.key().as_ref()],
+        bump,
+        has_one = private_state
+    )]
+    pub guardian_set: Account<'info, GuardianSet>,
+
+    /// Pays the update fee; may be omitted while `fee_lamports` is 0
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+
+    /// Receives the update fee; may be omitted while `fee_lamports` is 0
+    /// CHECK: Pinned to the account's fee_destination
+    #[account(
+        mut,
+        address = private_state.fee_destination @ PrivateStateError::InvalidFeeDestination
+    )]
+    pub fee_destination: Option<UncheckedAccount<'info>>,
+
+    /// System program for the fee transfer; may be omitted while `fee_lamports` is 0
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Accounts for the set_commitment_len instruction.
+#[derive(Accounts)]
+pub struct SetCommitmentLen<'info> {
+    /// The private state account whose commitment length is being changed
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = !private_state.frozen @ PrivateStateError::StateFrozen,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The authority who owns this account
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_policy_many instruction.
+///
+/// The target accounts are passed as `remaining_accounts`.
+#[derive(Accounts)]
+pub struct SetPolicyMany<'info> {
+    /// The authority who owns every account in the batch
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the bump_group instruction.
+///
+/// The grouped accounts are passed as `remaining_accounts`.
+#[derive(Accounts)]
+pub struct BumpGroup<'info> {
+    /// The authority who owns every account in the group (pays any update fees)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Receives the update fees; may be omitted while no account charges one
+    /// CHECK: Checked against each account's fee_destination
+    #[account(mut)]
+    pub fee_destination: Option<UncheckedAccount<'info>>,
+
+    /// System program for the fee transfers; may be omitted while no account
+    /// charges a fee
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Accounts for the assert_state instruction.
+///
+/// This context is intentionally minimal (read-only) to be CPI-friendly.
+/// Other programs can validate state without needing to be the authority.
+#[derive(Accounts)]
+pub struct AssertState<'info> {
+    /// The private state account to validate (read-only)
+    pub private_state: Account<'info, PrivateState>,
+}
+
+/// Accounts for the assert_state_signed instruction.
+#[derive(Accounts)]
+pub struct AssertStateSigned<'info> {
+    /// The private state account to validate (read-only)
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The key the assertion is bound to
+    pub requester: Signer<'info>,
+}
+
+/// Accounts for the assert_pair instruction.
+#[derive(Accounts)]
+pub struct AssertPair<'info> {
+    /// First private state account of the pair (read-only)
+    pub state_a: Account<'info, PrivateState>,
+
+    /// Second private state account of the pair (read-only)
+    pub state_b: Account<'info, PrivateState>,
+}
+
+/// Accounts for the assert_authority_signed instruction.
+///
+/// Read-only like `AssertState`, plus the instructions sysvar for introspection.
+#[derive(Accounts)]
+pub struct AssertAuthoritySigned<'info> {
+    /// The private state account whose authority must have signed (read-only)
+    pub private_state: Account<'info, PrivateState>,
+
+    /// CHECK: Address is pinned to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+/// Accounts for the set_allowed_callers instruction.
+#[derive(Accounts)]
+pub struct SetAllowedCallers<'info> {
+    /// The private state account the allowlist applies to
+    #[account(
+        has_one = authority,
+        constraint = !private_state.sealed @ PrivateStateError::StateSealed
+    )]
     pub private_state: Account<'info, PrivateState>,
 
-    /// The authority who owns this account
-    pub authority: Signer<'info>,
+    /// The allowlist PDA (created on first call, overwritten afterwards)
+    /// CHECK: Address pinned by seeds; owner checked before overwriting
+    #[account(
+        mut,
+        seeds = [b"allowed_callers", private_state.key().as_ref()],
+        bump
+    )]
+    pub allowed_callers: UncheckedAccount<'info>,
+
+    /// The authority who owns the private state (pays rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for the assert_state_restricted instruction.
+///
+/// Read-only like `AssertState`, plus the allowlist and the instructions sysvar.
+#[derive(Accounts)]
+pub struct AssertStateRestricted<'info> {
+    /// The private state account to validate (read-only)
+    pub private_state: Account<'info, PrivateState>,
+
+    /// The account's caller allowlist
+    #[account(
+        seeds = [b"allowed_callers", private_state.key().as_ref()],
+        bump
+    )]
+    pub allowed_callers: Account<'info, CallerAllowlist>,
+
+    /// CHECK: Address is pinned to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+// ============================================================================
+// Types and Enums
+// ============================================================================
+
+/// Layout version written by this build of the program.
+///
+/// Bump this whenever a field is appended to `PrivateState`, and read the
+/// field from that version on in `PrivateState::try_deserialize_versioned`.
+pub const CURRENT_STATE_VERSION: u8 = 15;
+
+/// Account size of the current `PrivateState` layout, in bytes.
+///
+/// 8 (discriminator) + the struct's `InitSpace`, so it always matches the
+/// fields (with every `Option` counted as `Some`). Clients read it through
+/// `state_size` instead of hardcoding a size.
+///
+/// When appending a field, bump `CURRENT_STATE_VERSION` and mirror the field
+/// in `StateSnapshot` if it is public.
+///
+/// ```
+/// use anchor_lang::{AnchorDeserialize, AnchorSerialize, Space};
+/// use anchor_lang::prelude::Pubkey;
+/// use private_state_toolkit::{PrivateState, PRIVATE_STATE_SPACE};
+///
+/// let zeroed = [0u8; PRIVATE_STATE_SPACE - 8];
+/// let mut state = PrivateState::deserialize(&mut &zeroed[..]).unwrap();
+/// state.pending_authority = Some(Pubkey::new_unique());
+/// state.delegate = Some(Pubkey::new_unique());
+/// state.recovery = Some(Pubkey::new_unique());
+///
+/// assert_eq!(PRIVATE_STATE_SPACE, 8 + PrivateState::INIT_SPACE);
+/// assert_eq!(8 + state.try_to_vec().unwrap().len(), PRIVATE_STATE_SPACE);
+/// ```
+pub const PRIVATE_STATE_SPACE: usize = 8 + PrivateState::INIT_SPACE;
+
+/// Denominator of basis-point ratios (`close_state_split`).
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Number of commitments kept in `PrivateState::recent`.
+pub const RECENT_COMMITMENTS: usize = 4;
+
+/// Size of the `read_history` return data: the head byte plus every ring entry.
+pub const HISTORY_LEN: usize = 1 + 32 * RECENT_COMMITMENTS;
+
+/// Size of the nonce band reserved below `u64::MAX`.
+pub const NONCE_RESERVED_BAND: u64 = 1 << 16;
+
+/// Highest nonce an update may set.
+///
+/// Without it, an AllowSkips jump to `u64::MAX - 1` leaves the account one
+/// update away from being permanently stuck.
+pub const NONCE_CEILING: u64 = u64::MAX - NONCE_RESERVED_BAND;
+
+/// Maximum number of namespaces in a `MultiState`.
+pub const MAX_MULTI_ENTRIES: usize = 16;
+
+/// Account size of a `MultiState`, in bytes:
+/// 8 (discriminator) + 32 (authority) + 1 (policy) + 8 (max_nonce)
+/// + 4 + 41 * MAX_MULTI_ENTRIES (entries).
+pub const MULTI_STATE_SPACE: usize = 8 + 32 + 1 + 8 + 4 + 41 * MAX_MULTI_ENTRIES;
+
+/// Account size of the original unversioned layout:
+/// 8 (discriminator) + 32 (authority) + 32 (commitment) + 8 (nonce) + 1 (policy).
+pub const LEGACY_STATE_SPACE: usize = 8 + 32 + 32 + 8 + 1;
+
+/// Maximum number of accounts a single batch instruction may touch.
+///
+/// Keeps compute usage bounded regardless of how many accounts are passed.
+pub const MAX_BATCH_ACCOUNTS: usize = 16;
+
+/// Maximum number of transitions a single `batch_update` may apply.
+pub const MAX_BATCH_TRANSITIONS: usize = 16;
+
+/// Maximum number of guardians in a `GuardianSet`.
+pub const MAX_GUARDIANS: usize = 10;
+
+/// Account size of a `GuardianSet`, in bytes:
+/// 8 (discriminator) + 32 (private_state) + 2 (weight_threshold)
+/// + 4 + 34 * MAX_GUARDIANS (guardians).
+pub const GUARDIAN_SET_SPACE: usize = 8 + 32 + 2 + 4 + 34 * MAX_GUARDIANS;
+
+/// Maximum number of programs in a `CallerAllowlist`.
+pub const MAX_ALLOWED_CALLERS: usize = 8;
+
+/// Account size of a `CallerAllowlist`, in bytes:
+/// 8 (discriminator) + 32 (private_state) + 4 + 32 * MAX_ALLOWED_CALLERS (callers).
+pub const CALLER_ALLOWLIST_SPACE: usize = 8 + 32 + 4 + 32 * MAX_ALLOWED_CALLERS;
+
+/// Maximum number of devices tracked by a `CausalClock`.
+pub const MAX_DEVICE_CLOCKS: usize = 16;
+
+/// Account size of a `CausalClock`, in bytes:
+/// 8 (discriminator) + 32 (private_state) + 4 + 9 * MAX_DEVICE_CLOCKS (device_clocks).
+pub const CAUSAL_CLOCK_SPACE: usize = 8 + 32 + 4 + 9 * MAX_DEVICE_CLOCKS;
+
+/// Maximum payload length accepted by `verify_payload`, in bytes.
+pub const MAX_VERIFY_PAYLOAD_LEN: usize = 1024;
+
+/// Depth of a `MerkleRegistry` tree, and the exact length of a membership proof.
+///
+/// Allows `2^16` leaves while keeping `assert_membership` at 16 hashes.
+pub const MERKLE_DEPTH: usize = 16;
+
+/// Account size of a `MerkleRegistry`, in bytes:
+/// 8 (discriminator) + 32 (authority) + 32 (root) + 8 (count)
+/// + 32 * MERKLE_DEPTH (filled_subtrees).
+pub const MERKLE_REGISTRY_SPACE: usize = 8 + 32 + 32 + 8 + 32 * MERKLE_DEPTH;
+
+/// `assert_state_in_set` nonce sentinel: accept any nonce.
+///
+/// Updates never go past `NONCE_CEILING`, so no live state holds this nonce.
+pub const ANY_NONCE: u64 = u64::MAX;
+
+/// Maximum number of entries in an allowed-set argument (policies, authorities,
+/// commitments).
+pub const MAX_ALLOWED_SET: usize = 8;
+
+/// Size of the immutable metadata blob in bytes.
+pub const METADATA_LEN: usize = 64;
+
+/// `hash_algo` value for sha256 commitments (the default).
+pub const HASH_ALGO_SHA256: u8 = 0;
+
+/// `hash_algo` value for keccak256 commitments (Ethereum tooling).
+pub const HASH_ALGO_KECCAK256: u8 = 1;
+
+/// Commitment length for full-width 32-byte digests (SHA-256).
+pub const FULL_COMMITMENT_LEN: u8 = 32;
+
+/// `check_state` flag: the commitment does not match.
+pub const CHECK_COMMITMENT_MISMATCH: u8 = 1 << 0;
+
+/// `check_state` flag: the nonce does not match.
+pub const CHECK_NONCE_MISMATCH: u8 = 1 << 1;
+
+/// `check_state` flag: the state is past its `expiry_slot`.
+pub const CHECK_EXPIRED: u8 = 1 << 2;
+
+/// Number of leading commitment bytes written to program logs.
+///
+/// Kept short: logs are public, and the prefix is only for eyeballing which
+/// state a transaction touched.
+pub const LOG_PREFIX_LEN: usize = 4;
+
+/// `assert_state_cmp` op: nonce equals the bound.
+pub const NONCE_CMP_EQ: u8 = 0;
+
+/// `assert_state_cmp` op: nonce is greater than or equal to the bound.
+pub const NONCE_CMP_GE: u8 = 1;
+
+/// `assert_state_cmp` op: nonce is greater than the bound.
+pub const NONCE_CMP_GT: u8 = 2;
+
+/// `assert_state_cmp` op: nonce is less than or equal to the bound.
+pub const NONCE_CMP_LE: u8 = 3;
+
+/// `assert_state_cmp` op: nonce is less than the bound.
+pub const NONCE_CMP_LT: u8 = 4;
+
+/// `status` flag: the account is frozen.
+pub const STATUS_FROZEN: u8 = 1 << 0;
+
+/// `status` flag: the account's configuration is sealed.
+pub const STATUS_SEALED: u8 = 1 << 1;
+
+/// `status` flag: the state is past its `expiry_slot`.
+pub const STATUS_EXPIRED: u8 = 1 << 2;
+
+/// Fraction of a limit that triggers `StateHealthWarning`: the warning fires
+/// once at most `1 / HEALTH_WARNING_FRACTION` (10%) of it is left.
+pub const HEALTH_WARNING_FRACTION: u64 = 10;
+
+/// `StateHealthWarning` flag: the nonce is close to `max_nonce`.
+pub const HEALTH_NEAR_NONCE_CAP: u8 = 1 << 0;
+
+/// `StateHealthWarning` flag: the current rate-limit window is nearly used up.
+pub const HEALTH_NEAR_RATE_LIMIT: u8 = 1 << 1;
+
+/// `StateHealthWarning` flag: the state is close to (or past) its `expiry_slot`.
+pub const HEALTH_NEAR_EXPIRY: u8 = 1 << 2;
+
+/// Snapshot of a private state returned by `get_state`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PrivateStateView {
+    /// Current commitment
+    pub commitment: [u8; 32],
+    /// Current nonce
+    pub nonce: u64,
+    /// Current update policy
+    pub policy: u8,
+    /// Commitment hash algorithm (0 = sha256, 1 = keccak256)
+    pub hash_algo: u8,
+    /// Number of successful updates
+    pub update_count: u64,
+    /// Number of resets
+    pub epoch: u64,
+    /// Most recent commitments, newest first (see `PrivateState::recent_commitments`)
+    pub recent: [[u8; 32]; RECENT_COMMITMENTS],
+    /// Slot the account was created in (0 if created before v13)
+    pub created_slot: u64,
+}
+
+/// Public view of a `PrivateState`, returned by `snapshot`.
+///
+/// Mirrors the account field for field, minus internal bookkeeping (`bump`,
+/// `recent_head` and the rate-limit window counters), with `recent` ordered
+/// newest first. Fields are appended here whenever they are appended to
+/// `PrivateState`.
+///
+/// ```
+/// use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+/// use private_state_toolkit::{PrivateState, StateSnapshot, PRIVATE_STATE_SPACE};
+///
+/// let mut state = PrivateState::deserialize(&mut &[0u8; PRIVATE_STATE_SPACE - 8][..]).unwrap();
+/// state.nonce = 7;
+/// state.commitment = [9; 32];
+/// state.recent[0] = [9; 32];
+/// state.expiry_slot = 1_000;
+///
+/// let snapshot = StateSnapshot::from(&state);
+/// let decoded = StateSnapshot::try_from_slice(&snapshot.try_to_vec().unwrap()).unwrap();
+/// assert_eq!(decoded, snapshot);
+/// assert_eq!(decoded.nonce, state.nonce);
+/// assert_eq!(decoded.commitment, state.commitment);
+/// assert_eq!(decoded.recent, state.recent_commitments());
+/// assert_eq!(decoded.expiry_slot, state.expiry_slot);
+/// ```
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct StateSnapshot {
+    /// Layout version (`CURRENT_STATE_VERSION` once migrated)
+    pub version: u8,
+    /// Current authority
+    pub authority: Pubkey,
+    /// Current commitment
+    pub commitment: [u8; 32],
+    /// Current nonce
+    pub nonce: u64,
+    /// Current update policy
+    pub policy: u8,
+    /// Number of significant leading commitment bytes (32 for SHA-256)
+    pub commitment_len: u8,
+    /// Whether `reset` and `transfer_and_reset` are allowed
+    pub allow_reset: bool,
+    /// Authority proposed by `propose_authority_transfer`, if any
+    pub pending_authority: Option<Pubkey>,
+    /// Nonce ceiling for StrictSequentialWithCap (0 = none)
+    pub max_nonce: u64,
+    /// Minimum slots between updates (0 = none)
+    pub min_update_slots: u64,
+    /// Slot of the last update (or initialization)
+    pub last_update_slot: u64,
+    /// Whether the account is frozen
+    pub frozen: bool,
+    /// Key allowed to update on the authority's behalf, if any
+    pub delegate: Option<Pubkey>,
+    /// Commitment hash algorithm (0 = sha256, 1 = keccak256)
+    pub hash_algo: u8,
+    /// Number of successful updates
+    pub update_count: u64,
+    /// Number of resets
+    pub epoch: u64,
+    /// Most recent commitments, newest first (see `PrivateState::recent_commitments`)
+    pub recent: [[u8; 32]; RECENT_COMMITMENTS],
+    /// Updates allowed per rate-limit window (0 = unlimited)
+    pub max_updates_per_window: u16,
+    /// Rate-limit window length in slots (0 = unlimited)
+    pub window_len_slots: u64,
+    /// Whether commitment logging is suppressed
+    pub quiet: bool,
+    /// Last slot at which the state is valid for gating (0 = never expires)
+    pub expiry_slot: u64,
+    /// Whether the configuration is sealed
+    pub sealed: bool,
+    /// Lamports charged per update (0 = free)
+    pub fee_lamports: u64,
+    /// Wallet that receives update fees
+    pub fee_destination: Pubkey,
+    /// Whether `update` rejects an unchanged commitment
+    pub reject_noop: bool,
+    /// Key allowed to `recover_authority` after inactivity, if any
+    pub recovery: Option<Pubkey>,
+    /// Slots without an update before recovery is allowed
+    pub inactivity_slots: u64,
+    /// Slot the account was created in (0 if created before v13)
+    pub created_slot: u64,
+    /// Reason passed to the last `freeze` (0 while not frozen)
+    pub freeze_reason: u8,
+    /// Wallet that receives the `close_state_split` treasury share
+    pub treasury: Pubkey,
+    /// Treasury share of `close_state_split` in basis points
+    pub treasury_bps: u16,
+}
+
+impl From<&PrivateState> for StateSnapshot {
+    fn from(state: &PrivateState) -> Self {
+        Self {
+            version: state.version,
+            authority: state.authority,
+            commitment: state.commitment,
+            nonce: state.nonce,
+            policy: state.policy,
+            commitment_len: state.commitment_len,
+            allow_reset: state.allow_reset,
+            pending_authority: state.pending_authority,
+            max_nonce: state.max_nonce,
+            min_update_slots: state.min_update_slots,
+            last_update_slot: state.last_update_slot,
+            frozen: state.frozen,
+            delegate: state.delegate,
+            hash_algo: state.hash_algo,
+            update_count: state.update_count,
+            epoch: state.epoch,
+            recent: state.recent_commitments(),
+            max_updates_per_window: state.max_updates_per_window,
+            window_len_slots: state.window_len_slots,
+            quiet: state.quiet,
+            expiry_slot: state.expiry_slot,
+            sealed: state.sealed,
+            fee_lamports: state.fee_lamports,
+            fee_destination: state.fee_destination,
+            reject_noop: state.reject_noop,
+            recovery: state.recovery,
+            inactivity_slots: state.inactivity_slots,
+            created_slot: state.created_slot,
+            freeze_reason: state.freeze_reason,
+            treasury: state.treasury,
+            treasury_bps: state.treasury_bps,
+        }
+    }
+}
+
+/// Settings for `initialize` and `initialize_pda`.
+///
+/// Serialized field by field, exactly as if each were a separate argument.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct InitOptions {
+    /// Nonce ceiling for StrictSequentialWithCap (ignored otherwise)
+    pub max_nonce: u64,
+    /// Minimum slots between updates (0 = no delay)
+    pub min_update_slots: u64,
+    /// Commitment hash: 0 = sha256, 1 = keccak256
+    pub hash_algo: u8,
+    /// Last slot valid for gating (0 = never expires)
+    pub expiry_slot: u64,
+    /// Lamports charged per update (0 = free)
+    pub fee_lamports: u64,
+    /// Receiver of update fees (ignored while `fee_lamports` is 0)
+    pub fee_destination: Pubkey,
+    /// Key that may `recover_authority` once the account goes inactive
+    pub recovery: Option<Pubkey>,
+    /// Slots without an update after which recovery is allowed
+    pub inactivity_slots: u64,
+}
+
+/// One step of a `batch_update`.
+///
+/// Serialized exactly like a `([u8; 32], u64)` tuple.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Transition {
+    /// Commitment after this step
+    pub new_commitment: [u8; 32],
+    /// Nonce after this step (must satisfy policy)
+    pub nonce: u64,
+}
+
+/// One member of a `GuardianSet`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct WeightedGuardian {
+    /// Guardian key
+    pub key: Pubkey,
+    /// Weight counted toward `weight_threshold` when this guardian signs
+    pub weight: u16,
+}
+
+/// One device's counter in a `CausalClock`.
+///
+/// Serialized exactly like a `(u8, u64)` tuple.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct DeviceClock {
+    /// Device id
+    pub device: u8,
+    /// Number of edits from this device the sender has seen
+    pub counter: u64,
+}
+
+/// One namespaced state inside a `MultiState`.
+///
+/// Serialized exactly like a `(u8, [u8; 32], u64)` tuple.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MultiStateEntry {
+    /// Namespace byte identifying the entry
+    pub namespace: u8,
+    /// Current commitment
+    pub commitment: [u8; 32],
+    /// Current nonce
+    pub nonce: u64,
+}
+
+/// Update policy for nonce validation.
+///
+/// This determines how strictly nonces must increment. Borsh encodes it as its
+/// variant index, so it is byte-for-byte the same as the raw `u8` policy taken
+/// by `initialize_raw` / `set_policy_raw`.
+///
+/// ```
+/// use anchor_lang::AnchorSerialize;
+/// use private_state_toolkit::UpdatePolicy;
+///
+/// assert_eq!(UpdatePolicy::StrictSequential.try_to_vec().unwrap(), [0]);
+/// assert_eq!(UpdatePolicy::AllowSkips.try_to_vec().unwrap(), [1]);
+/// assert_eq!(UpdatePolicy::StrictSequentialWithCap.try_to_vec().unwrap(), [2]);
+/// ```
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum UpdatePolicy {
+    /// Nonce must increment by exactly 1 each update.
+    ///
+    /// Use for: Turn-based games, deterministic workflows, strict ordering
+    StrictSequential,
+
+    /// Nonce must increase but can skip values.
+    ///
+    /// Use for: Offline updates, batched operations, async workflows
+    AllowSkips,
+
+    /// Like StrictSequential, but no update is accepted once the nonce
+    /// reaches `max_nonce`.
+    ///
+    /// Use for: Games or workflows with a fixed number of turns
+    StrictSequentialWithCap,
+}
+
+/// Convert u8 to UpdatePolicy enum.
+impl TryFrom<u8> for UpdatePolicy {
+    type Error = anchor_lang::error::Error;
+
+    fn try_from(value: u8) -> std::result::Result<Self, anchor_lang::error::Error> {
+        match value {
+            0 => Ok(UpdatePolicy::StrictSequential),
+            1 => Ok(UpdatePolicy::AllowSkips),
+            2 => Ok(UpdatePolicy::StrictSequentialWithCap),
+            _ => Err(PrivateStateError::InvalidPolicy.into()),
+        }
+    }
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+/// Emitted when a private state account is created.
+#[event]
+pub struct StateInitialized {
+    /// The private state account
+    pub account: Pubkey,
+    /// Authority that owns the account
+    pub authority: Pubkey,
+    /// Initial commitment
+    pub commitment: [u8; 32],
+    /// Initial nonce
+    pub nonce: u64,
+    /// Update policy
+    pub policy: u8,
+    /// Slot the account was created in
+    pub created_slot: u64,
+}
+
+/// Emitted whenever the stored commitment/nonce advances.
+///
+/// Carries both commitments so listeners can verify the chain of updates.
+#[event]
+pub struct StateUpdated {
+    /// The private state account
+    pub account: Pubkey,
+    /// Commitment before the update
+    pub old_commitment: [u8; 32],
+    /// Commitment after the update
+    pub new_commitment: [u8; 32],
+    /// Nonce after the update
+    pub nonce: u64,
+    /// Update policy in effect
+    pub policy: u8,
+    /// True when the update only re-randomized the commitment (see `reblind`)
+    pub reblind: bool,
+    /// Caller-supplied correlation tag from `update` (never stored)
+    pub memo: Option<[u8; 32]>,
+}
+
+/// Emitted by `touch`: the nonce advanced but the commitment did not.
+#[event]
+pub struct StateTouched {
+    /// The private state account
+    pub account: Pubkey,
+    /// Unchanged commitment
+    pub commitment: [u8; 32],
+    /// Nonce after the touch
+    pub nonce: u64,
+    /// Slot recorded as the last update
+    pub slot: u64,
+}
+
+/// Emitted when `register_leaf` appends to a `MerkleRegistry`.
+#[event]
+pub struct LeafRegistered {
+    /// The registry account
+    pub registry: Pubkey,
+    /// The registered leaf
+    pub leaf: [u8; 32],
+    /// Index of the leaf (for membership proofs)
+    pub index: u64,
+    /// Root after the append
+    pub root: [u8; 32],
+}
+
+/// Emitted when the account's authority changes.
+#[event]
+pub struct AuthorityTransferred {
+    /// The private state account
+    pub account: Pubkey,
+    /// Previous authority
+    pub old_authority: Pubkey,
+    /// New authority
+    pub new_authority: Pubkey,
+}
+
+/// Emitted when the account's update policy changes.
+#[event]
+pub struct PolicyChanged {
+    /// The private state account
+    pub account: Pubkey,
+    /// Previous policy
+    pub old_policy: u8,
+    /// New policy
+    pub new_policy: u8,
+}
+
+/// Emitted when the nonce sequence restarts (`reset`, `transfer_and_reset`).
+#[event]
+pub struct StateReset {
+    /// The private state account
+    pub account: Pubkey,
+    /// Commitment before the reset
+    pub old_commitment: [u8; 32],
+    /// Commitment stored at nonce 0
+    pub new_commitment: [u8; 32],
+    /// Epoch after the reset
+    pub epoch: u64,
+}
+
+/// Emitted when `freeze` freezes an account.
+#[event]
+pub struct StateFrozen {
+    /// The private state account
+    pub account: Pubkey,
+    /// Operator-defined freeze reason
+    pub reason: u8,
+    /// Slot the account was frozen in
+    pub slot: u64,
+}
+
+/// Emitted instead of `StateAsserted` when the account is in quiet mode.
+#[event]
+pub struct StateAssertedQuiet {
+    /// The private state account
+    pub account: Pubkey,
+}
+
+/// Emitted when `update_multi` changes one namespace of a `MultiState`.
+#[event]
+pub struct MultiStateUpdated {
+    /// The multi-state account
+    pub account: Pubkey,
+    /// Namespace that was updated
+    pub namespace: u8,
+    /// Commitment before the update
+    pub old_commitment: [u8; 32],
+    /// Commitment after the update
+    pub new_commitment: [u8; 32],
+    /// Nonce after the update
+    pub nonce: u64,
+}
+
+/// Emitted by any nonce-advancing instruction when the account is close to
+/// becoming unusable.
+///
+/// Lets dashboards alert before the hard failure (`MaxNonceReached`,
+/// `RateLimitExceeded`, `StateExpired`).
+#[event]
+pub struct StateHealthWarning {
+    /// The private state account
+    pub account: Pubkey,
+    /// `HEALTH_*` flags for each limit that is close
+    pub conditions: u8,
+    /// Nonce after the update
+    pub nonce: u64,
+    /// Nonce ceiling (StrictSequentialWithCap)
+    pub max_nonce: u64,
+    /// Updates used in the current rate-limit window
+    pub updates_in_window: u16,
+    /// Updates allowed per window
+    pub max_updates_per_window: u16,
+    /// Last slot at which the state is valid for gating
+    pub expiry_slot: u64,
+}
+
+/// Emitted when `assert_state` succeeds.
+#[event]
+pub struct StateAsserted {
+    /// The private state account
+    pub account: Pubkey,
+    /// Commitment that was asserted
+    pub commitment: [u8; 32],
+    /// Nonce that was asserted
+    pub nonce: u64,
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Custom errors for PST operations.
+#[error_code]
+pub enum PrivateStateError {
+    /// Thrown when update() receives wrong old_commitment.
+    /// This means the caller doesn't have the current state.
+    #[msg("Stored commitment does not match the provided old commitment.")]
+    CommitmentMismatch,
+
+    /// Thrown when assert_state() receives wrong nonce.
+    #[msg("Nonce does not match expected value.")]
+    NonceMismatch,
+
+    /// Thrown in StrictSequential mode when nonce doesn't increment by 1.
+    #[msg("Nonce must increment exactly by one.")]
+    NonceNotSequential,
+
+    /// Thrown in AllowSkips mode when nonce doesn't increase.
+    #[msg("Nonce must be strictly greater than the stored nonce.")]
+    NonceNotMonotonic,
+
+    /// Thrown when policy value is not 0, 1 or 2.
+    #[msg("Invalid policy; expected 0 (StrictSequential), 1 (AllowSkips) or 2 (StrictSequentialWithCap).")]
+    InvalidPolicy,
+
+    /// Thrown when a batch instruction receives no accounts or transitions.
+    #[msg("Batch must contain at least one entry.")]
+    EmptyBatch,
+
+    /// Thrown when a batch exceeds MAX_BATCH_ACCOUNTS or MAX_BATCH_TRANSITIONS.
+    #[msg("Batch exceeds the maximum number of entries.")]
+    BatchTooLarge,
+
+    /// Thrown when the same account appears twice in a batch.
+    #[msg("Batch contains the same account more than once.")]
+    DuplicateAccount,
+
+    /// Thrown when assert_authority_signed is not preceded by an Ed25519 instruction.
+    #[msg("Expected an Ed25519 signature verification instruction before this one.")]
+    MissingSignatureVerification,
+
+    /// Thrown when the verified signature is not the authority's over the message.
+    #[msg("Signature was not made by the authority over the given message.")]
+    SignatureMismatch,
+
+    /// Thrown when reblind() receives a commitment equal to the current one.
+    #[msg("Reblinded commitment must differ from the current commitment.")]
+    ReblindUnchanged,
+
+    /// Thrown when commitment_len is not in 1..=32.
+    #[msg("Invalid commitment length; expected 1 to 32 significant bytes.")]
+    InvalidCommitmentLen,
+
+    /// Thrown when bytes past commitment_len are not zero.
+    #[msg("Commitment padding past the significant length must be zero.")]
+    NonCanonicalCommitment,
+
+    /// Thrown when an allowed-set argument exceeds MAX_ALLOWED_SET.
+    #[msg("Allowed set exceeds the maximum number of entries.")]
+    AllowedSetTooLarge,
+
+    /// Thrown when the account's policy is not in the allowed set.
+    #[msg("Account policy is not in the allowed set.")]
+    PolicyNotAllowed,
+
+    /// Thrown when a reset is attempted on an account that opted out.
+    #[msg("Resets are disabled for this account.")]
+    ResetNotAllowed,
+
+    /// Thrown when assert_pair's commitments don't hash to the expected value.
+    #[msg("Paired commitments do not hash to the expected value.")]
+    PairMismatch,
+
+    /// Thrown when the nonce cannot advance because it would overflow u64.
+    #[msg("Nonce cannot advance past u64::MAX.")]
+    NonceOverflow,
+
+    /// Thrown when set_metadata is called for an account that already has metadata.
+    #[msg("Metadata has already been set for this account.")]
+    MetadataAlreadySet,
+
+    /// Thrown when the account's authority is not in the allowed set.
+    #[msg("Account authority is not in the allowed set.")]
+    AuthorityNotAllowed,
+
+    /// Thrown when cancelling a transfer that was never proposed.
+    #[msg("No authority transfer is pending.")]
+    NoPendingAuthority,
+
+    /// Thrown when the accept signer is not the proposed authority.
+    #[msg("Signer is not the pending authority.")]
+    NotPendingAuthority,
+
+    /// Thrown when assert_state_min_nonce finds a nonce below the minimum.
+    #[msg("Nonce is below the required minimum.")]
+    NonceBelowMinimum,
+
+    /// Thrown when update_chained's new commitment is not the chained hash.
+    #[msg("New commitment does not chain from the old commitment.")]
+    ChainLinkMismatch,
+
+    /// Thrown in StrictSequentialWithCap mode once the nonce reaches max_nonce.
+    #[msg("Nonce has reached the account's maximum.")]
+    MaxNonceReached,
+
+    /// Thrown when an update arrives before min_update_slots have passed.
+    #[msg("Update is too soon after the previous one.")]
+    UpdateTooSoon,
+
+    /// Thrown when mutating an account that has been frozen.
+    #[msg("Private state is frozen.")]
+    StateFrozen,
+
+    /// Thrown when a guardian set is empty, too large, has duplicates or a bad threshold.
+    #[msg("Invalid guardian set or threshold.")]
+    InvalidGuardianSet,
+
+    /// Thrown when multisig_update has fewer guardian signatures than the threshold.
+    #[msg("Not enough guardian approvals.")]
+    GuardianThresholdNotMet,
+
+    /// Thrown when set_delegate is given the default pubkey.
+    #[msg("Delegate must not be the default pubkey.")]
+    InvalidDelegate,
+
+    /// Thrown when update_as_delegate is signed by neither the authority nor the delegate.
+    #[msg("Signer is neither the authority nor the delegate.")]
+    NotAuthorizedUpdater,
+
+    /// Thrown when assert_state_fresh finds the last update older than allowed.
+    #[msg("Private state has not been updated recently enough.")]
+    StateStale,
+
+    /// Thrown when hash_algo is not 0 (sha256) or 1 (keccak256).
+    #[msg("Invalid hash algorithm; expected 0 (sha256) or 1 (keccak256).")]
+    InvalidHashAlgo,
+
+    /// Thrown when assert_state_with_epoch finds a different epoch.
+    #[msg("Epoch mismatch; the state has been reset.")]
+    EpochMismatch,
+
+    /// Thrown when seeds, bump and program id don't form a valid PDA (or, for
+    /// transfer_authority_from_pda, not the PDA that holds authority).
+    #[msg("Seeds and bump do not derive a valid PDA for the program.")]
+    InvalidPda,
+
+    /// Thrown when the consumer link PDA already exists.
+    #[msg("This consumer program is already registered for the account.")]
+    ConsumerAlreadyRegistered,
+
+    /// Thrown when the account has used up its updates for the current window.
+    #[msg("Too many updates in the current rate-limit window.")]
+    RateLimitExceeded,
+
+    /// Thrown when a MultiState has no entry for the namespace.
+    #[msg("Namespace not found in the multi-state account.")]
+    NamespaceNotFound,
+
+    /// Thrown when initialize_multi is given the same namespace twice.
+    #[msg("Namespaces must be distinct.")]
+    DuplicateNamespace,
+
+    /// Thrown when initialize_multi is given more than MAX_MULTI_ENTRIES entries.
+    #[msg("Too many namespaces for one multi-state account.")]
+    TooManyNamespaces,
+
+    /// Thrown when gating on a state that is past its expiry_slot.
+    #[msg("Private state has expired.")]
+    StateExpired,
+
+    /// Thrown when assert_state_signed's requester is not the expected key.
+    #[msg("Requester does not match the expected signer.")]
+    UnauthorizedRequester,
+
+    /// Thrown when a new nonce would enter the band reserved below u64::MAX.
+    #[msg("Nonce is too close to u64::MAX; the account could be exhausted.")]
+    NonceExhaustionRisk,
+
+    /// Thrown when compare_and_swap_authority finds a different authority.
+    #[msg("Current authority does not match the expected authority.")]
+    AuthorityMismatch,
+
+    /// Thrown when changing the configuration of a sealed account.
+    #[msg("Private state is sealed; its configuration can no longer change.")]
+    StateSealed,
+
+    /// Thrown when the fee payer's balance cannot cover fee_lamports.
+    #[msg("Fee payer cannot cover the update fee.")]
+    InsufficientFee,
+
+    /// Thrown when the fee destination is the default pubkey, missing or wrong.
+    #[msg("Fee destination is missing or does not match the account's fee_destination.")]
+    InvalidFeeDestination,
+
+    /// Thrown when an update keeps the commitment on a reject_noop account.
+    #[msg("Update does not change the commitment.")]
+    NoOpUpdate,
+
+    /// Thrown when recover_authority is signed by a key other than recovery.
+    #[msg("Signer is not the account's recovery key.")]
+    UnauthorizedRecovery,
+
+    /// Thrown when recover_authority is called before inactivity_slots have passed.
+    #[msg("Account was updated too recently to be recovered.")]
+    AccountStillActive,
+
+    /// Thrown when a range argument has its minimum above its maximum.
+    #[msg("Invalid range; minimum must not exceed maximum.")]
+    InvalidRange,
+
+    /// Thrown when assert_state_range finds the nonce outside the window.
+    #[msg("Nonce is outside the expected range.")]
+    NonceOutOfRange,
+
+    /// Thrown when assert_state_restricted's CPI caller is not on the allowlist.
+    #[msg("Calling program is not allowed to gate on this account.")]
+    CallerNotAllowed,
+
+    /// Thrown when update_causal's clock is dominated by the stored clock.
+    #[msg("Device clock is dominated by the stored clock (stale update).")]
+    CausalConflict,
+
+    /// Thrown when a device clock lists the same device twice.
+    #[msg("Device clock lists a device more than once.")]
+    DuplicateDevice,
+
+    /// Thrown when a merged clock would exceed MAX_DEVICE_CLOCKS devices.
+    #[msg("Too many devices in the clock.")]
+    TooManyDevices,
+
+    /// Thrown when verify_payload is given more than MAX_VERIFY_PAYLOAD_LEN bytes.
+    #[msg("Payload exceeds the maximum length for on-chain verification.")]
+    PayloadTooLarge,
+
+    /// Thrown when update_checked's expected_current_nonce differs from the stored nonce.
+    #[msg("Current nonce does not match the expected value.")]
+    CurrentNonceMismatch,
+
+    /// Thrown when assert_state_cmp is given an op other than 0-4.
+    #[msg("Invalid comparison op; expected 0 (eq), 1 (ge), 2 (gt), 3 (le) or 4 (lt).")]
+    InvalidComparisonOp,
+
+    /// Thrown when the nonce fails the comparison requested by assert_state_cmp.
+    #[msg("Nonce does not satisfy the requested comparison.")]
+    NonceComparisonFailed,
+
+    /// Thrown when initialize_pda targets a PDA that already holds an account.
+    #[msg("Private state account is already initialized.")]
+    AlreadyInitialized,
+
+    /// Thrown when the stored commitment matches none of assert_state_in_set's entries.
+    #[msg("Commitment is not in the allowed set.")]
+    CommitmentNotInSet,
+
+    /// Thrown when register_leaf is called on a registry holding 2^MERKLE_DEPTH leaves.
+    #[msg("Merkle registry is full.")]
+    RegistryFull,
+
+    /// Thrown when a membership proof does not have exactly MERKLE_DEPTH entries.
+    #[msg("Membership proof must have exactly MERKLE_DEPTH sibling hashes.")]
+    InvalidProofLength,
+
+    /// Thrown when a membership proof does not reach the registry's root.
+    #[msg("Leaf is not in the registry at the given index.")]
+    LeafNotInRegistry,
+
+    /// Thrown when update_bound's auth_tag doesn't cover the submitted transition.
+    #[msg("Auth tag does not match the account, new commitment and nonce.")]
+    AuthTagMismatch,
+
+    /// Thrown when a recovery key is set with an inactivity_slots of 0.
+    #[msg("A recovery key requires a non-zero inactivity window.")]
+    InvalidInactivityWindow,
+
+    /// Thrown when assert_quiescent finds an update within min_quiet_slots.
+    #[msg("Private state was updated too recently.")]
+    StateNotQuiescent,
+
+    /// Thrown when assert_k_of_n_fresh finds fewer than k fresh accounts.
+    #[msg("Too few of the accounts were updated recently.")]
+    FreshnessThresholdNotMet,
+
+    /// Thrown when a close split's treasury_bps exceeds BPS_DENOMINATOR.
+    #[msg("Treasury share must be at most 10000 basis points.")]
+    InvalidSplit,
+
+    /// Thrown when assert_rotation's old_commitment is not the one the last update replaced.
+    #[msg("The last update did not replace the expected old commitment.")]
+    RotationOldMismatch,
+
+    /// Thrown when assert_rotation's new_commitment is not the current commitment.
+    #[msg("The current commitment is not the expected new commitment.")]
+    RotationNewMismatch,
+
+    /// Thrown when set_close_split has a share but no treasury, or when
+    /// close_state_split's treasury is not the configured one.
+    #[msg("Treasury does not match the configured close split.")]
+    InvalidTreasury,
 }
 
-/// Accounts for the transfer_authority instruction.
-#[derive(Accounts)]
-pub struct TransferAuthority<'info> {
-    /// The private state account whose authority is being transferred
-    #[account(mut, has_one = authority)]
-    pub private_state: Account<'info, PrivateState>,
+// ============================================================================
+// Helper Functions
+// ============================================================================
 
-    /// The current authority (must sign)
-    pub authority: Signer<'info>,
+/// Validates that a policy value is valid (0, 1 or 2).
+fn validate_policy(policy: u8) -> Result<()> {
+    match policy {
+        0..=2 => Ok(()),
+        _ => Err(PrivateStateError::InvalidPolicy.into()),
+    }
 }
 
-/// Accounts for the set_policy instruction.
-#[derive(Accounts)]
-pub struct SetPolicy<'info> {
-    /// The private state account whose policy is being changed
-    #[account(mut, has_one = authority)]
-    pub private_state: Account<'info, PrivateState>,
+/// Stores a (validated) policy byte and announces the change.
+fn change_policy(state: &mut Account<PrivateState>, policy: u8) {
+    let old_policy = state.policy;
+    state.policy = policy;
+    msg!("policy: {} -> {}", old_policy, policy);
+    emit!(PolicyChanged {
+        account: state.key(),
+        old_policy,
+        new_policy: policy,
+    });
+}
 
-    /// The authority who owns this account
-    pub authority: Signer<'info>,
+/// Validates that a hash algorithm value is known (sha256 or keccak256).
+fn validate_hash_algo(hash_algo: u8) -> Result<()> {
+    match hash_algo {
+        HASH_ALGO_SHA256 | HASH_ALGO_KECCAK256 => Ok(()),
+        _ => Err(PrivateStateError::InvalidHashAlgo.into()),
+    }
 }
 
-/// Accounts for the assert_state instruction.
+/// Validates an update fee: a non-zero fee needs a real destination.
+fn validate_fee(fee_lamports: u64, fee_destination: &Pubkey) -> Result<()> {
+    require!(
+        fee_lamports == 0 || *fee_destination != Pubkey::default(),
+        PrivateStateError::InvalidFeeDestination
+    );
+    Ok(())
+}
+
+/// Validates a close split: at most the whole account, and a non-zero share
+/// needs a real treasury.
+fn validate_close_split(treasury: &Pubkey, treasury_bps: u16) -> Result<()> {
+    require!(
+        treasury_bps <= BPS_DENOMINATOR,
+        PrivateStateError::InvalidSplit
+    );
+    require!(
+        treasury_bps == 0 || *treasury != Pubkey::default(),
+        PrivateStateError::InvalidTreasury
+    );
+    Ok(())
+}
+
+/// A recovery key needs a non-zero inactivity window, otherwise it could take
+/// over an account one slot after its last update.
+fn validate_recovery(recovery: Option<Pubkey>, inactivity_slots: u64) -> Result<()> {
+    require!(
+        recovery.is_none() || inactivity_slots > 0,
+        PrivateStateError::InvalidInactivityWindow
+    );
+    Ok(())
+}
+
+/// Writes the initial fields of a freshly created private state account.
 ///
-/// This context is intentionally minimal (read-only) to be CPI-friendly.
-/// Other programs can validate state without needing to be the authority.
-#[derive(Accounts)]
-pub struct AssertState<'info> {
-    /// The private state account to validate (read-only)
-    pub private_state: Account<'info, PrivateState>,
+/// Shared by the `initialize` family; `initialize_pda` sets `bump` itself.
+/// `account` is the address of `state`, for the event.
+fn init_state(
+    state: &mut PrivateState,
+    account: Pubkey,
+    authority: Pubkey,
+    initial_commitment: [u8; 32],
+    initial_nonce: u64,
+    policy: u8,
+    options: &InitOptions,
+) -> Result<()> {
+    validate_policy(policy)?;
+    validate_hash_algo(options.hash_algo)?;
+    validate_fee(options.fee_lamports, &options.fee_destination)?;
+    validate_recovery(options.recovery, options.inactivity_slots)?;
+    let slot = Clock::get()?.slot;
+    state.version = CURRENT_STATE_VERSION;
+    state.authority = authority;
+    state.commitment = initial_commitment;
+    state.nonce = initial_nonce;
+    state.policy = policy;
+    state.commitment_len = FULL_COMMITMENT_LEN;
+    state.allow_reset = true;
+    state.pending_authority = None;
+    state.max_nonce = options.max_nonce;
+    state.min_update_slots = options.min_update_slots;
+    state.last_update_slot = slot;
+    state.frozen = false;
+    state.delegate = None;
+    state.hash_algo = options.hash_algo;
+    state.update_count = 0;
+    state.epoch = 0;
+    reset_recent(state, initial_commitment);
+    state.window_start_slot = 0;
+    state.updates_in_window = 0;
+    state.max_updates_per_window = 0;
+    state.window_len_slots = 0;
+    state.quiet = false;
+    state.expiry_slot = options.expiry_slot;
+    state.sealed = false;
+    state.fee_lamports = options.fee_lamports;
+    state.fee_destination = options.fee_destination;
+    state.reject_noop = false;
+    state.recovery = options.recovery;
+    state.inactivity_slots = options.inactivity_slots;
+    state.created_slot = slot;
+    state.freeze_reason = 0;
+    state.treasury = Pubkey::default();
+    state.treasury_bps = 0;
+
+    log_state(state);
+    emit!(StateInitialized {
+        account,
+        authority: state.authority,
+        commitment: state.commitment,
+        nonce: state.nonce,
+        policy: state.policy,
+        created_slot: state.created_slot,
+    });
+    Ok(())
 }
 
-// ============================================================================
-// Types and Enums
-// ============================================================================
+/// Shared tail of every nonce-advancing instruction, run once the new state
+/// has been written.
+///
+/// Logs the state and emits `StateHealthWarning` if it is close to a limit.
+fn finish_update(state: &Account<PrivateState>) {
+    log_state(state);
+    emit_health_warning(state);
+}
 
-/// Update policy for nonce validation.
+/// Emits `StateHealthWarning` if the just-updated state is close to a limit.
+fn emit_health_warning(state: &Account<PrivateState>) {
+    // stamp_update_slot has just recorded the current slot
+    let conditions = state.health_conditions(state.last_update_slot);
+    if conditions != 0 {
+        emit!(StateHealthWarning {
+            account: state.key(),
+            conditions,
+            nonce: state.nonce,
+            max_nonce: state.max_nonce,
+            updates_in_window: state.updates_in_window,
+            max_updates_per_window: state.max_updates_per_window,
+            expiry_slot: state.expiry_slot,
+        });
+    }
+}
+
+/// Derives the PDA for `seeds || [bump]` under `program_id`.
 ///
-/// This determines how strictly nonces must increment.
-#[derive(Clone, Copy)]
-pub enum UpdatePolicy {
-    /// Nonce must increment by exactly 1 each update.
-    ///
-    /// Use for: Turn-based games, deterministic workflows, strict ordering
-    StrictSequential,
+/// Fails with `InvalidPda` if the seeds don't produce a valid PDA.
+fn derive_pda(program_id: &Pubkey, seeds: &[Vec<u8>], bump: u8) -> Result<Pubkey> {
+    let bump_seed = [bump];
+    let mut seed_refs: Vec<&[u8]> = seeds.iter().map(|seed| seed.as_slice()).collect();
+    seed_refs.push(&bump_seed);
+    Pubkey::create_program_address(&seed_refs, program_id)
+        .map_err(|_| PrivateStateError::InvalidPda.into())
+}
 
-    /// Nonce must increase but can skip values.
-    ///
-    /// Use for: Offline updates, batched operations, async workflows
-    AllowSkips,
+/// Validates a transition away from the stored state.
+///
+/// Checks that the caller knows the current commitment and that `next_nonce`
+/// follows the account's policy.
+fn validate_transition(
+    state: &PrivateState,
+    old_commitment: &[u8; 32],
+    next_nonce: u64,
+) -> Result<()> {
+    // Verify caller knows the current state by checking commitment
+    require!(
+        commitment_matches(state, old_commitment),
+        PrivateStateError::CommitmentMismatch
+    );
+
+    validate_nonce_rule(state.policy, state.nonce, state.max_nonce, next_nonce)
 }
 
-/// Convert u8 to UpdatePolicy enum.
-impl TryFrom<u8> for UpdatePolicy {
-    type Error = anchor_lang::error::Error;
+/// Checks `next_nonce` against `policy`, given the current nonce and ceiling.
+///
+/// Shared by `PrivateState` updates and `MultiState` entries.
+fn validate_nonce_rule(policy: u8, nonce: u64, max_nonce: u64, next_nonce: u64) -> Result<()> {
+    // Never let a jump park the nonce where no further update fits
+    require!(
+        next_nonce <= NONCE_CEILING,
+        PrivateStateError::NonceExhaustionRisk
+    );
 
-    fn try_from(value: u8) -> std::result::Result<Self, anchor_lang::error::Error> {
-        match value {
-            0 => Ok(UpdatePolicy::StrictSequential),
-            1 => Ok(UpdatePolicy::AllowSkips),
-            _ => Err(PrivateStateError::InvalidPolicy.into()),
+    // Enforce nonce rules based on the account's policy
+    match UpdatePolicy::try_from(policy)? {
+        UpdatePolicy::StrictSequential => {
+            // Turn-based: nonce must increment by exactly 1
+            require!(
+                next_nonce == sequential_nonce(nonce)?,
+                PrivateStateError::NonceNotSequential
+            );
+        }
+        UpdatePolicy::AllowSkips => {
+            // Async-friendly: nonce just needs to increase
+            require!(
+                next_nonce > nonce,
+                PrivateStateError::NonceNotMonotonic
+            );
+        }
+        UpdatePolicy::StrictSequentialWithCap => {
+            // Turn-based with a fixed number of turns
+            require!(
+                nonce < max_nonce,
+                PrivateStateError::MaxNonceReached
+            );
+            require!(
+                next_nonce == sequential_nonce(nonce)?,
+                PrivateStateError::NonceNotSequential
+            );
         }
     }
+    Ok(())
 }
 
-// ============================================================================
-// Errors
-// ============================================================================
+/// Splits `lamports` into `(treasury, authority)` shares for `close_state_split`.
+///
+/// The treasury gets `lamports * treasury_bps / BPS_DENOMINATOR` rounded down
+/// and the authority the rest, so the shares always add up to `lamports`.
+/// Fails with `InvalidSplit` if `treasury_bps` exceeds `BPS_DENOMINATOR`.
+///
+/// ```
+/// use private_state_toolkit::split_lamports;
+///
+/// assert_eq!(split_lamports(1_000, 2_500).unwrap(), (250, 750));
+/// // The rounding remainder goes to the authority
+/// assert_eq!(split_lamports(999, 5_000).unwrap(), (499, 500));
+/// assert_eq!(split_lamports(u64::MAX, 10_000).unwrap(), (u64::MAX, 0));
+/// assert!(split_lamports(1_000, 10_001).is_err());
+/// ```
+pub fn split_lamports(lamports: u64, treasury_bps: u16) -> Result<(u64, u64)> {
+    require!(
+        treasury_bps <= BPS_DENOMINATOR,
+        PrivateStateError::InvalidSplit
+    );
+    // At most `lamports`, so the cast back to u64 cannot truncate
+    let to_treasury =
+        (u128::from(lamports) * u128::from(treasury_bps) / u128::from(BPS_DENOMINATOR)) as u64;
+    Ok((to_treasury, lamports - to_treasury))
+}
 
-/// Custom errors for PST operations.
-#[error_code]
-pub enum PrivateStateError {
-    /// Thrown when update() receives wrong old_commitment.
-    /// This means the caller doesn't have the current state.
-    #[msg("Stored commitment does not match the provided old commitment.")]
-    CommitmentMismatch,
+/// The only nonce a strict policy accepts after `nonce`.
+///
+/// Fails with `NonceOverflow` at `u64::MAX` rather than saturating, which
+/// would let every later "increment by one" check pass on the same value.
+///
+/// ```
+/// use private_state_toolkit::sequential_nonce;
+///
+/// assert_eq!(sequential_nonce(0).unwrap(), 1);
+/// assert_eq!(sequential_nonce(u64::MAX - 1).unwrap(), u64::MAX);
+/// assert!(sequential_nonce(u64::MAX)
+///     .unwrap_err()
+///     .to_string()
+///     .contains("NonceOverflow"));
+/// ```
+pub fn sequential_nonce(nonce: u64) -> Result<u64> {
+    nonce
+        .checked_add(1)
+        .ok_or_else(|| PrivateStateError::NonceOverflow.into())
+}
 
-    /// Thrown when assert_state() receives wrong nonce.
-    #[msg("Nonce does not match expected value.")]
-    NonceMismatch,
+/// Enforces `min_update_slots` and the rate limit, and records the current
+/// slot as the last update.
+///
+/// The first update is measured from the slot stamped at initialization. The
+/// rate-limit window restarts at the current slot once it has elapsed.
+fn stamp_update_slot(state: &mut PrivateState) -> Result<()> {
+    let slot = Clock::get()?.slot;
+    require!(
+        slot >= state.last_update_slot.saturating_add(state.min_update_slots),
+        PrivateStateError::UpdateTooSoon
+    );
+    if state.max_updates_per_window > 0 && state.window_len_slots > 0 {
+        if slot >= state.window_start_slot.saturating_add(state.window_len_slots) {
+            state.window_start_slot = slot;
+            state.updates_in_window = 0;
+        }
+        require!(
+            state.updates_in_window < state.max_updates_per_window,
+            PrivateStateError::RateLimitExceeded
+        );
+        state.updates_in_window += 1;
+    }
+    state.last_update_slot = slot;
+    Ok(())
+}
 
-    /// Thrown in StrictSequential mode when nonce doesn't increment by 1.
-    #[msg("Nonce must increment exactly by one.")]
-    NonceNotSequential,
+/// Compares the significant prefix of the stored commitment with `expected`.
+///
+/// Constant-time, so compute usage doesn't reveal how many leading bytes of a
+/// guess were right.
+fn commitment_matches(state: &PrivateState, expected: &[u8; 32]) -> bool {
+    let len = state.commitment_len as usize;
+    constant_time_eq(&state.commitment[..len], &expected[..len])
+}
 
-    /// Thrown in AllowSkips mode when nonce doesn't increase.
-    #[msg("Nonce must be strictly greater than the stored nonce.")]
-    NonceNotMonotonic,
+/// Computes the `update_bound` tag for a transition of `private_state`.
+///
+/// ```
+/// use anchor_lang::prelude::Pubkey;
+/// use anchor_lang::solana_program::hash::hashv;
+/// use private_state_toolkit::update_auth_tag;
+///
+/// let private_state = Pubkey::new_unique();
+/// let tag = update_auth_tag(&private_state, &[7; 32], 5);
+///
+/// let mut preimage = private_state.to_bytes().to_vec();
+/// preimage.extend_from_slice(&[7; 32]);
+/// preimage.extend_from_slice(&5u64.to_le_bytes());
+/// assert_eq!(preimage.len(), 72);
+/// assert_eq!(tag, hashv(&[&preimage]).to_bytes());
+/// assert_ne!(tag, update_auth_tag(&private_state, &[7; 32], 6));
+/// ```
+pub fn update_auth_tag(
+    private_state: &Pubkey,
+    new_commitment: &[u8; 32],
+    next_nonce: u64,
+) -> [u8; 32] {
+    hashv(&[
+        private_state.as_ref(),
+        new_commitment,
+        &next_nonce.to_le_bytes(),
+    ])
+    .to_bytes()
+}
 
-    /// Thrown when policy value is not 0 or 1.
-    #[msg("Invalid policy; expected 0 (StrictSequential) or 1 (AllowSkips).")]
-    InvalidPolicy,
+/// Hashes two Merkle children into their parent: `sha256(left || right)`.
+fn merkle_parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(&[left, right]).to_bytes()
 }
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
+/// Roots of empty subtrees: entry `i` is the root of an empty tree of height
+/// `i` (entry 0 is the all-zero empty leaf, entry `MERKLE_DEPTH` the root of
+/// an empty registry).
+pub fn merkle_zero_hashes() -> [[u8; 32]; MERKLE_DEPTH + 1] {
+    let mut zeros = [[0; 32]; MERKLE_DEPTH + 1];
+    for level in 1..=MERKLE_DEPTH {
+        zeros[level] = merkle_parent(&zeros[level - 1], &zeros[level - 1]);
+    }
+    zeros
+}
 
-/// Validates that a policy value is valid (0 or 1).
-fn validate_policy(policy: u8) -> Result<()> {
-    match policy {
-        0 | 1 => Ok(()),
-        _ => Err(PrivateStateError::InvalidPolicy.into()),
+/// Appends `leaf` at `index` and returns the new root.
+///
+/// `filled_subtrees` holds, per level, the last left child seen there and is
+/// updated in place; `index` must be the number of leaves already appended.
+pub fn append_merkle_leaf(
+    filled_subtrees: &mut [[u8; 32]; MERKLE_DEPTH],
+    index: u64,
+    leaf: [u8; 32],
+) -> [u8; 32] {
+    let zeros = merkle_zero_hashes();
+    let mut node = leaf;
+    for (level, filled) in filled_subtrees.iter_mut().enumerate() {
+        node = if (index >> level) & 1 == 0 {
+            *filled = node;
+            merkle_parent(&node, &zeros[level])
+        } else {
+            merkle_parent(filled, &node)
+        };
+    }
+    node
+}
+
+/// Recomputes the root reached from `leaf` at `index` with the sibling
+/// hashes in `proof` (bottom first).
+///
+/// ```
+/// use private_state_toolkit::{
+///     append_merkle_leaf, merkle_root_from_proof, merkle_zero_hashes, MERKLE_DEPTH,
+/// };
+///
+/// let zeros = merkle_zero_hashes();
+/// let mut filled = [[0u8; 32]; MERKLE_DEPTH];
+/// filled.copy_from_slice(&zeros[..MERKLE_DEPTH]);
+/// let (a, b) = ([1u8; 32], [2u8; 32]);
+/// append_merkle_leaf(&mut filled, 0, a);
+/// let root = append_merkle_leaf(&mut filled, 1, b);
+///
+/// // a's siblings: b, then the empty subtrees above
+/// let mut proof = vec![b];
+/// proof.extend_from_slice(&zeros[1..MERKLE_DEPTH]);
+/// assert_eq!(merkle_root_from_proof(a, &proof, 0), root);
+///
+/// // Exclusion: a different leaf, or the right leaf at the wrong index, fails
+/// assert_ne!(merkle_root_from_proof([3; 32], &proof, 0), root);
+/// assert_ne!(merkle_root_from_proof(a, &proof, 1), root);
+/// ```
+pub fn merkle_root_from_proof(leaf: [u8; 32], proof: &[[u8; 32]], index: u64) -> [u8; 32] {
+    proof
+        .iter()
+        .enumerate()
+        .fold(leaf, |node, (level, sibling)| {
+            if (index >> level) & 1 == 0 {
+                merkle_parent(&node, sibling)
+            } else {
+                merkle_parent(sibling, &node)
+            }
+        })
+}
+
+/// Evaluates `nonce <op> bound` for an `assert_state_cmp` op (`NONCE_CMP_*`).
+///
+/// Fails with `InvalidComparisonOp` for an unknown op.
+///
+/// ```
+/// use private_state_toolkit::{compare_nonce, NONCE_CMP_EQ, NONCE_CMP_GT, NONCE_CMP_LE};
+///
+/// assert!(compare_nonce(5, NONCE_CMP_EQ, 5).unwrap());
+/// assert!(!compare_nonce(5, NONCE_CMP_GT, 5).unwrap());
+/// assert!(compare_nonce(5, NONCE_CMP_LE, 5).unwrap());
+/// assert!(compare_nonce(5, 5, 5).is_err());
+/// ```
+pub fn compare_nonce(nonce: u64, op: u8, bound: u64) -> Result<bool> {
+    match op {
+        NONCE_CMP_EQ => Ok(nonce == bound),
+        NONCE_CMP_GE => Ok(nonce >= bound),
+        NONCE_CMP_GT => Ok(nonce > bound),
+        NONCE_CMP_LE => Ok(nonce <= bound),
+        NONCE_CMP_LT => Ok(nonce < bound),
+        _ => err!(PrivateStateError::InvalidComparisonOp),
+    }
+}
+
+/// Byte-slice equality whose cost depends only on the length.
+///
+/// Folds the XOR of every byte pair before comparing with zero, so a mismatch
+/// in the first byte costs the same as one in the last.
+///
+/// ```
+/// use private_state_toolkit::constant_time_eq;
+///
+/// assert!(constant_time_eq(&[1, 2, 3], &[1, 2, 3]));
+/// assert!(!constant_time_eq(&[1, 2, 3], &[0, 2, 3]));
+/// assert!(!constant_time_eq(&[1, 2, 3], &[1, 2, 4]));
+/// assert!(!constant_time_eq(&[1, 2], &[1, 2, 3]));
+/// assert!(constant_time_eq(&[], &[]));
+/// ```
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Validates a commitment about to replace the stored one.
+///
+/// Checks its padding past `commitment_len` and, on `reject_noop` accounts,
+/// that it differs from the stored commitment (`NoOpUpdate`).
+fn validate_new_commitment(state: &PrivateState, new_commitment: &[u8; 32]) -> Result<()> {
+    validate_commitment_padding(new_commitment, state.commitment_len)?;
+    require!(
+        !state.reject_noop || !constant_time_eq(&state.commitment, new_commitment),
+        PrivateStateError::NoOpUpdate
+    );
+    Ok(())
+}
+
+/// Validates `commitment_len` and checks that `commitment` is zero past it.
+fn validate_commitment_padding(commitment: &[u8; 32], commitment_len: u8) -> Result<()> {
+    require!(
+        (1..=FULL_COMMITMENT_LEN).contains(&commitment_len),
+        PrivateStateError::InvalidCommitmentLen
+    );
+    require!(
+        commitment[commitment_len as usize..].iter().all(|b| *b == 0),
+        PrivateStateError::NonCanonicalCommitment
+    );
+    Ok(())
+}
+
+/// Validates the size of a batch and rejects repeated accounts.
+fn validate_batch(accounts: &[AccountInfo]) -> Result<()> {
+    require!(!accounts.is_empty(), PrivateStateError::EmptyBatch);
+    require!(
+        accounts.len() <= MAX_BATCH_ACCOUNTS,
+        PrivateStateError::BatchTooLarge
+    );
+    for (i, a) in accounts.iter().enumerate() {
+        require!(
+            accounts[i + 1..].iter().all(|b| b.key != a.key),
+            PrivateStateError::DuplicateAccount
+        );
+    }
+    Ok(())
+}
+
+/// Loads a writable `PrivateState` passed outside the typed context and checks
+/// that it belongs to `authority`.
+///
+/// Mirrors the `mut, has_one = authority` constraints used by the typed contexts;
+/// `Account::try_from` verifies program ownership and the discriminator.
+fn load_authorized_state<'info>(
+    info: &'info AccountInfo<'info>,
+    authority: &Pubkey,
+) -> Result<Account<'info, PrivateState>> {
+    require!(info.is_writable, ErrorCode::ConstraintMut);
+    let state = Account::<PrivateState>::try_from(info)?;
+    require_keys_eq!(state.authority, *authority, ErrorCode::ConstraintHasOne);
+    require!(!state.frozen, PrivateStateError::StateFrozen);
+    Ok(state)
+}
+
+/// Checks that `ix` is an Ed25519 program instruction verifying exactly one
+/// `signature` by `pubkey` over `message`.
+///
+/// Ed25519 instruction data layout:
+/// - `[0]` number of signatures, `[1]` padding
+/// - `[2..16]` seven u16 LE offsets: signature, signature ix index, public key,
+///   public key ix index, message, message size, message ix index
+///
+/// All ix indexes must be `u16::MAX`, meaning the data lives in the Ed25519
+/// instruction itself rather than being borrowed from another instruction.
+fn verify_ed25519_ix(
+    ix: &Instruction,
+    pubkey: &Pubkey,
+    message: &[u8; 32],
+    signature: &[u8; 64],
+) -> Result<()> {
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        PrivateStateError::MissingSignatureVerification
+    );
+
+    let data = &ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        PrivateStateError::SignatureMismatch
+    );
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    require!(
+        read_u16(4) == u16::MAX && read_u16(8) == u16::MAX && read_u16(14) == u16::MAX,
+        PrivateStateError::SignatureMismatch
+    );
+    require!(
+        read_u16(12) as usize == message.len(),
+        PrivateStateError::SignatureMismatch
+    );
+
+    let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+    require!(
+        slice(read_u16(6), 32) == Some(pubkey.as_ref())
+            && slice(read_u16(2), 64) == Some(&signature[..])
+            && slice(read_u16(10), 32) == Some(&message[..]),
+        PrivateStateError::SignatureMismatch
+    );
+    Ok(())
+}
+
+/// Creates a program-owned PDA account of `space` bytes, funded by `payer`.
+///
+/// Used where `#[account(init)]` can't be, so the caller can reject an existing
+/// account with a descriptive error instead of the system program's.
+fn create_pda_account<'info>(
+    payer: &Signer<'info>,
+    target: &UncheckedAccount<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let lamports = Rent::get()?.minimum_balance(space);
+    let signer_seeds = [seeds];
+    let cpi_ctx = CpiContext::new_with_signer(
+        system_program.to_account_info(),
+        anchor_lang::system_program::CreateAccount {
+            from: payer.to_account_info(),
+            to: target.to_account_info(),
+        },
+        &signer_seeds,
+    );
+    anchor_lang::system_program::create_account(cpi_ctx, lamports, space as u64, &crate::ID)
+}
+
+/// Records `commitment` as the newest entry of the `recent` ring buffer.
+fn push_recent(state: &mut PrivateState, commitment: [u8; 32]) {
+    state.recent_head = (state.recent_head + 1) % RECENT_COMMITMENTS as u8;
+    state.recent[state.recent_head as usize] = commitment;
+}
+
+/// Clears the `recent` ring buffer down to a single `commitment`.
+fn reset_recent(state: &mut PrivateState, commitment: [u8; 32]) {
+    state.recent = [[0; 32]; RECENT_COMMITMENTS];
+    state.recent_head = 0;
+    state.recent[0] = commitment;
+}
+
+/// Validates and writes the guardian set shared by `set_guardians` and
+/// `set_weighted_guardians`.
+///
+/// Creates the PDA on first use; otherwise grows an account written by an
+/// older, smaller layout before overwriting it.
+fn write_guardian_set(
+    ctx: Context<SetGuardians>,
+    guardians: Vec<WeightedGuardian>,
+    weight_threshold: u16,
+) -> Result<()> {
+    let total_weight: u32 = guardians.iter().map(|g| u32::from(g.weight)).sum();
+    require!(
+        guardians.len() <= MAX_GUARDIANS
+            && guardians.iter().all(|g| g.weight >= 1)
+            && weight_threshold >= 1
+            && u32::from(weight_threshold) <= total_weight
+            && guardians
+                .iter()
+                .enumerate()
+                .all(|(i, g)| guardians[..i].iter().all(|p| p.key != g.key)),
+        PrivateStateError::InvalidGuardianSet
+    );
+
+    let private_state = ctx.accounts.private_state.key();
+    if ctx.accounts.guardian_set.data_is_empty() {
+        let seeds: &[&[u8]] = &[
+            b"guardians",
+            private_state.as_ref(),
+            &[ctx.bumps.guardian_set],
+        ];
+        create_pda_account(
+            &ctx.accounts.authority,
+            &ctx.accounts.guardian_set,
+            &ctx.accounts.system_program,
+            GUARDIAN_SET_SPACE,
+            seeds,
+        )?;
+    } else {
+        require_keys_eq!(
+            *ctx.accounts.guardian_set.owner,
+            crate::ID,
+            ErrorCode::ConstraintOwner
+        );
+        grow_account(
+            &ctx.accounts.guardian_set.to_account_info(),
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            GUARDIAN_SET_SPACE,
+        )?;
+    }
+
+    let guardian_set = GuardianSet {
+        private_state,
+        weight_threshold,
+        guardians,
+    };
+    let mut data = ctx.accounts.guardian_set.try_borrow_mut_data()?;
+    guardian_set.try_serialize(&mut &mut data[..])?;
+
+    msg!(
+        "guardians set: weight {}-of-{} across {} guardians",
+        weight_threshold,
+        total_weight,
+        guardian_set.guardians.len()
+    );
+    Ok(())
+}
+
+/// Charges the account's `fee_lamports` to the authority, paid to `fee_destination`.
+///
+/// A no-op for free accounts, which may omit both optional fee accounts.
+fn collect_update_fee<'info>(accounts: &Update<'info>) -> Result<()> {
+    charge_update_fee(
+        &accounts.private_state,
+        &accounts.authority.to_account_info(),
+        accounts.fee_destination.as_ref(),
+        accounts.system_program.as_ref(),
+    )
+}
+
+/// Charges `state.fee_lamports` to `payer`, paid to `fee_destination`.
+///
+/// Every instruction that advances the nonce goes through here, so the fee
+/// can't be dodged by picking another update path. A no-op for free accounts,
+/// which may omit both optional fee accounts.
+fn charge_update_fee<'info>(
+    state: &PrivateState,
+    payer: &AccountInfo<'info>,
+    fee_destination: Option<&UncheckedAccount<'info>>,
+    system_program: Option<&Program<'info, System>>,
+) -> Result<()> {
+    let fee = state.fee_lamports;
+    if fee == 0 {
+        return Ok(());
+    }
+    let (Some(fee_destination), Some(system_program)) = (fee_destination, system_program) else {
+        return err!(PrivateStateError::InvalidFeeDestination);
+    };
+    require_keys_eq!(
+        fee_destination.key(),
+        state.fee_destination,
+        PrivateStateError::InvalidFeeDestination
+    );
+    require!(payer.lamports() >= fee, PrivateStateError::InsufficientFee);
+    let cpi_ctx = CpiContext::new(
+        system_program.to_account_info(),
+        anchor_lang::system_program::Transfer {
+            from: payer.clone(),
+            to: fee_destination.to_account_info(),
+        },
+    );
+    anchor_lang::system_program::transfer(cpi_ctx, fee)
+}
+
+/// Reads the next borsh field of an account.
+fn read_field<T: AnchorDeserialize>(data: &mut &[u8]) -> Result<T> {
+    Ok(T::deserialize(data)?)
+}
+
+/// Reads a field introduced in layout `introduced`, or its default for older accounts.
+fn read_field_since<T: AnchorDeserialize + Default>(
+    data: &mut &[u8],
+    version: u8,
+    introduced: u8,
+) -> Result<T> {
+    if version < introduced {
+        return Ok(T::default());
+    }
+    read_field(data)
+}
+
+/// Reallocs `target` up to `space` bytes, with `payer` covering the extra rent.
+///
+/// New bytes are zero-filled. A no-op if the account is already large enough.
+fn grow_account<'info>(
+    target: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    if target.data_len() >= space {
+        return Ok(());
+    }
+    let shortfall = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(target.lamports());
+    if shortfall > 0 {
+        let cpi_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: payer.to_account_info(),
+                to: target.clone(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_ctx, shortfall)?;
+    }
+    target.realloc(space, true)?;
+    Ok(())
+}
+
+/// Merges an incoming vector clock into the stored one.
+///
+/// Devices missing from a clock count as 0. Fails with `CausalConflict` if the
+/// incoming clock is at or below the stored one for every device (a stale or
+/// replayed update); otherwise, whether it dominates or is concurrent, returns
+/// the per-device maximum sorted by device id.
+///
+/// ```
+/// use private_state_toolkit::{merge_device_clocks, DeviceClock};
+///
+/// let clock = |entries: &[(u8, u64)]| -> Vec<DeviceClock> {
+///     entries.iter().map(|&(device, counter)| DeviceClock { device, counter }).collect()
+/// };
+/// let stored = clock(&[(1, 3), (2, 1)]);
+///
+/// // Dominating: device 1 moved on
+/// assert_eq!(merge_device_clocks(&stored, &clock(&[(1, 4), (2, 1)])).unwrap(),
+///            clock(&[(1, 4), (2, 1)]));
+/// // Concurrent: device 2 edited without seeing device 1's latest
+/// assert_eq!(merge_device_clocks(&stored, &clock(&[(1, 2), (2, 2)])).unwrap(),
+///            clock(&[(1, 3), (2, 2)]));
+/// // A new device joins
+/// assert_eq!(merge_device_clocks(&stored, &clock(&[(3, 1)])).unwrap(),
+///            clock(&[(1, 3), (2, 1), (3, 1)]));
+/// // Stale: equal to, or dominated by, the stored clock
+/// assert!(merge_device_clocks(&stored, &stored).is_err());
+/// assert!(merge_device_clocks(&stored, &clock(&[(1, 3)])).is_err());
+/// assert!(merge_device_clocks(&stored, &[]).is_err());
+/// // Duplicate device ids are rejected
+/// assert!(merge_device_clocks(&[], &clock(&[(1, 1), (1, 2)])).is_err());
+/// ```
+pub fn merge_device_clocks(
+    stored: &[DeviceClock],
+    incoming: &[DeviceClock],
+) -> Result<Vec<DeviceClock>> {
+    require!(
+        incoming
+            .iter()
+            .enumerate()
+            .all(|(i, c)| incoming[..i].iter().all(|p| p.device != c.device)),
+        PrivateStateError::DuplicateDevice
+    );
+
+    let counter_in = |clock: &[DeviceClock], device: u8| {
+        clock
+            .iter()
+            .find(|c| c.device == device)
+            .map_or(0, |c| c.counter)
+    };
+    require!(
+        incoming
+            .iter()
+            .any(|c| c.counter > counter_in(stored, c.device)),
+        PrivateStateError::CausalConflict
+    );
+
+    let mut merged = stored.to_vec();
+    for c in incoming {
+        match merged.iter_mut().find(|m| m.device == c.device) {
+            Some(m) => m.counter = m.counter.max(c.counter),
+            None => merged.push(*c),
+        }
+    }
+    require!(
+        merged.len() <= MAX_DEVICE_CLOCKS,
+        PrivateStateError::TooManyDevices
+    );
+    merged.sort_by_key(|c| c.device);
+    Ok(merged)
+}
+
+/// The program that invoked the current instruction via a direct CPI.
+///
+/// `None` for top-level calls and for CPIs nested deeper than one level, whose
+/// immediate caller the runtime doesn't expose (see `assert_state_restricted`).
+fn cpi_caller(instructions: &AccountInfo) -> Result<Option<Pubkey>> {
+    if get_stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT + 1 {
+        return Ok(None);
+    }
+    Ok(Some(get_instruction_relative(0, instructions)?.program_id))
+}
+
+/// Whether a state with `expiry_slot` is expired at `slot`.
+///
+/// The expiry slot itself is still valid; 0 means the state never expires.
+///
+/// ```
+/// use private_state_toolkit::is_past_expiry;
+///
+/// assert!(!is_past_expiry(100, 99));
+/// assert!(!is_past_expiry(100, 100));
+/// assert!(is_past_expiry(100, 101));
+/// assert!(!is_past_expiry(0, u64::MAX));
+/// ```
+pub fn is_past_expiry(expiry_slot: u64, slot: u64) -> bool {
+    expiry_slot != 0 && slot > expiry_slot
+}
+
+/// Fails with `StateExpired` if the state is past its `expiry_slot`.
+fn require_not_expired(state: &PrivateState) -> Result<()> {
+    require!(
+        !state.is_expired(Clock::get()?.slot),
+        PrivateStateError::StateExpired
+    );
+    Ok(())
+}
+
+/// Logs the account's state unless it is in quiet mode.
+fn log_state(state: &PrivateState) {
+    if !state.quiet {
+        log_commitment(state.nonce, &state.commitment, state.policy);
     }
 }
 
+/// Logs and emits the outcome of a successful assertion.
+///
+/// Quiet accounts log nothing and emit only the opaque `StateAssertedQuiet`.
+fn emit_asserted(state: &Account<PrivateState>) {
+    if state.quiet {
+        emit!(StateAssertedQuiet {
+            account: state.key(),
+        });
+        return;
+    }
+    log_commitment(state.nonce, &state.commitment, state.policy);
+    emit!(StateAsserted {
+        account: state.key(),
+        commitment: state.commitment,
+        nonce: state.nonce,
+    });
+}
+
 /// Logs the current state to program logs (visible in transaction logs).
 ///
-/// Logs first 6 bytes of commitment as hex for debugging.
-fn log_commitment(nonce: u64, commitment: &[u8; 32], policy: u8) {
-    let prefix = to_hex(&commitment[0..6]);
+/// Logs the first `LOG_PREFIX_LEN` bytes of commitment as hex for debugging.
+fn log_commitment(nonce: u64, commitment: &[u8], policy: u8) {
+    let prefix = to_hex(log_prefix(commitment));
     msg!(
         "nonce: {}, commitment_prefix: {}, policy: {}",
         nonce,
@@ -391,6 +5950,20 @@ fn log_commitment(nonce: u64, commitment: &[u8; 32], policy: u8) {
     );
 }
 
+/// The part of `commitment` that is logged: its first `LOG_PREFIX_LEN` bytes,
+/// or all of it if it is shorter.
+///
+/// ```
+/// use private_state_toolkit::{log_prefix, LOG_PREFIX_LEN};
+///
+/// assert_eq!(log_prefix(&[7; 32]).len(), LOG_PREFIX_LEN);
+/// assert_eq!(log_prefix(&[1, 2]), [1, 2]);
+/// assert!(log_prefix(&[]).is_empty());
+/// ```
+pub fn log_prefix(commitment: &[u8]) -> &[u8] {
+    commitment.get(..LOG_PREFIX_LEN).unwrap_or(commitment)
+}
+
 /// Converts bytes to lowercase hex string.
 fn to_hex(bytes: &[u8]) -> String {
     let mut out = String::with_capacity(bytes.len() * 2);
@@ -0,0 +1,28 @@
+//! Checks `vectors/commitment.json` (see `examples/gen_vectors.rs`) against
+//! `compute_commitment`.
+
+use private_state_toolkit::client::compute_commitment;
+use serde_json::Value;
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn commitment_vectors_reproduce() {
+    let vectors: Vec<Value> =
+        serde_json::from_str(include_str!("../vectors/commitment.json")).unwrap();
+    assert!(!vectors.is_empty());
+
+    for vector in &vectors {
+        let name = vector["name"].as_str().unwrap();
+        let nonce: u64 = vector["nonce"].as_str().unwrap().parse().unwrap();
+        let payload = unhex(vector["payload"].as_str().unwrap());
+        let expected = unhex(vector["commitment"].as_str().unwrap());
+
+        assert_eq!(compute_commitment(nonce, &payload)[..], expected[..], "{}", name);
+    }
+}
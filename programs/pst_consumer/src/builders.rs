@@ -0,0 +1,101 @@
+//! # Instruction Builders
+//!
+//! Plain `Instruction` constructors for the consumer program, for scripts and
+//! programs that don't use Anchor's generated client. The account metas come
+//! from one place, so callers can't get the order wrong.
+//!
+//! ```
+//! use anchor_lang::prelude::Pubkey;
+//! use pst_consumer::builders;
+//!
+//! let consumer = Pubkey::new_unique();
+//! let private_state = Pubkey::new_unique();
+//! let authority = Pubkey::new_unique();
+//! let ix = builders::gated_action(
+//!     &pst_consumer::ID,
+//!     &consumer,
+//!     &private_state,
+//!     &private_state_toolkit::ID,
+//!     &authority,
+//!     [7; 32],
+//!     5,
+//! );
+//!
+//! let keys: Vec<_> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+//! assert_eq!(keys, [consumer, private_state, private_state_toolkit::ID, authority]);
+//! assert_eq!(ix.data.len(), 8 + 32 + 8);
+//! assert_eq!(ix.data[8..40], [7; 32]);
+//! assert_eq!(ix.data[40..], 5u64.to_le_bytes());
+//! ```
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::InstructionData;
+
+/// Account metas for `gated_action` and the other `GatedAction` instructions,
+/// in order: consumer (writable), private_state, pst_program, authority (signer).
+pub fn gated_action_accounts(
+    consumer: &Pubkey,
+    private_state: &Pubkey,
+    pst_program: &Pubkey,
+    authority: &Pubkey,
+) -> Vec<AccountMeta> {
+    crate::accounts::GatedAction {
+        consumer: *consumer,
+        private_state: *private_state,
+        pst_program: *pst_program,
+        authority: *authority,
+    }
+    .to_account_metas(None)
+}
+
+/// Builds an `initialize_consumer` instruction; `consumer` and `authority`
+/// must sign.
+pub fn initialize_consumer(
+    program_id: &Pubkey,
+    consumer: &Pubkey,
+    private_state: &Pubkey,
+    authority: &Pubkey,
+    initial_balance: i64,
+    min_action_slots: u64,
+    max_count: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: crate::accounts::InitializeConsumer {
+            consumer: *consumer,
+            private_state: *private_state,
+            authority: *authority,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::InitializeConsumer {
+            private_state: *private_state,
+            initial_balance,
+            min_action_slots,
+            max_count,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `gated_action` instruction; `authority` must sign.
+pub fn gated_action(
+    program_id: &Pubkey,
+    consumer: &Pubkey,
+    private_state: &Pubkey,
+    pst_program: &Pubkey,
+    authority: &Pubkey,
+    expected_commitment: [u8; 32],
+    expected_nonce: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: gated_action_accounts(consumer, private_state, pst_program, authority),
+        data: crate::instruction::GatedAction {
+            expected_commitment,
+            expected_nonce,
+        }
+        .data(),
+    }
+}
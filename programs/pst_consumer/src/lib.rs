@@ -16,7 +16,23 @@
 //! 2. User creates a consumer account linked to that PST account
 //! 3. User calls `gated_action` with expected commitment/nonce
 //! 4. Consumer validates via CPI to PST's `assert_state`
-//! 5. If valid, consumer increments its counter
+//! 5. If valid, consumer increments its counter and emits `GatedActionPerformed`
+//!
+//! `set_checkpoint` and `gated_on_progress` gate on change rather than on a
+//! static value: the action only passes once the PST nonce has advanced past
+//! the checkpoint (e.g. "claim the reward only if your state moved on").
+//!
+//! The consumer can also hold a PST account in custody: the owner moves its
+//! authority to the consumer's `["custody", consumer]` PDA with PST's
+//! `transfer_authority_to_pda`, and `release_custody` hands it back.
+//!
+//! With a nonzero `max_count` the counter is a quota: gated actions fail with
+//! `MaxCountReached` until the consumer's authority calls `reset_counter`.
+//!
+//! Steps 1 and 2 can be done in one go with `initialize_and_link`.
+//!
+//! Scripts and non-Anchor callers can build these instructions with the
+//! `builders` module instead of assembling account metas by hand.
 //!
 //! This pattern enables:
 //! - Gated access based on private credentials
@@ -25,6 +41,8 @@
 
 use anchor_lang::prelude::*;
 
+pub mod builders;
+
 declare_id!("BxqCdUzNrMifua7Rd3qQSqgd4oyTzdcTqH1tbYuvi5bf");
 
 #[program]
@@ -36,18 +54,100 @@ pub mod pst_consumer {
     /// # Arguments
     ///
     /// * `private_state` - The PST account this consumer will validate against
+    /// * `initial_balance` - Starting credit balance for `gated_action_delta`
+    /// * `min_action_slots` - Minimum slots between gated actions (0 = no cooldown)
+    /// * `max_count` - Most gated actions before `reset_counter` is needed (0 = unlimited)
     ///
     /// # Purpose
     ///
     /// Creates the link between this consumer program and a specific PST account.
-    /// Future `gated_action` calls will validate against this PST account.
+    /// Future `gated_action` calls will validate against this PST account, and
+    /// only through the PST program that owns it (recorded as `pst_program`).
+    ///
+    /// The PST account's current nonce is recorded as `baseline_nonce`, so the
+    /// consumer measures progress from the moment it was linked.
     pub fn initialize_consumer(
         ctx: Context<InitializeConsumer>,
         private_state: Pubkey,
+        initial_balance: i64,
+        min_action_slots: u64,
+        max_count: u64,
     ) -> Result<()> {
+        require!(
+            ctx.accounts.private_state.key() == private_state,
+            ConsumerError::InvalidPrivateState
+        );
+
         let account = &mut ctx.accounts.consumer;
         account.count = 0;
         account.private_state = private_state;
+        account.baseline_nonce = ctx.accounts.private_state.nonce;
+        account.last_seen_nonce = ctx.accounts.private_state.nonce;
+        account.pst_program = *ctx.accounts.private_state.to_account_info().owner;
+        account.balance = initial_balance;
+        account.min_action_slots = min_action_slots;
+        account.last_action_slot = 0;
+        account.max_count = max_count;
+        account.authority = ctx.accounts.authority.key();
+        account.checkpoint_nonce = account.baseline_nonce;
+        Ok(())
+    }
+
+    /// Creates a PST private state and a consumer linked to it in one instruction.
+    ///
+    /// CPIs PST's `initialize_pda`, so the private state lives at
+    /// `["pst", authority, label]` under the PST program and the consumer can
+    /// only ever be linked to the account it just created.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - 16-byte namespace label for the PST PDA
+    /// * `initial_commitment` - SHA-256 hash of (nonce || encrypted_payload)
+    /// * `policy` - PST update policy
+    /// * `options` - Remaining PST settings (see `InitOptions`)
+    /// * `initial_balance` - Starting credit balance for `gated_action_delta`
+    /// * `min_action_slots` - Minimum slots between gated actions (0 = no cooldown)
+    /// * `max_count` - Most gated actions before `reset_counter` is needed (0 = unlimited)
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_and_link(
+        ctx: Context<InitializeAndLink>,
+        label: [u8; 16],
+        initial_commitment: [u8; 32],
+        policy: u8,
+        options: private_state_toolkit::InitOptions,
+        initial_balance: i64,
+        min_action_slots: u64,
+        max_count: u64,
+    ) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.pst_program.to_account_info(),
+            private_state_toolkit::cpi::accounts::InitializePda {
+                private_state: ctx.accounts.private_state.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+        );
+        private_state_toolkit::cpi::initialize_pda(
+            cpi_ctx,
+            label,
+            initial_commitment,
+            policy,
+            options,
+        )?;
+
+        // A freshly initialized PST account always starts at nonce 0
+        let account = &mut ctx.accounts.consumer;
+        account.count = 0;
+        account.private_state = ctx.accounts.private_state.key();
+        account.baseline_nonce = 0;
+        account.last_seen_nonce = 0;
+        account.pst_program = ctx.accounts.pst_program.key();
+        account.balance = initial_balance;
+        account.min_action_slots = min_action_slots;
+        account.last_action_slot = 0;
+        account.max_count = max_count;
+        account.authority = ctx.accounts.authority.key();
+        account.checkpoint_nonce = account.baseline_nonce;
         Ok(())
     }
 
@@ -63,11 +163,14 @@ pub mod pst_consumer {
     ///
     /// # Validation Flow
     ///
-    /// 1. Verify private_state matches consumer's linked PST account
+    /// 1. Verify private_state matches consumer's linked PST account, that
+    ///    `expected_nonce` is not below the highest nonce already accepted, and
+    ///    that `min_action_slots` have passed since the last gated action
+    ///    (`ActionCooldown`, checked before the CPI so a spammed call fails cheaply)
     /// 2. **CPI to PST**: Call `assert_state` on PST program
     /// 3. PST validates commitment and nonce match on-chain state
     /// 4. If CPI succeeds, we know state is valid → execute gated action
-    /// 5. Increment consumer's counter
+    /// 5. Increment consumer's counter (`MaxCountReached` once it hits `max_count`)
     ///
     /// # CPI Security
     ///
@@ -75,38 +178,349 @@ pub mod pst_consumer {
     /// - PST program checks its own on-chain state
     /// - Consumer must provide correct values
     /// - If PST fails, entire transaction reverts
+    /// - Stale-but-valid older states (e.g. after a PST reset) are rejected
+    ///   with `NonceRegression`
     pub fn gated_action(
         ctx: Context<GatedAction>,
         expected_commitment: [u8; 32],
         expected_nonce: u64,
     ) -> Result<()> {
-        // Ensure the private_state account matches what this consumer expects
+        let slot = assert_linked_state(ctx.accounts, expected_commitment, expected_nonce)?;
+
+        // If we reach here, PST validation succeeded
+        // Now execute the gated action: increment counter
+        let account = &mut ctx.accounts.consumer;
+        account.increment_count(slot)?;
+        msg!("effective nonce: {}", account.effective_nonce(expected_nonce));
+        emit_gated_action(
+            &ctx.accounts.consumer,
+            ctx.accounts.private_state.key(),
+            expected_commitment,
+            expected_nonce,
+        );
+        Ok(())
+    }
+
+    /// Records the linked PST account's current nonce as `checkpoint_nonce`.
+    ///
+    /// The baseline for `gated_on_progress`; only the consumer's authority may
+    /// move it. Starts at the nonce the consumer was linked at.
+    pub fn set_checkpoint(ctx: Context<SetCheckpoint>) -> Result<()> {
+        let nonce = ctx.accounts.private_state.nonce;
+        ctx.accounts.consumer.checkpoint_nonce = nonce;
+        msg!("checkpoint: {}", nonce);
+        Ok(())
+    }
+
+    /// Performs a gated action only if the PST state advanced since the checkpoint.
+    ///
+    /// Like `gated_action`, but also requires `expected_nonce` (proven via the
+    /// `assert_state` CPI) to be strictly greater than `checkpoint_nonce`,
+    /// failing with `NoProgress` otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment we expect PST to have
+    /// * `expected_nonce` - The nonce we expect PST to have
+    pub fn gated_on_progress(
+        ctx: Context<GatedAction>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+    ) -> Result<()> {
         require!(
-            ctx.accounts.consumer.private_state == ctx.accounts.private_state.key(),
-            ConsumerError::InvalidPrivateState
+            expected_nonce > ctx.accounts.consumer.checkpoint_nonce,
+            ConsumerError::NoProgress
         );
+        let slot = assert_linked_state(ctx.accounts, expected_commitment, expected_nonce)?;
 
-        // Prepare CPI accounts for PST's assert_state instruction
-        let cpi_program = ctx.accounts.pst_program.to_account_info();
-        let cpi_accounts = private_state_toolkit::cpi::accounts::AssertState {
-            private_state: ctx.accounts.private_state.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-
-        // Call PST's assert_state via CPI
-        // This validates the commitment and nonce without decryption
-        private_state_toolkit::cpi::assert_state(
-            cpi_ctx,
+        let account = &mut ctx.accounts.consumer;
+        account.increment_count(slot)?;
+        emit_gated_action(
+            &ctx.accounts.consumer,
+            ctx.accounts.private_state.key(),
             expected_commitment,
             expected_nonce,
-        )?;
+        );
+        Ok(())
+    }
+
+    /// Performs a gated action only if the consumer's count is within a range.
+    ///
+    /// Atomically asserts the PST state, checks `min_count <= count <= max_count`,
+    /// and increments. Models state machines where the consumer's own progress
+    /// and the private state must jointly be valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment we expect PST to have
+    /// * `expected_nonce` - The nonce we expect PST to have
+    /// * `min_count` - Lowest acceptable count before the increment
+    /// * `max_count` - Highest acceptable count before the increment
+    pub fn gated_action_ranged(
+        ctx: Context<GatedAction>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+        min_count: u64,
+        max_count: u64,
+    ) -> Result<()> {
+        require!(min_count <= max_count, ConsumerError::InvalidRange);
+        let count = ctx.accounts.consumer.count;
+        require!(
+            (min_count..=max_count).contains(&count),
+            ConsumerError::CountOutOfRange
+        );
+
+        let slot = assert_linked_state(ctx.accounts, expected_commitment, expected_nonce)?;
 
-        // If we reach here, PST validation succeeded
-        // Now execute the gated action: increment counter
         let account = &mut ctx.accounts.consumer;
-        account.count = account.count.saturating_add(1);
+        account.increment_count(slot)?;
+        emit_gated_action(
+            &ctx.accounts.consumer,
+            ctx.accounts.private_state.key(),
+            expected_commitment,
+            expected_nonce,
+        );
         Ok(())
     }
+
+    /// Performs a gated action that adjusts the consumer's credit balance.
+    ///
+    /// Like `gated_action`, but also applies `delta` to `balance` once the PST
+    /// CPI has succeeded, turning the consumer into a credit meter gated on
+    /// private state.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_commitment` - The commitment we expect PST to have
+    /// * `expected_nonce` - The nonce we expect PST to have
+    /// * `delta` - Amount added to the balance (negative to spend credit)
+    ///
+    /// Fails with `InsufficientBalance` if the balance would go negative.
+    pub fn gated_action_delta(
+        ctx: Context<GatedAction>,
+        expected_commitment: [u8; 32],
+        expected_nonce: u64,
+        delta: i64,
+    ) -> Result<()> {
+        let slot = assert_linked_state(ctx.accounts, expected_commitment, expected_nonce)?;
+
+        let account = &mut ctx.accounts.consumer;
+        let balance = account
+            .balance
+            .checked_add(delta)
+            .ok_or(ConsumerError::BalanceOverflow)?;
+        require!(balance >= 0, ConsumerError::InsufficientBalance);
+
+        account.balance = balance;
+        account.increment_count(slot)?;
+        msg!("balance: {}", balance);
+        emit_gated_action(
+            &ctx.accounts.consumer,
+            ctx.accounts.private_state.key(),
+            expected_commitment,
+            expected_nonce,
+        );
+        Ok(())
+    }
+
+    /// Performs an action gated on several PST states at once (AND semantics).
+    ///
+    /// CPIs `assert_state` for every PST account in `remaining_accounts`
+    /// against the expectation at the same index; if any check fails the whole
+    /// transaction reverts. E.g. "prove both your age and membership
+    /// credentials". The cooldown applies as in `gated_action`, and if the
+    /// linked PST account is among them its nonce must not regress.
+    ///
+    /// # Arguments
+    ///
+    /// * `expectations` - `(commitment, nonce)` pairs, one per remaining account
+    ///
+    /// # Remaining Accounts
+    ///
+    /// The PST private state accounts to validate, in the same order.
+    pub fn gated_action_multi<'info>(
+        ctx: Context<'_, '_, 'info, 'info, GatedActionMulti<'info>>,
+        expectations: Vec<Expectation>,
+    ) -> Result<()> {
+        require!(!expectations.is_empty(), ConsumerError::NoExpectations);
+        require!(
+            expectations.len() == ctx.remaining_accounts.len(),
+            ConsumerError::MismatchedInputLengths
+        );
+
+        require_keys_eq!(
+            ctx.accounts.pst_program.key(),
+            ctx.accounts.consumer.pst_program,
+            ConsumerError::InvalidPstProgram
+        );
+        // Check the cooldown before paying for the CPIs
+        let slot = Clock::get()?.slot;
+        ctx.accounts.consumer.check_cooldown(slot)?;
+
+        let linked = ctx.accounts.consumer.private_state;
+        let mut linked_nonce = None;
+        let cpi_program = ctx.accounts.pst_program.to_account_info();
+        for (info, expected) in ctx.remaining_accounts.iter().zip(&expectations) {
+            require_keys_eq!(
+                *info.owner,
+                ctx.accounts.pst_program.key(),
+                ConsumerError::InvalidPrivateState
+            );
+            if *info.key == linked {
+                ctx.accounts.consumer.check_nonce(expected.nonce)?;
+                linked_nonce = Some(expected.nonce);
+            }
+            let cpi_accounts = private_state_toolkit::cpi::accounts::AssertState {
+                private_state: info.clone(),
+            };
+            let cpi_ctx = CpiContext::new(cpi_program.clone(), cpi_accounts);
+            private_state_toolkit::cpi::assert_state(cpi_ctx, expected.commitment, expected.nonce)?;
+        }
+
+        let account = &mut ctx.accounts.consumer;
+        if let Some(nonce) = linked_nonce {
+            account.last_seen_nonce = nonce;
+        }
+        account.increment_count(slot)?;
+        for (info, expected) in ctx.remaining_accounts.iter().zip(&expectations) {
+            emit_gated_action(
+                &ctx.accounts.consumer,
+                info.key(),
+                expected.commitment,
+                expected.nonce,
+            );
+        }
+        Ok(())
+    }
+
+    /// Performs an action if at least one of several PST states is valid (OR).
+    ///
+    /// A failed CPI would revert the whole transaction, so instead of calling
+    /// `assert_state` this reads each PST account directly and compares it
+    /// with `PrivateState::matches`. Accounts that aren't readable PST states
+    /// (e.g. closed ones) or are past their expiry simply don't count, nor does
+    /// the linked PST account at a nonce below `last_seen_nonce`. The cooldown
+    /// applies as in `gated_action`.
+    ///
+    /// # Arguments
+    ///
+    /// * `expectations` - `(commitment, nonce)` pairs, one per remaining account
+    ///
+    /// # Remaining Accounts
+    ///
+    /// The candidate PST private state accounts, in the same order.
+    pub fn gated_action_any<'info>(
+        ctx: Context<'_, '_, 'info, 'info, GatedActionAny<'info>>,
+        expectations: Vec<Expectation>,
+    ) -> Result<()> {
+        require!(!expectations.is_empty(), ConsumerError::NoExpectations);
+        require!(
+            expectations.len() == ctx.remaining_accounts.len(),
+            ConsumerError::MismatchedInputLengths
+        );
+
+        let slot = Clock::get()?.slot;
+        ctx.accounts.consumer.check_cooldown(slot)?;
+
+        let consumer = &ctx.accounts.consumer;
+        let mut linked_nonce = None;
+        let matched: Vec<_> = ctx
+            .remaining_accounts
+            .iter()
+            .zip(&expectations)
+            .filter(|(info, expected)| {
+                let valid = Account::<private_state_toolkit::PrivateState>::try_from(info)
+                    .map(|state| {
+                        state.matches(&expected.commitment, expected.nonce)
+                            && !state.is_expired(slot)
+                    })
+                    .unwrap_or(false);
+                // The linked state only counts if it doesn't move the nonce backwards
+                if *info.key != consumer.private_state {
+                    return valid;
+                }
+                let valid = valid && consumer.check_nonce(expected.nonce).is_ok();
+                if valid {
+                    linked_nonce = Some(expected.nonce);
+                }
+                valid
+            })
+            .map(|(info, expected)| (info.key(), expected))
+            .collect();
+        require!(!matched.is_empty(), ConsumerError::NoValidState);
+
+        msg!("valid states: {}/{}", matched.len(), expectations.len());
+        let account = &mut ctx.accounts.consumer;
+        if let Some(nonce) = linked_nonce {
+            account.last_seen_nonce = nonce;
+        }
+        account.increment_count(slot)?;
+        for (private_state, expected) in matched {
+            emit_gated_action(
+                &ctx.accounts.consumer,
+                private_state,
+                expected.commitment,
+                expected.nonce,
+            );
+        }
+        Ok(())
+    }
+
+    /// Resets the consumer's count to zero, restoring its `max_count` quota.
+    ///
+    /// Only the consumer's authority (its creator) may call this.
+    pub fn reset_counter(ctx: Context<ResetCounter>) -> Result<()> {
+        let account = &mut ctx.accounts.consumer;
+        msg!("count reset from {}", account.count);
+        account.count = 0;
+        Ok(())
+    }
+
+    /// Releases a PST account held in custody back to `new_authority`.
+    ///
+    /// The PST account's authority must be this consumer's custody PDA
+    /// (`["custody", consumer]`). Signs PST's `transfer_authority_from_pda` with
+    /// the PDA's seeds; only the consumer's authority may call this.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_authority` - Public key to hand the PST account to (e.g. a wallet)
+    pub fn release_custody(ctx: Context<ReleaseCustody>, new_authority: Pubkey) -> Result<()> {
+        let consumer = ctx.accounts.consumer.key();
+        let bump = ctx.bumps.custody;
+        let signer_seeds: &[&[&[u8]]] = &[&[CUSTODY_SEED, consumer.as_ref(), &[bump]]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.pst_program.to_account_info(),
+            private_state_toolkit::cpi::accounts::TransferAuthority {
+                private_state: ctx.accounts.private_state.to_account_info(),
+                authority: ctx.accounts.custody.to_account_info(),
+            },
+            signer_seeds,
+        );
+        private_state_toolkit::cpi::transfer_authority_from_pda(
+            cpi_ctx,
+            crate::ID,
+            vec![CUSTODY_SEED.to_vec(), consumer.to_bytes().to_vec()],
+            bump,
+            new_authority,
+        )
+    }
+
+    /// Returns the PST nonce relative to when this consumer was linked.
+    ///
+    /// Computes `pst_nonce - baseline_nonce` and returns it via return data, so
+    /// consumers linked at different times each see their own progress rather
+    /// than the absolute PST nonce.
+    pub fn effective_nonce(ctx: Context<EffectiveNonce>) -> Result<u64> {
+        require!(
+            ctx.accounts.consumer.private_state == ctx.accounts.private_state.key(),
+            ConsumerError::InvalidPrivateState
+        );
+        Ok(ctx
+            .accounts
+            .consumer
+            .effective_nonce(ctx.accounts.private_state.nonce))
+    }
 }
 
 // ============================================================================
@@ -115,7 +529,7 @@ pub mod pst_consumer {
 
 /// Consumer account that tracks gated actions.
 ///
-/// **Total size: 48 bytes** (8-byte discriminator + 40 bytes data)
+/// **Total size: 168 bytes** (8-byte discriminator + 160 bytes data)
 #[account]
 pub struct ConsumerAccount {
     /// Number of successful gated_action calls (8 bytes)
@@ -124,6 +538,83 @@ pub struct ConsumerAccount {
 
     /// The PST private state account this consumer validates against (32 bytes)
     pub private_state: Pubkey,
+
+    /// PST nonce at the time this consumer was linked (8 bytes)
+    pub baseline_nonce: u64,
+
+    /// Highest PST nonce accepted by a gated action so far (8 bytes)
+    /// Gated actions never accept a lower nonce
+    pub last_seen_nonce: u64,
+
+    /// Credit balance adjusted by gated_action_delta (8 bytes)
+    /// Never negative
+    pub balance: i64,
+
+    /// Minimum slots between gated actions, 0 = no cooldown (8 bytes)
+    pub min_action_slots: u64,
+
+    /// Slot of the last successful gated action, 0 = none yet (8 bytes)
+    pub last_action_slot: u64,
+
+    /// PST program the link was made for (32 bytes)
+    /// Gated actions must CPI into this program id
+    pub pst_program: Pubkey,
+
+    /// Most gated actions before a reset, 0 = unlimited (8 bytes)
+    pub max_count: u64,
+
+    /// Creator of this consumer, the only key allowed to reset_counter (32 bytes)
+    pub authority: Pubkey,
+
+    /// PST nonce recorded by set_checkpoint (8 bytes)
+    /// gated_on_progress requires a higher nonce
+    pub checkpoint_nonce: u64,
+}
+
+impl ConsumerAccount {
+    /// PST progress since this consumer was linked.
+    ///
+    /// Saturates at zero if the PST nonce is below the baseline.
+    pub fn effective_nonce(&self, pst_nonce: u64) -> u64 {
+        pst_nonce.saturating_sub(self.baseline_nonce)
+    }
+
+    /// Fails with `ActionCooldown` if fewer than `min_action_slots` slots have
+    /// passed since the last gated action.
+    pub fn check_cooldown(&self, slot: u64) -> Result<()> {
+        require!(
+            self.last_action_slot == 0
+                || slot.saturating_sub(self.last_action_slot) >= self.min_action_slots,
+            ConsumerError::ActionCooldown
+        );
+        Ok(())
+    }
+
+    /// Fails with `NonceRegression` if `nonce` is below `last_seen_nonce`.
+    pub fn check_nonce(&self, nonce: u64) -> Result<()> {
+        require!(
+            nonce >= self.last_seen_nonce,
+            ConsumerError::NonceRegression
+        );
+        Ok(())
+    }
+
+    /// Counts one more gated action at `slot`.
+    ///
+    /// Every counter-advancing instruction goes through here, so the cooldown
+    /// applies to all of them; `last_action_slot` moves to `slot`. Fails with
+    /// `ActionCooldown` (see `check_cooldown`), or `MaxCountReached` if
+    /// `max_count` is set and already reached.
+    pub fn increment_count(&mut self, slot: u64) -> Result<()> {
+        self.check_cooldown(slot)?;
+        require!(
+            self.max_count == 0 || self.count < self.max_count,
+            ConsumerError::MaxCountReached
+        );
+        self.count = self.count.saturating_add(1);
+        self.last_action_slot = slot;
+        Ok(())
+    }
 }
 
 // ============================================================================
@@ -134,10 +625,13 @@ pub struct ConsumerAccount {
 #[derive(Accounts)]
 pub struct InitializeConsumer<'info> {
     /// The consumer account to create
-    /// Space: 8 (discriminator) + 8 (count) + 32 (private_state)
-    #[account(init, payer = authority, space = 8 + 8 + 32)]
+    /// Space: CONSUMER_ACCOUNT_SPACE
+    #[account(init, payer = authority, space = CONSUMER_ACCOUNT_SPACE)]
     pub consumer: Account<'info, ConsumerAccount>,
 
+    /// The PST private state account being linked (read for its current nonce)
+    pub private_state: Account<'info, private_state_toolkit::PrivateState>,
+
     /// The authority creating this account (pays rent)
     #[account(mut)]
     pub authority: Signer<'info>,
@@ -146,6 +640,30 @@ pub struct InitializeConsumer<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Accounts for the initialize_and_link instruction.
+#[derive(Accounts)]
+pub struct InitializeAndLink<'info> {
+    /// The consumer account to create
+    /// Space: CONSUMER_ACCOUNT_SPACE
+    #[account(init, payer = authority, space = CONSUMER_ACCOUNT_SPACE)]
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The PST private state PDA to create
+    /// CHECK: Created by PST's initialize_pda, which checks its seeds
+    #[account(mut)]
+    pub private_state: UncheckedAccount<'info>,
+
+    /// The PST program (for CPI)
+    pub pst_program: Program<'info, private_state_toolkit::program::PrivateStateToolkit>,
+
+    /// The authority of both new accounts (pays rent for both)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
 /// Accounts for the gated_action instruction.
 #[derive(Accounts)]
 pub struct GatedAction<'info> {
@@ -165,6 +683,144 @@ pub struct GatedAction<'info> {
     pub authority: Signer<'info>,
 }
 
+/// Accounts for the gated_action_multi instruction.
+///
+/// The PST accounts to validate are passed as `remaining_accounts`.
+#[derive(Accounts)]
+pub struct GatedActionMulti<'info> {
+    /// The consumer account (increments on success)
+    #[account(mut)]
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The PST program (for CPI)
+    pub pst_program: Program<'info, private_state_toolkit::program::PrivateStateToolkit>,
+
+    /// The signer (required for transaction)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the gated_action_any instruction.
+///
+/// The candidate PST accounts are passed as `remaining_accounts`.
+#[derive(Accounts)]
+pub struct GatedActionAny<'info> {
+    /// The consumer account (increments on success)
+    #[account(mut)]
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The signer (required for transaction)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the reset_counter instruction.
+#[derive(Accounts)]
+pub struct ResetCounter<'info> {
+    /// The consumer account to reset
+    #[account(mut, has_one = authority @ ConsumerError::Unauthorized)]
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The consumer's authority (must sign)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the set_checkpoint instruction.
+#[derive(Accounts)]
+pub struct SetCheckpoint<'info> {
+    /// The consumer account whose checkpoint moves
+    #[account(mut, has_one = authority @ ConsumerError::Unauthorized)]
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The linked PST private state account (read for its current nonce)
+    #[account(address = consumer.private_state @ ConsumerError::InvalidPrivateState)]
+    pub private_state: Account<'info, private_state_toolkit::PrivateState>,
+
+    /// The consumer's authority (must sign)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the release_custody instruction.
+#[derive(Accounts)]
+pub struct ReleaseCustody<'info> {
+    /// The consumer whose custody PDA holds the PST account
+    #[account(has_one = authority @ ConsumerError::Unauthorized)]
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The PST private state account being released
+    /// CHECK: Validated by PST program via CPI (authority must be `custody`)
+    #[account(mut)]
+    pub private_state: AccountInfo<'info>,
+
+    /// The consumer's custody PDA, the PST account's current authority
+    /// CHECK: Only used as a PDA signer
+    #[account(seeds = [CUSTODY_SEED, consumer.key().as_ref()], bump)]
+    pub custody: UncheckedAccount<'info>,
+
+    /// The PST program (for CPI)
+    pub pst_program: Program<'info, private_state_toolkit::program::PrivateStateToolkit>,
+
+    /// The consumer's authority (must sign)
+    pub authority: Signer<'info>,
+}
+
+/// Accounts for the effective_nonce instruction.
+#[derive(Accounts)]
+pub struct EffectiveNonce<'info> {
+    /// The consumer account whose baseline is applied
+    pub consumer: Account<'info, ConsumerAccount>,
+
+    /// The linked PST private state account (read-only)
+    pub private_state: Account<'info, private_state_toolkit::PrivateState>,
+}
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Account size of `ConsumerAccount`, in bytes.
+///
+/// 8 (discriminator) + 8 (count) + 32 (private_state) + 8 (baseline_nonce)
+/// + 8 (last_seen_nonce) + 8 (balance) + 8 (min_action_slots) + 8 (last_action_slot)
+/// + 32 (pst_program) + 8 (max_count) + 32 (authority) + 8 (checkpoint_nonce)
+pub const CONSUMER_ACCOUNT_SPACE: usize = 8 + 8 + 32 + 8 + 8 + 8 + 8 + 8 + 32 + 8 + 32 + 8;
+
+/// Seed prefix of the custody PDA (`["custody", consumer]`) that can hold PST
+/// authority on a consumer's behalf.
+pub const CUSTODY_SEED: &[u8] = b"custody";
+
+/// Expected PST state for one account in `gated_action_multi` / `gated_action_any`.
+///
+/// Serialized exactly like a `([u8; 32], u64)` tuple.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Expectation {
+    /// The commitment we expect PST to have
+    pub commitment: [u8; 32],
+    /// The nonce we expect PST to have
+    pub nonce: u64,
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+/// Emitted after a gated action passes its PST check.
+///
+/// Multi-state actions emit one per validated state (for `gated_action_any`,
+/// one per matching state), all with the same `count`. Lets indexers rebuild
+/// the access log, including several actions in one slot.
+#[event]
+pub struct GatedActionPerformed {
+    /// The consumer account
+    pub consumer: Pubkey,
+    /// The PST private state that was validated
+    pub private_state: Pubkey,
+    /// Commitment proven via assert_state
+    pub commitment: [u8; 32],
+    /// Nonce proven via assert_state
+    pub nonce: u64,
+    /// Consumer count after the action
+    pub count: u64,
+}
+
 // ============================================================================
 // Errors
 // ============================================================================
@@ -175,4 +831,128 @@ pub enum ConsumerError {
     /// Thrown when private_state account doesn't match consumer's linked PST account.
     #[msg("Consumer account does not match the expected private state.")]
     InvalidPrivateState,
+
+    /// Thrown when a range argument has its minimum above its maximum.
+    #[msg("Invalid range; minimum must not exceed maximum.")]
+    InvalidRange,
+
+    /// Thrown when the consumer's count is outside the requested range.
+    #[msg("Consumer count is outside the expected range.")]
+    CountOutOfRange,
+
+    /// Thrown when the number of PST accounts and expectations differ.
+    #[msg("Number of accounts does not match number of expectations.")]
+    MismatchedInputLengths,
+
+    /// Thrown when a multi-state gate is given nothing to check.
+    #[msg("At least one expectation is required.")]
+    NoExpectations,
+
+    /// Thrown when none of the candidate PST states match in gated_action_any.
+    #[msg("None of the provided private states is valid.")]
+    NoValidState,
+
+    /// Thrown when a gated action presents a nonce below one already accepted.
+    #[msg("Nonce is lower than one this consumer has already seen.")]
+    NonceRegression,
+
+    /// Thrown when a gated_action_delta would take the balance below zero.
+    #[msg("Insufficient balance for this action.")]
+    InsufficientBalance,
+
+    /// Thrown when a gated_action_delta would overflow the balance.
+    #[msg("Balance overflow.")]
+    BalanceOverflow,
+
+    /// Thrown when a gated action comes before `min_action_slots` have passed.
+    #[msg("Gated action is still cooling down.")]
+    ActionCooldown,
+
+    /// Thrown when a gated action goes through a PST program other than the linked one.
+    #[msg("PST program does not match the one this consumer was linked with.")]
+    InvalidPstProgram,
+
+    /// Thrown when a gated action would take the count past `max_count`.
+    #[msg("Consumer has reached its maximum count; reset_counter is required.")]
+    MaxCountReached,
+
+    /// Thrown when reset_counter, set_checkpoint or release_custody is signed by someone
+    /// other than the consumer's authority.
+    #[msg("Signer is not the consumer's authority.")]
+    Unauthorized,
+
+    /// Thrown when gated_on_progress presents a nonce not above `checkpoint_nonce`.
+    #[msg("Private state has not advanced since the checkpoint.")]
+    NoProgress,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Verifies the consumer's linked PST account via CPI to `assert_state`.
+///
+/// Fails if `private_state` is not the account this consumer is linked to or is
+/// not owned by the PST program, if `pst_program` is not the linked one, if
+/// `expected_nonce` is below `last_seen_nonce`, if the consumer is still in its
+/// cooldown, or if PST rejects the expected commitment/nonce. On success
+/// `last_seen_nonce` advances to `expected_nonce` and the current slot is
+/// returned for `increment_count`.
+fn assert_linked_state(
+    accounts: &mut GatedAction,
+    expected_commitment: [u8; 32],
+    expected_nonce: u64,
+) -> Result<u64> {
+    // Ensure the private_state account matches what this consumer expects
+    require!(
+        accounts.consumer.private_state == accounts.private_state.key(),
+        ConsumerError::InvalidPrivateState
+    );
+    // Only trust the PST deployment the link was made for
+    require_keys_eq!(
+        accounts.pst_program.key(),
+        accounts.consumer.pst_program,
+        ConsumerError::InvalidPstProgram
+    );
+    // Reject look-alike accounts owned by some other program
+    require_keys_eq!(
+        *accounts.private_state.owner,
+        accounts.pst_program.key(),
+        ConsumerError::InvalidPrivateState
+    );
+    // Never let the consumer's view of the state move backwards
+    accounts.consumer.check_nonce(expected_nonce)?;
+    // Check the cooldown before paying for the CPI
+    let slot = Clock::get()?.slot;
+    accounts.consumer.check_cooldown(slot)?;
+
+    // Prepare CPI accounts for PST's assert_state instruction
+    let cpi_ctx = private_state_toolkit::builders::assert_state_cpi_ctx(
+        accounts.pst_program.to_account_info(),
+        accounts.private_state.to_account_info(),
+    );
+
+    // Call PST's assert_state via CPI
+    // This validates the commitment and nonce without decryption
+    private_state_toolkit::cpi::assert_state(cpi_ctx, expected_commitment, expected_nonce)?;
+
+    accounts.consumer.last_seen_nonce = expected_nonce;
+    Ok(slot)
+}
+
+/// Emits `GatedActionPerformed` for a PST state a just-succeeded action
+/// validated.
+fn emit_gated_action(
+    consumer: &Account<ConsumerAccount>,
+    private_state: Pubkey,
+    commitment: [u8; 32],
+    nonce: u64,
+) {
+    emit!(GatedActionPerformed {
+        consumer: consumer.key(),
+        private_state,
+        commitment,
+        nonce,
+        count: consumer.count,
+    });
 }
@@ -0,0 +1,82 @@
+//! Checks that `pst_consumer::builders` produce instructions the program's own
+//! dispatcher accepts: the right discriminator, decodable arguments and the
+//! account order of the `#[derive(Accounts)]` structs.
+
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use pst_consumer::{builders, instruction};
+
+#[test]
+fn initialize_consumer_matches_program_layout() {
+    let consumer = Pubkey::new_unique();
+    let private_state = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let ix = builders::initialize_consumer(
+        &pst_consumer::ID,
+        &consumer,
+        &private_state,
+        &authority,
+        -3,
+        10,
+        100,
+    );
+
+    assert_eq!(ix.program_id, pst_consumer::ID);
+    let metas: Vec<_> = ix
+        .accounts
+        .iter()
+        .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+        .collect();
+    assert_eq!(
+        metas,
+        [
+            (consumer, true, true),
+            (private_state, false, false),
+            (authority, true, true),
+            (anchor_lang::system_program::ID, false, false),
+        ]
+    );
+
+    assert_eq!(ix.data[..8], instruction::InitializeConsumer::DISCRIMINATOR);
+    let args = instruction::InitializeConsumer::try_from_slice(&ix.data[8..]).unwrap();
+    assert_eq!(args.private_state, private_state);
+    assert_eq!(args.initial_balance, -3);
+    assert_eq!(args.min_action_slots, 10);
+    assert_eq!(args.max_count, 100);
+}
+
+#[test]
+fn gated_action_matches_program_layout() {
+    let consumer = Pubkey::new_unique();
+    let private_state = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let ix = builders::gated_action(
+        &pst_consumer::ID,
+        &consumer,
+        &private_state,
+        &private_state_toolkit::ID,
+        &authority,
+        [9; 32],
+        42,
+    );
+
+    let metas: Vec<_> = ix
+        .accounts
+        .iter()
+        .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+        .collect();
+    assert_eq!(
+        metas,
+        [
+            (consumer, false, true),
+            (private_state, false, false),
+            (private_state_toolkit::ID, false, false),
+            (authority, true, false),
+        ]
+    );
+
+    assert_eq!(ix.data[..8], instruction::GatedAction::DISCRIMINATOR);
+    let args = instruction::GatedAction::try_from_slice(&ix.data[8..]).unwrap();
+    assert_eq!(args.expected_commitment, [9; 32]);
+    assert_eq!(args.expected_nonce, 42);
+}
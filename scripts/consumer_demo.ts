@@ -100,17 +100,30 @@ async function initializeConsumerAccount(params: {
   authority: Keypair;
   consumer: Keypair;
   privateState: PublicKey;
+  initialBalance?: bigint;
+  minActionSlots?: bigint;
+  maxCount?: bigint;
 }): Promise<string> {
+  const balanceBuf = Buffer.alloc(8);
+  balanceBuf.writeBigInt64LE(params.initialBalance ?? 0n);
+  const cooldownBuf = Buffer.alloc(8);
+  cooldownBuf.writeBigUInt64LE(params.minActionSlots ?? 0n);
+  const maxCountBuf = Buffer.alloc(8);
+  maxCountBuf.writeBigUInt64LE(params.maxCount ?? 0n);
   const ix = new TransactionInstruction({
     programId: CONSUMER_PROGRAM_ID,
     keys: [
       { pubkey: params.consumer.publicKey, isSigner: true, isWritable: true },
+      { pubkey: params.privateState, isSigner: false, isWritable: false },
       { pubkey: params.authority.publicKey, isSigner: true, isWritable: true },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
     ],
     data: Buffer.concat([
       DISCRIMINATOR.initializeConsumer,
       params.privateState.toBuffer(),
+      balanceBuf,
+      cooldownBuf,
+      maxCountBuf,
     ]),
   });
   const tx = new Transaction().add(ix);
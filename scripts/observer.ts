@@ -36,10 +36,12 @@ const COMMITTED_PATH = path.join(STATE_DIR, "state.committed.json");
 const PENDING_PATH = path.join(STATE_DIR, "state.pending.json");
 
 function decodeAccount(data: Buffer): ChainState {
-  const authority = new PublicKey(data.subarray(8, 40)).toBase58();
-  const commitmentHex = Buffer.from(data.subarray(40, 72)).toString("hex");
-  const nonce = data.readBigUInt64LE(72);
-  const policy = data.readUInt8(80);
+  // Legacy 81-byte accounts have no version byte after the discriminator.
+  const base = data.length === 81 ? 8 : 9;
+  const authority = new PublicKey(data.subarray(base, base + 32)).toBase58();
+  const commitmentHex = Buffer.from(data.subarray(base + 32, base + 64)).toString("hex");
+  const nonce = data.readBigUInt64LE(base + 64);
+  const policy = data.readUInt8(base + 72);
   return { nonce, commitmentHex, policy, authority };
 }
 
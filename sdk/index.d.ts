@@ -41,6 +41,17 @@ export declare function getProgramId(): PublicKey;
  * ```
  */
 export declare function commitment(nonce: bigint, encryptedPayload: Buffer): Buffer;
+/**
+ * Computes a chained commitment for `update_chained`.
+ *
+ * **Formula:** `sha256(old_commitment || nonce || link_proof)`
+ *
+ * @param oldCommitment - Commitment being replaced
+ * @param nonce - Nonce of the new state
+ * @param linkProof - 32 bytes mixed into the chain link
+ * @returns 32-byte SHA-256 commitment hash
+ */
+export declare function chainCommitment(oldCommitment: Buffer, nonce: bigint, linkProof: Buffer): Buffer;
 /**
  * Encrypted payload components from AES-256-GCM encryption.
  */
@@ -61,7 +72,20 @@ export declare enum UpdatePolicy {
     /** Nonce must increment by exactly 1 (turn-based, deterministic) */
     StrictSequential = 0,
     /** Nonce must increase but can skip values (async, offline-friendly) */
-    AllowSkips = 1
+    AllowSkips = 1,
+    /** Like StrictSequential, but capped at the account's max_nonce */
+    StrictSequentialWithCap = 2
+}
+/**
+ * Hash algorithm an account declares for its commitments.
+ *
+ * Recorded on-chain so clients and consumers agree; `commitment()` computes
+ * sha256 only.
+ */
+export declare enum HashAlgo {
+    Sha256 = 0,
+    /** For interop with Ethereum tooling */
+    Keccak256 = 1
 }
 /**
  * Encrypts plaintext using AES-256-GCM.
@@ -127,6 +151,14 @@ export declare function unpackEncryptedPayload(packed: Buffer): EncryptedPayload
  *
  * @param params.initialCommitment - sha256(nonce || encrypted_payload) at nonce 0
  * @param params.policy - Update policy enforced on-chain
+ * @param params.maxNonce - Nonce ceiling for StrictSequentialWithCap (default 0)
+ * @param params.minUpdateSlots - Minimum slots between updates (default 0)
+ * @param params.hashAlgo - Commitment hash the account declares (default Sha256)
+ * @param params.expirySlot - Last slot the state passes asserts (default 0 = never)
+ * @param params.feeLamports - Lamports charged per update (default 0 = free)
+ * @param params.feeDestination - Receiver of update fees (required with a fee)
+ * @param params.recovery - Key that may recover_authority after inactivity (default none)
+ * @param params.inactivitySlots - Idle slots before recovery (non-zero with a recovery key)
  * @returns Transaction signature
  */
 export declare function initPrivateState(params: {
@@ -135,6 +167,55 @@ export declare function initPrivateState(params: {
     privateState: Keypair;
     initialCommitment: Buffer;
     policy: UpdatePolicy;
+    maxNonce?: bigint;
+    minUpdateSlots?: bigint;
+    hashAlgo?: HashAlgo;
+    expirySlot?: bigint;
+    feeLamports?: bigint;
+    feeDestination?: PublicKey;
+    recovery?: PublicKey;
+    inactivitySlots?: bigint;
+}): Promise<string>;
+/**
+ * Derives the PDA address of a PST account from its authority and label.
+ *
+ * **Seeds:** `["pst", authority, label]`
+ *
+ * @param authority - Wallet that owns the account
+ * @param label - 16-byte namespace label
+ * @returns PDA address and bump seed
+ */
+export declare function findPrivateStatePda(authority: PublicKey, label: Buffer): [PublicKey, number];
+/**
+ * Initialize a new PST account at its authority + label PDA.
+ *
+ * @param params.label - 16-byte namespace label (see findPrivateStatePda)
+ * @param params.initialCommitment - sha256(nonce || encrypted_payload) at nonce 0
+ * @param params.policy - Update policy enforced on-chain
+ * @param params.maxNonce - Nonce ceiling for StrictSequentialWithCap (default 0)
+ * @param params.minUpdateSlots - Minimum slots between updates (default 0)
+ * @param params.hashAlgo - Commitment hash the account declares (default Sha256)
+ * @param params.expirySlot - Last slot the state passes asserts (default 0 = never)
+ * @param params.feeLamports - Lamports charged per update (default 0 = free)
+ * @param params.feeDestination - Receiver of update fees (required with a fee)
+ * @param params.recovery - Key that may recover_authority after inactivity (default none)
+ * @param params.inactivitySlots - Idle slots before recovery (non-zero with a recovery key)
+ * @returns Transaction signature
+ */
+export declare function initPrivateStatePda(params: {
+    connection: Connection;
+    authority: Keypair;
+    label: Buffer;
+    initialCommitment: Buffer;
+    policy: UpdatePolicy;
+    maxNonce?: bigint;
+    minUpdateSlots?: bigint;
+    hashAlgo?: HashAlgo;
+    expirySlot?: bigint;
+    feeLamports?: bigint;
+    feeDestination?: PublicKey;
+    recovery?: PublicKey;
+    inactivitySlots?: bigint;
 }): Promise<string>;
 /**
  * Update commitment + nonce with policy enforcement on-chain.
@@ -142,6 +223,8 @@ export declare function initPrivateState(params: {
  * @param params.oldCommitment - Must match stored commitment
  * @param params.newCommitment - New commitment hash
  * @param params.nextNonce - Candidate nonce (checked against policy)
+ * @param params.memo - Optional 32-byte correlation tag, emitted in StateUpdated only
+ * @param params.feeDestination - The account's fee destination (required if it charges a fee)
  * @returns Transaction signature
  */
 export declare function updatePrivateState(params: {
@@ -151,6 +234,8 @@ export declare function updatePrivateState(params: {
     oldCommitment: Buffer;
     newCommitment: Buffer;
     nextNonce: bigint;
+    feeDestination?: PublicKey;
+    memo?: Buffer;
 }): Promise<string>;
 /**
  * Update the policy byte (authority signer required).
@@ -176,7 +261,10 @@ export declare function assertState(params: {
     expectedCommitment: Buffer;
     expectedNonce: bigint;
 }): Promise<string>;
+/** Size of the original unversioned account layout (no version byte). */
+export declare const LEGACY_STATE_SPACE = 81;
 export type PrivateStateAccount = {
+    version: number;
     authority: PublicKey;
     commitment: Buffer;
     nonce: bigint;
@@ -186,6 +274,7 @@ export type PrivateStateAccount = {
  * Manual account decoding (skip discriminator).
  *
  * Layout:
+ * - version: u8 (absent in legacy 81-byte accounts, decoded as 0)
  * - authority: 32 bytes
  * - commitment: 32 bytes
  * - nonce: u64 LE
@@ -44,14 +44,17 @@ var __generator = (this && this.__generator) || function (thisArg, body) {
     }
 };
 Object.defineProperty(exports, "__esModule", { value: true });
-exports.UpdatePolicy = void 0;
+exports.LEGACY_STATE_SPACE = exports.HashAlgo = exports.UpdatePolicy = void 0;
 exports.getProgramId = getProgramId;
 exports.commitment = commitment;
+exports.chainCommitment = chainCommitment;
 exports.encryptPayload = encryptPayload;
 exports.decryptPayload = decryptPayload;
 exports.packEncryptedPayload = packEncryptedPayload;
 exports.unpackEncryptedPayload = unpackEncryptedPayload;
 exports.initPrivateState = initPrivateState;
+exports.findPrivateStatePda = findPrivateStatePda;
+exports.initPrivateStatePda = initPrivateStatePda;
 exports.updatePrivateState = updatePrivateState;
 exports.setPolicy = setPolicy;
 exports.assertState = assertState;
@@ -66,6 +69,7 @@ var web3_js_1 = require("@solana/web3.js");
  */
 var DISCRIMINATOR = {
     initialize: Buffer.from([175, 175, 109, 31, 13, 152, 155, 237]),
+    initializePda: Buffer.from([178, 254, 136, 212, 127, 85, 171, 210]),
     update: Buffer.from([219, 200, 88, 176, 158, 63, 253, 127]),
     transferAuthority: Buffer.from([35, 150, 249, 253, 241, 46, 101, 64]),
     assertState: Buffer.from([38, 168, 44, 85, 125, 248, 167, 163]),
@@ -117,6 +121,23 @@ function commitment(nonce, encryptedPayload) {
         .update(Buffer.concat([nonceBuf, encryptedPayload]))
         .digest();
 }
+/**
+ * Computes a chained commitment for `update_chained`.
+ *
+ * **Formula:** `sha256(old_commitment || nonce || link_proof)`
+ *
+ * @param oldCommitment - Commitment being replaced
+ * @param nonce - Nonce of the new state
+ * @param linkProof - 32 bytes mixed into the chain link
+ * @returns 32-byte SHA-256 commitment hash
+ */
+function chainCommitment(oldCommitment, nonce, linkProof) {
+    var nonceBuf = Buffer.alloc(8);
+    nonceBuf.writeBigUInt64LE(nonce);
+    return (0, crypto_1.createHash)("sha256")
+        .update(Buffer.concat([oldCommitment, nonceBuf, linkProof]))
+        .digest();
+}
 /**
  * Update policy for nonce validation.
  *
@@ -128,7 +149,21 @@ var UpdatePolicy;
     UpdatePolicy[UpdatePolicy["StrictSequential"] = 0] = "StrictSequential";
     /** Nonce must increase but can skip values (async, offline-friendly) */
     UpdatePolicy[UpdatePolicy["AllowSkips"] = 1] = "AllowSkips";
+    /** Like StrictSequential, but capped at the account's max_nonce */
+    UpdatePolicy[UpdatePolicy["StrictSequentialWithCap"] = 2] = "StrictSequentialWithCap";
 })(UpdatePolicy || (exports.UpdatePolicy = UpdatePolicy = {}));
+/**
+ * Hash algorithm an account declares for its commitments.
+ *
+ * Recorded on-chain so clients and consumers agree; `commitment()` computes
+ * sha256 only.
+ */
+var HashAlgo;
+(function (HashAlgo) {
+    HashAlgo[HashAlgo["Sha256"] = 0] = "Sha256";
+    /** For interop with Ethereum tooling */
+    HashAlgo[HashAlgo["Keccak256"] = 1] = "Keccak256";
+})(HashAlgo || (exports.HashAlgo = HashAlgo = {}));
 /**
  * Encrypts plaintext using AES-256-GCM.
  *
@@ -209,12 +244,31 @@ function unpackEncryptedPayload(packed) {
  *
  * @param params.initialCommitment - sha256(nonce || encrypted_payload) at nonce 0
  * @param params.policy - Update policy enforced on-chain
+ * @param params.maxNonce - Nonce ceiling for StrictSequentialWithCap (default 0)
+ * @param params.minUpdateSlots - Minimum slots between updates (default 0)
+ * @param params.hashAlgo - Commitment hash the account declares (default Sha256)
+ * @param params.expirySlot - Last slot the state passes asserts (default 0 = never)
+ * @param params.feeLamports - Lamports charged per update (default 0 = free)
+ * @param params.feeDestination - Receiver of update fees (required with a fee)
+ * @param params.recovery - Key that may recover_authority after inactivity (default none)
+ * @param params.inactivitySlots - Idle slots before recovery (non-zero with a recovery key)
  * @returns Transaction signature
  */
 function initPrivateState(params) {
     return __awaiter(this, void 0, void 0, function () {
-        var ix, tx;
+        var maxNonceBuf, minUpdateSlotsBuf, expirySlotBuf, feeLamportsBuf, inactivitySlotsBuf, ix, tx;
+        var _b, _c, _d, _e, _f, _g, _h;
         return __generator(this, function (_a) {
+            maxNonceBuf = Buffer.alloc(8);
+            maxNonceBuf.writeBigUInt64LE((_b = params.maxNonce) !== null && _b !== void 0 ? _b : BigInt(0));
+            minUpdateSlotsBuf = Buffer.alloc(8);
+            minUpdateSlotsBuf.writeBigUInt64LE((_c = params.minUpdateSlots) !== null && _c !== void 0 ? _c : BigInt(0));
+            expirySlotBuf = Buffer.alloc(8);
+            expirySlotBuf.writeBigUInt64LE((_e = params.expirySlot) !== null && _e !== void 0 ? _e : BigInt(0));
+            feeLamportsBuf = Buffer.alloc(8);
+            feeLamportsBuf.writeBigUInt64LE((_f = params.feeLamports) !== null && _f !== void 0 ? _f : BigInt(0));
+            inactivitySlotsBuf = Buffer.alloc(8);
+            inactivitySlotsBuf.writeBigUInt64LE((_h = params.inactivitySlots) !== null && _h !== void 0 ? _h : BigInt(0));
             ix = new web3_js_1.TransactionInstruction({
                 programId: getProgramId(),
                 keys: [
@@ -230,6 +284,17 @@ function initPrivateState(params) {
                     DISCRIMINATOR.initialize,
                     params.initialCommitment,
                     Buffer.from([params.policy]),
+                    maxNonceBuf,
+                    minUpdateSlotsBuf,
+                    Buffer.from([(_d = params.hashAlgo) !== null && _d !== void 0 ? _d : HashAlgo.Sha256]),
+                    expirySlotBuf,
+                    feeLamportsBuf,
+                    ((_g = params.feeDestination) !== null && _g !== void 0 ? _g : web3_js_1.PublicKey.default).toBuffer(),
+                    // Borsh Option<Pubkey>
+                    params.recovery
+                        ? Buffer.concat([Buffer.from([1]), params.recovery.toBuffer()])
+                        : Buffer.from([0]),
+                    inactivitySlotsBuf,
                 ]),
             });
             tx = new web3_js_1.Transaction().add(ix);
@@ -240,17 +305,97 @@ function initPrivateState(params) {
         });
     });
 }
+/**
+ * Derives the PDA address of a PST account from its authority and label.
+ *
+ * **Seeds:** `["pst", authority, label]`
+ *
+ * @param authority - Wallet that owns the account
+ * @param label - 16-byte namespace label
+ * @returns PDA address and bump seed
+ */
+function findPrivateStatePda(authority, label) {
+    if (label.length !== 16) {
+        throw new Error("label must be exactly 16 bytes.");
+    }
+    return web3_js_1.PublicKey.findProgramAddressSync([Buffer.from("pst"), authority.toBuffer(), label], getProgramId());
+}
+/**
+ * Initialize a new PST account at its authority + label PDA.
+ *
+ * @param params.label - 16-byte namespace label (see findPrivateStatePda)
+ * @param params.initialCommitment - sha256(nonce || encrypted_payload) at nonce 0
+ * @param params.policy - Update policy enforced on-chain
+ * @param params.maxNonce - Nonce ceiling for StrictSequentialWithCap (default 0)
+ * @param params.minUpdateSlots - Minimum slots between updates (default 0)
+ * @param params.hashAlgo - Commitment hash the account declares (default Sha256)
+ * @param params.expirySlot - Last slot the state passes asserts (default 0 = never)
+ * @param params.feeLamports - Lamports charged per update (default 0 = free)
+ * @param params.feeDestination - Receiver of update fees (required with a fee)
+ * @param params.recovery - Key that may recover_authority after inactivity (default none)
+ * @param params.inactivitySlots - Idle slots before recovery (non-zero with a recovery key)
+ * @returns Transaction signature
+ */
+function initPrivateStatePda(params) {
+    return __awaiter(this, void 0, void 0, function () {
+        var maxNonceBuf, minUpdateSlotsBuf, expirySlotBuf, feeLamportsBuf, inactivitySlotsBuf, privateState, ix, tx;
+        var _b, _c, _d, _e, _f, _g, _h;
+        return __generator(this, function (_a) {
+            maxNonceBuf = Buffer.alloc(8);
+            maxNonceBuf.writeBigUInt64LE((_b = params.maxNonce) !== null && _b !== void 0 ? _b : BigInt(0));
+            minUpdateSlotsBuf = Buffer.alloc(8);
+            minUpdateSlotsBuf.writeBigUInt64LE((_c = params.minUpdateSlots) !== null && _c !== void 0 ? _c : BigInt(0));
+            expirySlotBuf = Buffer.alloc(8);
+            expirySlotBuf.writeBigUInt64LE((_e = params.expirySlot) !== null && _e !== void 0 ? _e : BigInt(0));
+            feeLamportsBuf = Buffer.alloc(8);
+            feeLamportsBuf.writeBigUInt64LE((_f = params.feeLamports) !== null && _f !== void 0 ? _f : BigInt(0));
+            inactivitySlotsBuf = Buffer.alloc(8);
+            inactivitySlotsBuf.writeBigUInt64LE((_h = params.inactivitySlots) !== null && _h !== void 0 ? _h : BigInt(0));
+            privateState = findPrivateStatePda(params.authority.publicKey, params.label)[0];
+            ix = new web3_js_1.TransactionInstruction({
+                programId: getProgramId(),
+                keys: [
+                    { pubkey: privateState, isSigner: false, isWritable: true },
+                    { pubkey: params.authority.publicKey, isSigner: true, isWritable: true },
+                    { pubkey: web3_js_1.SystemProgram.programId, isSigner: false, isWritable: false },
+                ],
+                data: Buffer.concat([
+                    DISCRIMINATOR.initializePda,
+                    params.label,
+                    params.initialCommitment,
+                    Buffer.from([params.policy]),
+                    maxNonceBuf,
+                    minUpdateSlotsBuf,
+                    Buffer.from([(_d = params.hashAlgo) !== null && _d !== void 0 ? _d : HashAlgo.Sha256]),
+                    expirySlotBuf,
+                    feeLamportsBuf,
+                    ((_g = params.feeDestination) !== null && _g !== void 0 ? _g : web3_js_1.PublicKey.default).toBuffer(),
+                    // Borsh Option<Pubkey>
+                    params.recovery
+                        ? Buffer.concat([Buffer.from([1]), params.recovery.toBuffer()])
+                        : Buffer.from([0]),
+                    inactivitySlotsBuf,
+                ]),
+            });
+            tx = new web3_js_1.Transaction().add(ix);
+            return [2 /*return*/, (0, web3_js_1.sendAndConfirmTransaction)(params.connection, tx, [params.authority])];
+        });
+    });
+}
 /**
  * Update commitment + nonce with policy enforcement on-chain.
  *
  * @param params.oldCommitment - Must match stored commitment
  * @param params.newCommitment - New commitment hash
  * @param params.nextNonce - Candidate nonce (checked against policy)
+ * @param params.memo - Optional 32-byte correlation tag, emitted in StateUpdated only
+ * @param params.feeDestination - The account's fee destination (required if it charges a fee)
  * @returns Transaction signature
  */
 function updatePrivateState(params) {
     return __awaiter(this, void 0, void 0, function () {
         var nonceBuf, ix, tx;
+        var _b;
         return __generator(this, function (_a) {
             nonceBuf = Buffer.alloc(8);
             nonceBuf.writeBigUInt64LE(params.nextNonce);
@@ -258,13 +403,24 @@ function updatePrivateState(params) {
                 programId: getProgramId(),
                 keys: [
                     { pubkey: params.privateState, isSigner: false, isWritable: true },
-                    { pubkey: params.authority.publicKey, isSigner: true, isWritable: false },
+                    { pubkey: params.authority.publicKey, isSigner: true, isWritable: true },
+                    // Optional fee accounts: the program ID stands in for an omitted one
+                    {
+                        pubkey: (_b = params.feeDestination) !== null && _b !== void 0 ? _b : getProgramId(),
+                        isSigner: false,
+                        isWritable: params.feeDestination !== undefined,
+                    },
+                    { pubkey: web3_js_1.SystemProgram.programId, isSigner: false, isWritable: false },
                 ],
                 data: Buffer.concat([
                     DISCRIMINATOR.update,
                     params.oldCommitment,
                     params.newCommitment,
                     nonceBuf,
+                    // Borsh Option<[u8; 32]>
+                    params.memo
+                        ? Buffer.concat([Buffer.from([1]), params.memo])
+                        : Buffer.from([0]),
                 ]),
             });
             tx = new web3_js_1.Transaction().add(ix);
@@ -323,22 +479,33 @@ function assertState(params) {
         });
     });
 }
+/** Size of the original unversioned account layout (no version byte). */
+exports.LEGACY_STATE_SPACE = 81;
 /**
  * Manual account decoding (skip discriminator).
  *
  * Layout:
+ * - version: u8 (absent in legacy 81-byte accounts, decoded as 0)
  * - authority: 32 bytes
  * - commitment: 32 bytes
  * - nonce: u64 LE
  * - policy: u8
  */
 function decodePrivateState(data) {
-    var offset = 8;
+    var legacy = data.length === exports.LEGACY_STATE_SPACE;
+    var version = legacy ? 0 : data.readUInt8(8);
+    var offset = legacy ? 8 : 9;
     var authority = new web3_js_1.PublicKey(data.subarray(offset, offset + 32));
     var commitmentBuf = data.subarray(offset + 32, offset + 64);
     var nonce = data.readBigUInt64LE(offset + 64);
     var policy = data.readUInt8(offset + 72);
-    return { authority: authority, commitment: Buffer.from(commitmentBuf), nonce: nonce, policy: policy };
+    return {
+        version: version,
+        authority: authority,
+        commitment: Buffer.from(commitmentBuf),
+        nonce: nonce,
+        policy: policy,
+    };
 }
 /**
  * Fetch and decode on-chain PrivateState.
@@ -24,6 +24,7 @@ import {
  */
 const DISCRIMINATOR = {
   initialize: Buffer.from([175, 175, 109, 31, 13, 152, 155, 237]),
+  initializePda: Buffer.from([178, 254, 136, 212, 127, 85, 171, 210]),
   update: Buffer.from([219, 200, 88, 176, 158, 63, 253, 127]),
   transferAuthority: Buffer.from([35, 150, 249, 253, 241, 46, 101, 64]),
   assertState: Buffer.from([38, 168, 44, 85, 125, 248, 167, 163]),
@@ -78,6 +79,28 @@ export function commitment(nonce: bigint, encryptedPayload: Buffer): Buffer {
     .digest();
 }
 
+/**
+ * Computes a chained commitment for `update_chained`.
+ *
+ * **Formula:** `sha256(old_commitment || nonce || link_proof)`
+ *
+ * @param oldCommitment - Commitment being replaced
+ * @param nonce - Nonce of the new state
+ * @param linkProof - 32 bytes mixed into the chain link
+ * @returns 32-byte SHA-256 commitment hash
+ */
+export function chainCommitment(
+  oldCommitment: Buffer,
+  nonce: bigint,
+  linkProof: Buffer
+): Buffer {
+  const nonceBuf = Buffer.alloc(8);
+  nonceBuf.writeBigUInt64LE(nonce);
+  return createHash("sha256")
+    .update(Buffer.concat([oldCommitment, nonceBuf, linkProof]))
+    .digest();
+}
+
 /**
  * Encrypted payload components from AES-256-GCM encryption.
  */
@@ -100,6 +123,20 @@ export enum UpdatePolicy {
   StrictSequential = 0,
   /** Nonce must increase but can skip values (async, offline-friendly) */
   AllowSkips = 1,
+  /** Like StrictSequential, but capped at the account's max_nonce */
+  StrictSequentialWithCap = 2,
+}
+
+/**
+ * Hash algorithm an account declares for its commitments.
+ *
+ * Recorded on-chain so clients and consumers agree; `commitment()` computes
+ * sha256 only.
+ */
+export enum HashAlgo {
+  Sha256 = 0,
+  /** For interop with Ethereum tooling */
+  Keccak256 = 1,
 }
 
 /**
@@ -189,6 +226,14 @@ export function unpackEncryptedPayload(packed: Buffer): EncryptedPayload {
  *
  * @param params.initialCommitment - sha256(nonce || encrypted_payload) at nonce 0
  * @param params.policy - Update policy enforced on-chain
+ * @param params.maxNonce - Nonce ceiling for StrictSequentialWithCap (default 0)
+ * @param params.minUpdateSlots - Minimum slots between updates (default 0)
+ * @param params.hashAlgo - Commitment hash the account declares (default Sha256)
+ * @param params.expirySlot - Last slot the state passes asserts (default 0 = never)
+ * @param params.feeLamports - Lamports charged per update (default 0 = free)
+ * @param params.feeDestination - Receiver of update fees (required with a fee)
+ * @param params.recovery - Key that may recover_authority after inactivity (default none)
+ * @param params.inactivitySlots - Idle slots before recovery (non-zero with a recovery key)
  * @returns Transaction signature
  */
 export async function initPrivateState(params: {
@@ -197,7 +242,25 @@ export async function initPrivateState(params: {
   privateState: Keypair;
   initialCommitment: Buffer;
   policy: UpdatePolicy;
+  maxNonce?: bigint;
+  minUpdateSlots?: bigint;
+  hashAlgo?: HashAlgo;
+  expirySlot?: bigint;
+  feeLamports?: bigint;
+  feeDestination?: PublicKey;
+  recovery?: PublicKey;
+  inactivitySlots?: bigint;
 }): Promise<string> {
+  const maxNonceBuf = Buffer.alloc(8);
+  maxNonceBuf.writeBigUInt64LE(params.maxNonce ?? 0n);
+  const minUpdateSlotsBuf = Buffer.alloc(8);
+  minUpdateSlotsBuf.writeBigUInt64LE(params.minUpdateSlots ?? 0n);
+  const expirySlotBuf = Buffer.alloc(8);
+  expirySlotBuf.writeBigUInt64LE(params.expirySlot ?? 0n);
+  const feeLamportsBuf = Buffer.alloc(8);
+  feeLamportsBuf.writeBigUInt64LE(params.feeLamports ?? 0n);
+  const inactivitySlotsBuf = Buffer.alloc(8);
+  inactivitySlotsBuf.writeBigUInt64LE(params.inactivitySlots ?? 0n);
   const ix = new TransactionInstruction({
     programId: getProgramId(),
     keys: [
@@ -213,6 +276,17 @@ export async function initPrivateState(params: {
       DISCRIMINATOR.initialize,
       params.initialCommitment,
       Buffer.from([params.policy]),
+      maxNonceBuf,
+      minUpdateSlotsBuf,
+      Buffer.from([params.hashAlgo ?? HashAlgo.Sha256]),
+      expirySlotBuf,
+      feeLamportsBuf,
+      (params.feeDestination ?? PublicKey.default).toBuffer(),
+      // Borsh Option<Pubkey>
+      params.recovery
+        ? Buffer.concat([Buffer.from([1]), params.recovery.toBuffer()])
+        : Buffer.from([0]),
+      inactivitySlotsBuf,
     ]),
   });
   const tx = new Transaction().add(ix);
@@ -222,12 +296,110 @@ export async function initPrivateState(params: {
   ]);
 }
 
+/**
+ * Derives the PDA address of a PST account from its authority and label.
+ *
+ * **Seeds:** `["pst", authority, label]`
+ *
+ * @param authority - Wallet that owns the account
+ * @param label - 16-byte namespace label
+ * @returns PDA address and bump seed
+ */
+export function findPrivateStatePda(
+  authority: PublicKey,
+  label: Buffer
+): [PublicKey, number] {
+  if (label.length !== 16) {
+    throw new Error("label must be exactly 16 bytes.");
+  }
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("pst"), authority.toBuffer(), label],
+    getProgramId()
+  );
+}
+
+/**
+ * Initialize a new PST account at its authority + label PDA.
+ *
+ * @param params.label - 16-byte namespace label (see findPrivateStatePda)
+ * @param params.initialCommitment - sha256(nonce || encrypted_payload) at nonce 0
+ * @param params.policy - Update policy enforced on-chain
+ * @param params.maxNonce - Nonce ceiling for StrictSequentialWithCap (default 0)
+ * @param params.minUpdateSlots - Minimum slots between updates (default 0)
+ * @param params.hashAlgo - Commitment hash the account declares (default Sha256)
+ * @param params.expirySlot - Last slot the state passes asserts (default 0 = never)
+ * @param params.feeLamports - Lamports charged per update (default 0 = free)
+ * @param params.feeDestination - Receiver of update fees (required with a fee)
+ * @param params.recovery - Key that may recover_authority after inactivity (default none)
+ * @param params.inactivitySlots - Idle slots before recovery (non-zero with a recovery key)
+ * @returns Transaction signature
+ */
+export async function initPrivateStatePda(params: {
+  connection: Connection;
+  authority: Keypair;
+  label: Buffer;
+  initialCommitment: Buffer;
+  policy: UpdatePolicy;
+  maxNonce?: bigint;
+  minUpdateSlots?: bigint;
+  hashAlgo?: HashAlgo;
+  expirySlot?: bigint;
+  feeLamports?: bigint;
+  feeDestination?: PublicKey;
+  recovery?: PublicKey;
+  inactivitySlots?: bigint;
+}): Promise<string> {
+  const maxNonceBuf = Buffer.alloc(8);
+  maxNonceBuf.writeBigUInt64LE(params.maxNonce ?? 0n);
+  const minUpdateSlotsBuf = Buffer.alloc(8);
+  minUpdateSlotsBuf.writeBigUInt64LE(params.minUpdateSlots ?? 0n);
+  const expirySlotBuf = Buffer.alloc(8);
+  expirySlotBuf.writeBigUInt64LE(params.expirySlot ?? 0n);
+  const feeLamportsBuf = Buffer.alloc(8);
+  feeLamportsBuf.writeBigUInt64LE(params.feeLamports ?? 0n);
+  const inactivitySlotsBuf = Buffer.alloc(8);
+  inactivitySlotsBuf.writeBigUInt64LE(params.inactivitySlots ?? 0n);
+  const [privateState] = findPrivateStatePda(
+    params.authority.publicKey,
+    params.label
+  );
+  const ix = new TransactionInstruction({
+    programId: getProgramId(),
+    keys: [
+      { pubkey: privateState, isSigner: false, isWritable: true },
+      { pubkey: params.authority.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    ],
+    data: Buffer.concat([
+      DISCRIMINATOR.initializePda,
+      params.label,
+      params.initialCommitment,
+      Buffer.from([params.policy]),
+      maxNonceBuf,
+      minUpdateSlotsBuf,
+      Buffer.from([params.hashAlgo ?? HashAlgo.Sha256]),
+      expirySlotBuf,
+      feeLamportsBuf,
+      (params.feeDestination ?? PublicKey.default).toBuffer(),
+      // Borsh Option<Pubkey>
+      params.recovery
+        ? Buffer.concat([Buffer.from([1]), params.recovery.toBuffer()])
+        : Buffer.from([0]),
+      inactivitySlotsBuf,
+    ]),
+  });
+  const tx = new Transaction().add(ix);
+  return sendAndConfirmTransaction(params.connection, tx, [params.authority]);
+}
+
 /**
  * Update commitment + nonce with policy enforcement on-chain.
  *
  * @param params.oldCommitment - Must match stored commitment
  * @param params.newCommitment - New commitment hash
  * @param params.nextNonce - Candidate nonce (checked against policy)
+ * @param params.memo - Optional 32-byte correlation tag, emitted in StateUpdated only
+ * @param params.feeDestination - The account's fee destination (required if it charges a fee)
  * @returns Transaction signature
  */
 export async function updatePrivateState(params: {
@@ -237,6 +409,8 @@ export async function updatePrivateState(params: {
   oldCommitment: Buffer;
   newCommitment: Buffer;
   nextNonce: bigint;
+  feeDestination?: PublicKey;
+  memo?: Buffer;
 }): Promise<string> {
   const nonceBuf = Buffer.alloc(8);
   nonceBuf.writeBigUInt64LE(params.nextNonce);
@@ -244,13 +418,24 @@ export async function updatePrivateState(params: {
     programId: getProgramId(),
     keys: [
       { pubkey: params.privateState, isSigner: false, isWritable: true },
-      { pubkey: params.authority.publicKey, isSigner: true, isWritable: false },
+      { pubkey: params.authority.publicKey, isSigner: true, isWritable: true },
+      // Optional fee accounts: the program ID stands in for an omitted one
+      {
+        pubkey: params.feeDestination ?? getProgramId(),
+        isSigner: false,
+        isWritable: params.feeDestination !== undefined,
+      },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
     ],
     data: Buffer.concat([
       DISCRIMINATOR.update,
       params.oldCommitment,
       params.newCommitment,
       nonceBuf,
+      // Borsh Option<[u8; 32]>
+      params.memo
+        ? Buffer.concat([Buffer.from([1]), params.memo])
+        : Buffer.from([0]),
     ]),
   });
   const tx = new Transaction().add(ix);
@@ -311,7 +496,11 @@ export async function assertState(params: {
   return sendAndConfirmTransaction(params.connection, tx, [params.payer]);
 }
 
+/** Size of the original unversioned account layout (no version byte). */
+export const LEGACY_STATE_SPACE = 81;
+
 export type PrivateStateAccount = {
+  version: number;
   authority: PublicKey;
   commitment: Buffer;
   nonce: bigint;
@@ -322,18 +511,27 @@ export type PrivateStateAccount = {
  * Manual account decoding (skip discriminator).
  *
  * Layout:
+ * - version: u8 (absent in legacy 81-byte accounts, decoded as 0)
  * - authority: 32 bytes
  * - commitment: 32 bytes
  * - nonce: u64 LE
  * - policy: u8
  */
 export function decodePrivateState(data: Buffer): PrivateStateAccount {
-  const offset = 8;
+  const legacy = data.length === LEGACY_STATE_SPACE;
+  const version = legacy ? 0 : data.readUInt8(8);
+  const offset = legacy ? 8 : 9;
   const authority = new PublicKey(data.subarray(offset, offset + 32));
   const commitmentBuf = data.subarray(offset + 32, offset + 64);
   const nonce = data.readBigUInt64LE(offset + 64);
   const policy = data.readUInt8(offset + 72) as UpdatePolicy;
-  return { authority, commitment: Buffer.from(commitmentBuf), nonce, policy };
+  return {
+    version,
+    authority,
+    commitment: Buffer.from(commitmentBuf),
+    nonce,
+    policy,
+  };
 }
 
 /**
@@ -1,14 +1,17 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PrivateStateToolkit } from "../target/types/private_state_toolkit";
+import { PstConsumer } from "../target/types/pst_consumer";
 import { expect } from "chai";
 import { createHash } from "crypto";
+import { readFileSync } from "fs";
 
 describe("private_state_toolkit", () => {
   const provider = anchor.AnchorProvider.env();
   anchor.setProvider(provider);
 
   const program = anchor.workspace.PrivateStateToolkit as Program<PrivateStateToolkit>;
+  const consumerProgram = anchor.workspace.PstConsumer as Program<PstConsumer>;
 
   let privateStateKeypair: anchor.web3.Keypair;
   let authority: anchor.web3.Keypair;
@@ -18,6 +21,81 @@ describe("private_state_toolkit", () => {
     authority = anchor.web3.Keypair.generate();
   });
 
+  // Funds an account so it can pay for account creation.
+  async function airdrop(pubkey: anchor.web3.PublicKey) {
+    await provider.connection.requestAirdrop(
+      pubkey,
+      2 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await new Promise(resolve => setTimeout(resolve, 1000));
+  }
+
+  // Creates a private state account owned by `owner` and returns its keypair.
+  async function initState(
+    owner: anchor.web3.Keypair,
+    commitment: Buffer,
+    policy: number,
+    state: anchor.web3.Keypair = anchor.web3.Keypair.generate(),
+    maxNonce: number = 0,
+    minUpdateSlots: number = 0,
+    hashAlgo: number = 0,
+    expirySlot: number = 0,
+    feeLamports: number = 0,
+    feeDestination: anchor.web3.PublicKey = anchor.web3.PublicKey.default,
+    recovery: anchor.web3.PublicKey | null = null,
+    inactivitySlots: number = 0
+  ): Promise<anchor.web3.Keypair> {
+    await program.methods
+      .initializeRaw(
+        Array.from(commitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(maxNonce),
+          minUpdateSlots: new anchor.BN(minUpdateSlots),
+          hashAlgo,
+          expirySlot: new anchor.BN(expirySlot),
+          feeLamports: new anchor.BN(feeLamports),
+          feeDestination,
+          recovery,
+          inactivitySlots: new anchor.BN(inactivitySlots),
+        }
+      )
+      .accounts({
+        privateState: state.publicKey,
+        authority: owner.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([state, owner])
+      .rpc();
+    return state;
+  }
+
+  // Creates a consumer account linked to `state` and returns its keypair.
+  async function initConsumer(
+    owner: anchor.web3.Keypair,
+    state: anchor.web3.PublicKey,
+    initialBalance: number = 0,
+    minActionSlots: number = 0,
+    maxCount: number = 0
+  ): Promise<anchor.web3.Keypair> {
+    const consumer = anchor.web3.Keypair.generate();
+    await consumerProgram.methods
+      .initializeConsumer(
+        state,
+        new anchor.BN(initialBalance),
+        new anchor.BN(minActionSlots),
+        new anchor.BN(maxCount)
+      )
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state,
+        authority: owner.publicKey,
+      })
+      .signers([consumer, owner])
+      .rpc();
+    return consumer;
+  }
+
   it("Initializes a private state account", async () => {
     // Airdrop to authority
     await provider.connection.requestAirdrop(
@@ -30,7 +108,20 @@ describe("private_state_toolkit", () => {
     const policy = 0; // StrictSequential
 
     await program.methods
-      .initialize(Array.from(initialCommitment), policy)
+      .initializeRaw(
+        Array.from(initialCommitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
       .accounts({
         privateState: privateStateKeypair.publicKey,
         authority: authority.publicKey,
@@ -61,7 +152,20 @@ describe("private_state_toolkit", () => {
     const policy = 0;
 
     await program.methods
-      .initialize(Array.from(initialCommitment), policy)
+      .initializeRaw(
+        Array.from(initialCommitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
       .accounts({
         privateState: privateStateKeypair.publicKey,
         authority: authority.publicKey,
@@ -74,7 +178,8 @@ describe("private_state_toolkit", () => {
       .update(
         Array.from(initialCommitment),
         Array.from(newCommitment),
-        new anchor.BN(1)
+        new anchor.BN(1),
+        null
       )
       .accounts({
         privateState: privateStateKeypair.publicKey,
@@ -104,7 +209,20 @@ describe("private_state_toolkit", () => {
     const policy = 0;
 
     await program.methods
-      .initialize(Array.from(initialCommitment), policy)
+      .initializeRaw(
+        Array.from(initialCommitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
       .accounts({
         privateState: privateStateKeypair.publicKey,
         authority: authority.publicKey,
@@ -118,7 +236,8 @@ describe("private_state_toolkit", () => {
         .update(
           Array.from(wrongCommitment),
           Array.from(newCommitment),
-          new anchor.BN(1)
+          new anchor.BN(1),
+          null
         )
         .accounts({
           privateState: privateStateKeypair.publicKey,
@@ -145,7 +264,20 @@ describe("private_state_toolkit", () => {
     const policy = 0;
 
     await program.methods
-      .initialize(Array.from(initialCommitment), policy)
+      .initializeRaw(
+        Array.from(initialCommitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
       .accounts({
         privateState: privateStateKeypair.publicKey,
         authority: authority.publicKey,
@@ -182,7 +314,20 @@ describe("private_state_toolkit", () => {
     const policy = 1; // AllowSkips
 
     await program.methods
-      .initialize(Array.from(initialCommitment), policy)
+      .initializeRaw(
+        Array.from(initialCommitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
       .accounts({
         privateState: privateStateKeypair.publicKey,
         authority: authority.publicKey,
@@ -196,7 +341,8 @@ describe("private_state_toolkit", () => {
       .update(
         Array.from(initialCommitment),
         Array.from(newCommitment),
-        new anchor.BN(5)
+        new anchor.BN(5),
+        null
       )
       .accounts({
         privateState: privateStateKeypair.publicKey,
@@ -223,7 +369,20 @@ describe("private_state_toolkit", () => {
     const policy = 0;
 
     await program.methods
-      .initialize(Array.from(initialCommitment), policy)
+      .initializeRaw(
+        Array.from(initialCommitment),
+        policy,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
       .accounts({
         privateState: privateStateKeypair.publicKey,
         authority: authority.publicKey,
@@ -255,4 +414,3585 @@ describe("private_state_toolkit", () => {
       expect(error.toString()).to.include("CommitmentMismatch");
     }
   });
+
+  it("Sets policy on many accounts in one transaction", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const stateA = await initState(authority, commitment, 0);
+    const stateB = await initState(authority, commitment, 0);
+
+    await program.methods
+      .setPolicyMany(1)
+      .accounts({ authority: authority.publicKey })
+      .remainingAccounts([
+        { pubkey: stateA.publicKey, isSigner: false, isWritable: true },
+        { pubkey: stateB.publicKey, isSigner: false, isWritable: true },
+      ])
+      .signers([authority])
+      .rpc();
+
+    for (const state of [stateA, stateB]) {
+      const account = await program.account.privateState.fetch(state.publicKey);
+      expect(account.policy).to.equal(1);
+    }
+
+    // An account owned by someone else fails the whole batch
+    const other = anchor.web3.Keypair.generate();
+    await airdrop(other.publicKey);
+    const foreign = await initState(other, commitment, 1);
+    try {
+      await program.methods
+        .setPolicyMany(0)
+        .accounts({ authority: authority.publicKey })
+        .remainingAccounts([
+          { pubkey: stateA.publicKey, isSigner: false, isWritable: true },
+          { pubkey: foreign.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("ConstraintHasOne");
+    }
+  });
+
+  it("Verifies an off-chain message signed by the authority", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    const message = Buffer.from(createHash("sha256").update("intent").digest());
+    const edIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+      privateKey: authority.secretKey,
+      message,
+    });
+    // web3.js packs pubkey at 16..48 and the signature at 48..112
+    const signature = edIx.data.subarray(48, 112);
+
+    await program.methods
+      .assertAuthoritySigned(Array.from(message), Array.from(signature))
+      .accounts({
+        privateState: state.publicKey,
+        instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+      })
+      .preInstructions([edIx])
+      .rpc();
+
+    // A signature from a different key must be rejected
+    const stranger = anchor.web3.Keypair.generate();
+    const strangerIx = anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+      privateKey: stranger.secretKey,
+      message,
+    });
+    try {
+      await program.methods
+        .assertAuthoritySigned(
+          Array.from(message),
+          Array.from(strangerIx.data.subarray(48, 112))
+        )
+        .accounts({
+          privateState: state.publicKey,
+          instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .preInstructions([strangerIx])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("SignatureMismatch");
+    }
+  });
+
+  it("Reblinds the commitment and advances the nonce", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("blind-1").digest());
+    const reblinded = Buffer.from(createHash("sha256").update("blind-2").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .reblind(Array.from(commitment), Array.from(reblinded), new anchor.BN(1))
+      .accounts({
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(Buffer.from(account.commitment).equals(reblinded)).to.be.true;
+    expect(account.nonce.toNumber()).to.equal(1);
+
+    // Reblinding to the same commitment is not a re-randomization
+    try {
+      await program.methods
+        .reblind(Array.from(reblinded), Array.from(reblinded), new anchor.BN(2))
+        .accounts({
+          privateState: state.publicKey,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("ReblindUnchanged");
+    }
+  });
+
+  it("Bumps a group of accounts in lockstep", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("group").digest());
+    const stateA = await initState(authority, commitment, 0);
+    const stateB = await initState(authority, commitment, 1);
+
+    await program.methods
+      .bumpGroup()
+      .accounts({ authority: authority.publicKey })
+      .remainingAccounts([
+        { pubkey: stateA.publicKey, isSigner: false, isWritable: true },
+        { pubkey: stateB.publicKey, isSigner: false, isWritable: true },
+      ])
+      .signers([authority])
+      .rpc();
+
+    for (const state of [stateA, stateB]) {
+      const account = await program.account.privateState.fetch(state.publicKey);
+      expect(account.nonce.toNumber()).to.equal(1);
+      expect(Buffer.from(account.commitment).equals(commitment)).to.be.true;
+    }
+
+    // Listing the same account twice would bump it twice
+    try {
+      await program.methods
+        .bumpGroup()
+        .accounts({ authority: authority.publicKey })
+        .remainingAccounts([
+          { pubkey: stateA.publicKey, isSigner: false, isWritable: true },
+          { pubkey: stateA.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("DuplicateAccount");
+    }
+  });
+
+  it("Compares only the significant commitment prefix", async () => {
+    await airdrop(authority.publicKey);
+
+    // 20-byte digest padded to 32 bytes
+    const short = Buffer.alloc(32);
+    createHash("ripemd160").update("short").digest().copy(short);
+    const state = await initState(authority, short, 0);
+
+    await program.methods
+      .setCommitmentLen(20)
+      .accounts({
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.commitmentLen).to.equal(20);
+
+    await program.methods
+      .assertState(Array.from(short), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    // Updates must keep the padding zeroed
+    const dirty = Buffer.alloc(32, 1);
+    try {
+      await program.methods
+        .update(Array.from(short), Array.from(dirty), new anchor.BN(1), null)
+        .accounts({
+          privateState: state.publicKey,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonCanonicalCommitment");
+    }
+  });
+
+  it("Asserts the policy is within an allowed set", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 1);
+
+    await program.methods
+      .assertPolicyIn(Buffer.from([0, 1]))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    try {
+      await program.methods
+        .assertPolicyIn(Buffer.from([0]))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("PolicyNotAllowed");
+    }
+  });
+
+  it("Transfers authority and resets the state", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("old-owner").digest());
+    const fresh = Buffer.from(createHash("sha256").update("new-owner").digest());
+    const newAuthority = anchor.web3.Keypair.generate();
+    const state = await initState(authority, commitment, 1);
+
+    await program.methods
+      .update(Array.from(commitment), Array.from(commitment), new anchor.BN(7), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .transferAndReset(newAuthority.publicKey, Array.from(fresh))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(newAuthority.publicKey.toString());
+    expect(Buffer.from(account.commitment).equals(fresh)).to.be.true;
+    expect(account.nonce.toNumber()).to.equal(0);
+  });
+
+  it("Refuses transfer_and_reset once resets are disabled", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .setAllowReset(false)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .transferAndReset(anchor.web3.Keypair.generate().publicKey, Array.from(commitment))
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("ResetNotAllowed");
+    }
+  });
+
+  it("Asserts two accounts pair to an expected commitment", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitA = Buffer.from(createHash("sha256").update("player-a").digest());
+    const commitB = Buffer.from(createHash("sha256").update("player-b").digest());
+    const stateA = await initState(authority, commitA, 0);
+    const stateB = await initState(authority, commitB, 0);
+
+    const [lo, hi] = Buffer.compare(commitA, commitB) <= 0 ? [commitA, commitB] : [commitB, commitA];
+    const pairing = createHash("sha256").update(Buffer.concat([lo, hi])).digest();
+
+    // Order of the accounts does not matter
+    for (const [a, b] of [[stateA, stateB], [stateB, stateA]]) {
+      await program.methods
+        .assertPair(Array.from(pairing))
+        .accounts({ stateA: a.publicKey, stateB: b.publicKey })
+        .rpc();
+    }
+
+    try {
+      await program.methods
+        .assertPair(Array.from(commitA))
+        .accounts({ stateA: stateA.publicKey, stateB: stateB.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("PairMismatch");
+    }
+  });
+
+  it("Reports the next valid nonce", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 1);
+
+    await program.methods
+      .update(Array.from(commitment), Array.from(commitment), new anchor.BN(41), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const next = await program.methods
+      .nextNonce()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    expect(next.toNumber()).to.equal(42);
+  });
+
+  it("Attaches write-once metadata", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const [metadata] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("meta"), state.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const blob = Buffer.alloc(64);
+    createHash("sha256").update("document").digest().copy(blob);
+
+    const setMetadata = () =>
+      program.methods
+        .setMetadata(Array.from(blob))
+        .accounts({
+          privateState: state.publicKey,
+          metadata,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+    await setMetadata();
+    const account = await program.account.metaData.fetch(metadata);
+    expect(account.privateState.toString()).to.equal(state.publicKey.toString());
+    expect(Buffer.from(account.blob).equals(blob)).to.be.true;
+
+    try {
+      await setMetadata();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("MetadataAlreadySet");
+    }
+  });
+
+  it("Asserts the authority is one of the allowed issuers", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const otherIssuer = anchor.web3.Keypair.generate().publicKey;
+
+    await program.methods
+      .assertAuthorityIn([otherIssuer, authority.publicKey])
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    try {
+      await program.methods
+        .assertAuthorityIn([otherIssuer])
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("AuthorityNotAllowed");
+    }
+  });
+
+  it("Closes an account and returns rent to the authority", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const before = await provider.connection.getBalance(authority.publicKey);
+
+    await program.methods
+      .close()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    expect(await provider.connection.getAccountInfo(state.publicKey)).to.be.null;
+    expect(await provider.connection.getBalance(authority.publicKey)).to.be.greaterThan(before);
+  });
+
+  it("Emits StateUpdated with both commitments", async () => {
+    await airdrop(authority.publicKey);
+
+    const oldCommitment = Buffer.from(createHash("sha256").update("test1").digest());
+    const newCommitment = Buffer.from(createHash("sha256").update("test2").digest());
+    const state = await initState(authority, oldCommitment, 0);
+
+    let event: any = null;
+    const listener = program.addEventListener("stateUpdated", (e) => {
+      event = e;
+    });
+
+    await program.methods
+      .update(Array.from(oldCommitment), Array.from(newCommitment), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.removeEventListener(listener);
+
+    expect(event).to.not.be.null;
+    expect(event.account.toString()).to.equal(state.publicKey.toString());
+    expect(Buffer.from(event.oldCommitment).equals(oldCommitment)).to.be.true;
+    expect(Buffer.from(event.newCommitment).equals(newCommitment)).to.be.true;
+    expect(event.nonce.toNumber()).to.equal(1);
+    expect(event.reblind).to.be.false;
+  });
+
+  it("Transfers authority in two steps", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const newAuthority = anchor.web3.Keypair.generate();
+    const stranger = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .proposeAuthorityTransfer(newAuthority.publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    let account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(authority.publicKey.toString());
+    expect(account.pendingAuthority.toString()).to.equal(newAuthority.publicKey.toString());
+
+    // Only the proposed key can accept
+    try {
+      await program.methods
+        .acceptAuthorityTransfer()
+        .accounts({ privateState: state.publicKey, newAuthority: stranger.publicKey })
+        .signers([stranger])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NotPendingAuthority");
+    }
+
+    await program.methods
+      .acceptAuthorityTransfer()
+      .accounts({ privateState: state.publicKey, newAuthority: newAuthority.publicKey })
+      .signers([newAuthority])
+      .rpc();
+
+    account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(newAuthority.publicKey.toString());
+    expect(account.pendingAuthority).to.be.null;
+  });
+
+  it("Cancels a pending authority transfer", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .proposeAuthorityTransfer(anchor.web3.Keypair.generate().publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .cancelAuthorityTransfer()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.pendingAuthority).to.be.null;
+  });
+
+  it("Asserts a minimum nonce for freshness gating", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 1);
+    await program.methods
+      .update(Array.from(commitment), Array.from(commitment), new anchor.BN(10), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    for (const min of [0, 10]) {
+      await program.methods
+        .assertStateMinNonce(Array.from(commitment), new anchor.BN(min))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+    }
+
+    try {
+      await program.methods
+        .assertStateMinNonce(Array.from(commitment), new anchor.BN(11))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceBelowMinimum");
+    }
+  });
+
+  it("Asserts the commitment regardless of nonce", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("credential").digest());
+    const state = await initState(authority, commitment, 1);
+    await program.methods
+      .update(Array.from(commitment), Array.from(commitment), new anchor.BN(99), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .assertCommitment(Array.from(commitment))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    const wrongCommitment = Buffer.from(createHash("sha256").update("wrong").digest());
+    try {
+      await program.methods
+        .assertCommitment(Array.from(wrongCommitment))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CommitmentMismatch");
+    }
+  });
+
+  it("Initializes a private state PDA from authority + label", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const label = Buffer.alloc(16);
+    label.write("profile");
+    const [pda, bump] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("pst"), authority.publicKey.toBuffer(), label],
+      program.programId
+    );
+
+    await program.methods
+      .initializePda(
+        Array.from(label),
+        Array.from(commitment),
+        0,
+        {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        }
+      )
+      .accounts({
+        privateState: pda,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(pda);
+    expect(account.authority.toString()).to.equal(authority.publicKey.toString());
+    expect(account.bump).to.equal(bump);
+
+    // A retried initialization fails with a clear error
+    try {
+      await program.methods
+        .initializePda(Array.from(label), Array.from(Buffer.alloc(32, 1)), 0, {
+          maxNonce: new anchor.BN(0),
+          minUpdateSlots: new anchor.BN(0),
+          hashAlgo: 0,
+          expirySlot: new anchor.BN(0),
+          feeLamports: new anchor.BN(0),
+          feeDestination: anchor.web3.PublicKey.default,
+          recovery: null,
+          inactivitySlots: new anchor.BN(0),
+        })
+        .accounts({
+          privateState: pda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("AlreadyInitialized");
+    }
+  });
+
+  it("Enforces hash chaining in update_chained", async () => {
+    await airdrop(authority.publicKey);
+
+    const oldCommitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, oldCommitment, 0);
+
+    const linkProof = Buffer.alloc(32, 7);
+    const nonceBuf = Buffer.alloc(8);
+    nonceBuf.writeBigUInt64LE(1n);
+    const chained = createHash("sha256")
+      .update(Buffer.concat([oldCommitment, nonceBuf, linkProof]))
+      .digest();
+
+    const unchained = Buffer.from(createHash("sha256").update("forked").digest());
+    try {
+      await program.methods
+        .updateChained(
+          Array.from(oldCommitment),
+          Array.from(unchained),
+          new anchor.BN(1),
+          Array.from(linkProof)
+        )
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("ChainLinkMismatch");
+    }
+
+    await program.methods
+      .updateChained(
+        Array.from(oldCommitment),
+        Array.from(chained),
+        new anchor.BN(1),
+        Array.from(linkProof)
+      )
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(Buffer.from(account.commitment).equals(chained)).to.be.true;
+  });
+
+  it("Rejects updates past max_nonce under StrictSequentialWithCap", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("turn0").digest());
+    const c1 = Buffer.from(createHash("sha256").update("turn1").digest());
+    const c2 = Buffer.from(createHash("sha256").update("turn2").digest());
+    const state = await initState(authority, c0, 2, undefined, 1);
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .update(Array.from(c1), Array.from(c2), new anchor.BN(2), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("MaxNonceReached");
+    }
+  });
+
+  it("Rejects updates sooner than min_update_slots", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0, undefined, 0, 1_000_000);
+
+    try {
+      await program.methods
+        .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("UpdateTooSoon");
+    }
+  });
+
+  it("Blocks updates while frozen but keeps asserts working", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+
+    await program.methods
+      .freeze(0)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateFrozen");
+    }
+
+    await program.methods
+      .assertState(Array.from(c0), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    await program.methods
+      .unfreeze()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+  });
+
+  it("Applies a batch of transitions atomically", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+
+    const transitions = [2, 5, 9].map((nonce) => ({
+      newCommitment: Array.from(
+        createHash("sha256").update(`state-${nonce}`).digest()
+      ),
+      nonce: new anchor.BN(nonce),
+    }));
+
+    await program.methods
+      .batchUpdate(Array.from(c0), transitions)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(9);
+    expect(Array.from(account.commitment)).to.deep.equal(transitions[2].newCommitment);
+
+    try {
+      await program.methods
+        .batchUpdate(
+          Array.from(account.commitment),
+          [
+            { newCommitment: Array.from(c0), nonce: new anchor.BN(10) },
+            { newCommitment: Array.from(c0), nonce: new anchor.BN(10) },
+          ]
+        )
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceNotMonotonic");
+    }
+  });
+
+  it("Returns the current state via get_state", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 1);
+
+    const view = await program.methods
+      .getState()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    expect(Buffer.from(view.commitment).equals(commitment)).to.be.true;
+    expect(view.nonce.toNumber()).to.equal(0);
+    expect(view.policy).to.equal(1);
+  });
+
+  it("Requires M-of-N guardian signatures for multisig_update", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    const guardians = [0, 1, 2].map(() => anchor.web3.Keypair.generate());
+    const [guardianSet] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("guardians"), state.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .setGuardians(guardians.map((g) => g.publicKey), 2)
+      .accounts({
+        privateState: state.publicKey,
+        guardianSet,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const asSigners = (keys: anchor.web3.Keypair[]) =>
+      keys.map((g) => ({ pubkey: g.publicKey, isSigner: true, isWritable: false }));
+
+    try {
+      await program.methods
+        .multisigUpdate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+        .accounts({ privateState: state.publicKey, guardianSet })
+        .remainingAccounts(asSigners([guardians[0]]))
+        .signers([guardians[0]])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("GuardianThresholdNotMet");
+    }
+
+    await program.methods
+      .multisigUpdate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+      .accounts({ privateState: state.publicKey, guardianSet })
+      .remainingAccounts(asSigners([guardians[0], guardians[2]]))
+      .signers([guardians[0], guardians[2]])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(1);
+  });
+
+  it("Lets a delegate update but not change policy", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    const bot = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .setDelegate(bot.publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .updateAsDelegate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+      .accounts({ privateState: state.publicKey, updater: bot.publicKey })
+      .signers([bot])
+      .rpc();
+
+    try {
+      await program.methods
+        .setPolicy({ allowSkips: {} })
+        .accounts({ privateState: state.publicKey, authority: bot.publicKey })
+        .signers([bot])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("ConstraintHasOne");
+    }
+
+    await program.methods
+      .revokeDelegate()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .updateAsDelegate(Array.from(c1), Array.from(c0), new anchor.BN(2))
+        .accounts({ privateState: state.publicKey, updater: bot.publicKey })
+        .signers([bot])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NotAuthorizedUpdater");
+    }
+  });
+
+  it("Checks update recency in assert_state_fresh", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .assertStateFresh(Array.from(commitment), new anchor.BN(0), new anchor.BN(1_000_000))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    await new Promise(resolve => setTimeout(resolve, 2000));
+    try {
+      await program.methods
+        .assertStateFresh(Array.from(commitment), new anchor.BN(0), new anchor.BN(0))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateStale");
+    }
+  });
+
+  it("Creates current-version accounts and treats migrate as a no-op", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .migrate()
+      .accounts({
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.version).to.equal(15);
+    expect(Buffer.from(account.commitment).equals(commitment)).to.be.true;
+  });
+
+  it("Migrates a v12 account whose delegate was revoked", async () => {
+    // tests/fixtures/v12-revoked-delegate.json (loaded by Anchor.toml) is a v12
+    // account rewritten after revoke_delegate, with recovery set: the 32 stale
+    // bytes left past its struct sit where created_slot and freeze_reason decode.
+    const fixture = new anchor.web3.PublicKey("4Gn7gihwTagFdmSk2nEHcjNYDgPkCzx38YGGcAkM1yvE");
+    const fixtureAuthority = anchor.web3.Keypair.fromSecretKey(
+      Uint8Array.from(JSON.parse(readFileSync("tests/fixtures/v12-authority.json", "utf8")))
+    );
+    await airdrop(fixtureAuthority.publicKey);
+
+    await program.methods
+      .migrate()
+      .accounts({
+        privateState: fixture,
+        authority: fixtureAuthority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([fixtureAuthority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(fixture);
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    expect(account.version).to.equal(15);
+    expect(Buffer.from(account.commitment).equals(commitment)).to.be.true;
+    expect(account.delegate).to.be.null;
+    expect(account.recovery.toString()).to.equal("5QchJZUvBju5LqskScnmxJoU6XEa6GgYQozZjrrzRnVX");
+    expect(account.inactivitySlots.toNumber()).to.equal(100);
+    expect(account.createdSlot.toNumber()).to.equal(0);
+    expect(account.freezeReason).to.equal(0);
+  });
+
+  it("Records the commitment hash algorithm", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0, undefined, 0, 0, 1);
+
+    const view = await program.methods
+      .getState()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    expect(view.hashAlgo).to.equal(1);
+
+    try {
+      await initState(authority, commitment, 0, undefined, 0, 0, 7);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidHashAlgo");
+    }
+  });
+
+  it("Counts updates independently of nonce jumps", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(100), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const view = await program.methods
+      .getState()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    expect(view.nonce.toNumber()).to.equal(100);
+    expect(view.updateCount.toNumber()).to.equal(1);
+  });
+
+  it("Resets to nonce 0 and bumps the epoch", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const fresh = Buffer.from(createHash("sha256").update("fresh").digest());
+    const state = await initState(authority, c0, 0);
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .reset(Array.from(fresh))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(0);
+    expect(account.epoch.toNumber()).to.equal(1);
+    expect(Buffer.from(account.commitment).equals(fresh)).to.be.true;
+  });
+
+  it("Rejects a stale epoch in assert_state_with_epoch", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .assertStateWithEpoch(Array.from(commitment), new anchor.BN(0), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    // Reset to the same commitment: (commitment, nonce) recurs in epoch 1
+    await program.methods
+      .reset(Array.from(commitment))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .assertStateWithEpoch(Array.from(commitment), new anchor.BN(0), new anchor.BN(0))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("EpochMismatch");
+    }
+  });
+
+  it("Transfers authority to a verified PDA", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const seeds = [Buffer.from("vault"), authority.publicKey.toBuffer()];
+    const [pda, bump] = anchor.web3.PublicKey.findProgramAddressSync(
+      seeds,
+      program.programId
+    );
+
+    await program.methods
+      .transferAuthorityToPda(program.programId, seeds, bump)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(pda.toString());
+  });
+
+  it("Switches policy and updates atomically", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0); // StrictSequential
+
+    // The skip is validated against AllowSkips, not the old policy
+    await program.methods
+      .updateWithPolicy(Array.from(c0), Array.from(c1), new anchor.BN(10), 1)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.policy).to.equal(1);
+    expect(account.nonce.toNumber()).to.equal(10);
+    expect(Buffer.from(account.commitment).equals(c1)).to.be.true;
+  });
+
+  it("Registers and deregisters a consumer link", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const consumerProgram = program.programId;
+    const [link] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("consumer_link"),
+        state.publicKey.toBuffer(),
+        consumerProgram.toBuffer(),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .registerConsumer()
+      .accounts({
+        privateState: state.publicKey,
+        consumerProgram,
+        consumerLink: link,
+        registrar: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.consumerLink.fetch(link);
+    expect(account.privateState.toString()).to.equal(state.publicKey.toString());
+    expect(account.consumerProgram.toString()).to.equal(consumerProgram.toString());
+
+    await program.methods
+      .deregisterConsumer()
+      .accounts({ consumerLink: link, registrar: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    expect(await provider.connection.getAccountInfo(link)).to.be.null;
+  });
+
+  it("Reports mismatches from check_state without failing", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const other = Buffer.from(createHash("sha256").update("other").digest());
+    const state = await initState(authority, commitment, 0);
+
+    const check = (c: Buffer, nonce: number) =>
+      program.methods
+        .checkState(Array.from(c), new anchor.BN(nonce))
+        .accounts({ privateState: state.publicKey })
+        .view();
+
+    expect(await check(commitment, 0)).to.equal(0);
+    expect(await check(commitment, 1)).to.equal(2);
+    expect(await check(other, 1)).to.equal(3);
+  });
+
+  it("Keeps the four most recent commitments", async () => {
+    await airdrop(authority.publicKey);
+
+    const commits = [0, 1, 2, 3, 4].map((i) =>
+      Buffer.from(createHash("sha256").update(`state-${i}`).digest())
+    );
+    const state = await initState(authority, commits[0], 0);
+
+    for (let i = 1; i < commits.length; i++) {
+      await program.methods
+        .update(Array.from(commits[i - 1]), Array.from(commits[i]), new anchor.BN(i), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    }
+
+    const view = await program.methods
+      .getState()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    const recent = view.recent.map((c: number[]) => Buffer.from(c));
+    expect(recent.map((c: Buffer) => c.toString("hex"))).to.deep.equal(
+      [4, 3, 2, 1].map((i) => commits[i].toString("hex"))
+    );
+  });
+
+  it("Rate-limits updates per slot window", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+
+    await program.methods
+      .setRateLimit(1, new anchor.BN(1_000_000))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .update(Array.from(c1), Array.from(c0), new anchor.BN(2), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("RateLimitExceeded");
+    }
+  });
+
+  it("Updates and asserts namespaces of a multi-state account", async () => {
+    await airdrop(authority.publicKey);
+
+    const a0 = Buffer.from(createHash("sha256").update("a0").digest());
+    const a1 = Buffer.from(createHash("sha256").update("a1").digest());
+    const b0 = Buffer.from(createHash("sha256").update("b0").digest());
+    const multi = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .initializeMulti(0, new anchor.BN(0), [
+        { namespace: 1, commitment: Array.from(a0), nonce: new anchor.BN(0) },
+        { namespace: 2, commitment: Array.from(b0), nonce: new anchor.BN(0) },
+      ])
+      .accounts({
+        multiState: multi.publicKey,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority, multi])
+      .rpc();
+
+    await program.methods
+      .updateMulti(1, Array.from(a0), Array.from(a1), new anchor.BN(1))
+      .accounts({ multiState: multi.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .assertStateMulti(1, Array.from(a1), new anchor.BN(1))
+      .accounts({ multiState: multi.publicKey })
+      .rpc();
+    // Namespace 2 is untouched
+    await program.methods
+      .assertStateMulti(2, Array.from(b0), new anchor.BN(0))
+      .accounts({ multiState: multi.publicKey })
+      .rpc();
+
+    try {
+      await program.methods
+        .assertStateMulti(3, Array.from(b0), new anchor.BN(0))
+        .accounts({ multiState: multi.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NamespaceNotFound");
+    }
+  });
+
+  it("Suppresses commitment logs in quiet mode", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .setQuiet(true)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const sig = await program.methods
+      .assertState(Array.from(commitment), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await provider.connection.getTransaction(sig, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const logs = tx.meta.logMessages.join("\n");
+    expect(logs).to.not.include("commitment_prefix");
+  });
+
+  it("Refuses asserts after expiry_slot but still accepts updates", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const slot = await provider.connection.getSlot();
+    const state = await initState(authority, c0, 0, undefined, 0, 0, 0, slot + 1_000_000);
+
+    await program.methods
+      .assertState(Array.from(c0), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    // Any slot already behind us is past expiry
+    await program.methods
+      .setExpiry(new anchor.BN(1))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .assertState(Array.from(c0), new anchor.BN(0))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateExpired");
+    }
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+  });
+
+  it("Binds assert_state_signed to the requester", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const requester = anchor.web3.Keypair.generate();
+    const other = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .assertStateSigned(Array.from(commitment), new anchor.BN(0), requester.publicKey)
+      .accounts({ privateState: state.publicKey, requester: requester.publicKey })
+      .signers([requester])
+      .rpc();
+
+    try {
+      await program.methods
+        .assertStateSigned(Array.from(commitment), new anchor.BN(0), requester.publicKey)
+        .accounts({ privateState: state.publicKey, requester: other.publicKey })
+        .signers([other])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("UnauthorizedRequester");
+    }
+  });
+
+  it("Accepts a raw assert_state instruction from a non-Anchor caller", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    // Same layout as builders::assert_state: discriminator || commitment || nonce
+    const discriminator = createHash("sha256")
+      .update("global:assert_state")
+      .digest()
+      .subarray(0, 8);
+    const nonce = Buffer.alloc(8);
+    nonce.writeBigUInt64LE(0n);
+    const ix = new anchor.web3.TransactionInstruction({
+      programId: program.programId,
+      keys: [{ pubkey: state.publicKey, isSigner: false, isWritable: false }],
+      data: Buffer.concat([discriminator, commitment, nonce]),
+    });
+
+    await provider.sendAndConfirm(new anchor.web3.Transaction().add(ix));
+  });
+
+  it("Rejects nonces in the band reserved below u64::MAX", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const c2 = Buffer.from(createHash("sha256").update("last").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+    const ceiling = new anchor.BN("18446744073709551615").sub(new anchor.BN(1 << 16));
+
+    try {
+      await program.methods
+        .update(Array.from(c0), Array.from(c1), ceiling.addn(1), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceExhaustionRisk");
+    }
+
+    // The ceiling itself is still allowed, but nothing beyond it
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), ceiling, null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .update(Array.from(c1), Array.from(c2), ceiling.addn(1), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceExhaustionRisk");
+    }
+  });
+
+  it("Compares and swaps the authority", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const next = anchor.web3.Keypair.generate();
+
+    try {
+      await program.methods
+        .compareAndSwapAuthority(next.publicKey, next.publicKey)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("AuthorityMismatch");
+    }
+
+    await program.methods
+      .compareAndSwapAuthority(authority.publicKey, next.publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(next.publicKey.toString());
+  });
+
+  it("Locks configuration changes once sealed but keeps updates working", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+
+    await program.methods
+      .seal()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const accounts = { privateState: state.publicKey, authority: authority.publicKey };
+    const [guardianSet] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("guardians"), state.publicKey.toBuffer()],
+      program.programId
+    );
+    const [allowedCallers] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("allowed_callers"), state.publicKey.toBuffer()],
+      program.programId
+    );
+    const systemProgram = anchor.web3.SystemProgram.programId;
+    const guardian = anchor.web3.Keypair.generate().publicKey;
+    const loosening = [
+      program.methods.setPolicy({ allowSkips: {} }).accounts(accounts),
+      program.methods
+        .setGuardians([guardian], 1)
+        .accounts({ ...accounts, guardianSet, systemProgram }),
+      program.methods
+        .setWeightedGuardians([{ key: guardian, weight: 1 }], 1)
+        .accounts({ ...accounts, guardianSet, systemProgram }),
+      program.methods.setCommitmentLen(1).accounts(accounts),
+      program.methods.setAllowReset(false).accounts(accounts),
+      program.methods.setRateLimit(0, new anchor.BN(0)).accounts(accounts),
+      program.methods
+        .setAllowedCallers([guardian])
+        .accounts({ ...accounts, allowedCallers, systemProgram }),
+      program.methods.setCloseSplit(guardian, 10_000).accounts(accounts),
+    ];
+    for (const builder of loosening) {
+      try {
+        await builder.signers([authority]).rpc();
+        expect.fail("Should have thrown error");
+      } catch (error) {
+        expect(error.toString()).to.include("StateSealed");
+      }
+    }
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+  });
+
+  it("Sums guardian weights for multisig_update", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    const guardians = [0, 1, 2].map(() => anchor.web3.Keypair.generate());
+    const [guardianSet] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("guardians"), state.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // Weights 3, 1, 1 with a threshold of 4
+    await program.methods
+      .setWeightedGuardians(
+        guardians.map((g, i) => ({ key: g.publicKey, weight: i === 0 ? 3 : 1 })),
+        4
+      )
+      .accounts({
+        privateState: state.publicKey,
+        guardianSet,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const asSigners = (keys: anchor.web3.Keypair[]) =>
+      keys.map((g) => ({ pubkey: g.publicKey, isSigner: true, isWritable: false }));
+
+    // Just below: 3 < 4
+    try {
+      await program.methods
+        .multisigUpdate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+        .accounts({ privateState: state.publicKey, guardianSet })
+        .remainingAccounts(asSigners([guardians[0]]))
+        .signers([guardians[0]])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("GuardianThresholdNotMet");
+    }
+
+    // Exactly at the threshold: 3 + 1 = 4
+    await program.methods
+      .multisigUpdate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+      .accounts({ privateState: state.publicKey, guardianSet })
+      .remainingAccounts(asSigners([guardians[0], guardians[1]]))
+      .signers([guardians[0], guardians[1]])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(1);
+  });
+
+  it("Peeks at the current nonce", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(7), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const nonce = await program.methods
+      .peekNonce()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    expect(nonce.toNumber()).to.equal(7);
+  });
+
+  it("Charges the update fee to the authority", async () => {
+    const treasury = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+    await airdrop(treasury.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const fee = 5000;
+    const state = await initState(
+      authority,
+      c0,
+      0,
+      undefined,
+      0,
+      0,
+      0,
+      0,
+      fee,
+      treasury.publicKey
+    );
+
+    // Omitting the fee accounts is rejected once a fee is set
+    try {
+      await program.methods
+        .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidFeeDestination");
+    }
+
+    const before = await provider.connection.getBalance(treasury.publicKey);
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+        feeDestination: treasury.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const after = await provider.connection.getBalance(treasury.publicKey);
+    expect(after - before).to.equal(fee);
+
+    // The fee is fixed once sealed
+    await program.methods
+      .seal()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    try {
+      await program.methods
+        .setFee(new anchor.BN(0), treasury.publicKey)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateSealed");
+    }
+  });
+
+  it("Uses the same compute for near and far commitment misses", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+
+    // Simulated compute units for an assert_state that is expected to fail
+    const unitsFor = async (guess: Buffer) => {
+      const tx = await program.methods
+        .assertState(Array.from(guess), new anchor.BN(0))
+        .accounts({ privateState: state.publicKey })
+        .transaction();
+      tx.feePayer = authority.publicKey;
+      tx.recentBlockhash = (await provider.connection.getLatestBlockhash()).blockhash;
+      tx.sign(authority);
+      const sim = await provider.connection.simulateTransaction(tx);
+      expect(JSON.stringify(sim.value.err)).to.include("Custom");
+      return sim.value.unitsConsumed;
+    };
+
+    const nearMiss = Buffer.from(c0);
+    nearMiss[31] ^= 0xff;
+    const farMiss = Buffer.from(c0);
+    farMiss[0] ^= 0xff;
+
+    expect(await unitsFor(nearMiss)).to.equal(await unitsFor(farMiss));
+  });
+
+  it("Carries an update memo in StateUpdated without storing it", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const memo = Buffer.from(createHash("sha256").update("request-42").digest());
+    const state = await initState(authority, c0, 0);
+
+    let event: any = null;
+    const listener = program.addEventListener("stateUpdated", (e) => {
+      event = e;
+    });
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), Array.from(memo))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.removeEventListener(listener);
+
+    expect(event).to.not.be.null;
+    expect(Buffer.from(event.memo).equals(memo)).to.be.true;
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(Buffer.from(account.commitment).equals(c1)).to.be.true;
+  });
+
+  it("Rejects no-op updates only when reject_noop is set", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+
+    // Permitted by default: a heartbeat that only bumps the nonce
+    await program.methods
+      .update(Array.from(c0), Array.from(c0), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .setRejectNoop(true)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .update(Array.from(c0), Array.from(c0), new anchor.BN(2), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NoOpUpdate");
+    }
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(1);
+    expect(account.rejectNoop).to.be.true;
+  });
+
+  it("Lets the recovery key take over only after inactivity", async () => {
+    const recovery = anchor.web3.Keypair.generate();
+    const newAuthority = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(
+      authority,
+      c0,
+      0,
+      undefined,
+      0,
+      0,
+      0,
+      0,
+      0,
+      undefined,
+      recovery.publicKey,
+      1000
+    );
+
+    // Freshly initialized: the account counts as active
+    try {
+      await program.methods
+        .recoverAuthority(newAuthority.publicKey)
+        .accounts({ privateState: state.publicKey, recovery: recovery.publicKey })
+        .signers([recovery])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("AccountStillActive");
+    }
+
+    // Only the designated recovery key may try
+    try {
+      await program.methods
+        .recoverAuthority(newAuthority.publicKey)
+        .accounts({ privateState: state.publicKey, recovery: newAuthority.publicKey })
+        .signers([newAuthority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("UnauthorizedRecovery");
+    }
+
+    // A recovery key needs a non-zero inactivity window
+    try {
+      await initState(
+        authority,
+        c0,
+        0,
+        undefined,
+        0,
+        0,
+        0,
+        0,
+        0,
+        undefined,
+        recovery.publicKey,
+        0
+      );
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidInactivityWindow");
+    }
+
+    // With a short window the recovery key can act once it has passed
+    const idle = await initState(
+      authority,
+      c0,
+      0,
+      undefined,
+      0,
+      0,
+      0,
+      0,
+      0,
+      undefined,
+      recovery.publicKey,
+      2
+    );
+    await new Promise(resolve => setTimeout(resolve, 2000));
+    await program.methods
+      .recoverAuthority(newAuthority.publicKey)
+      .accounts({ privateState: idle.publicKey, recovery: recovery.publicKey })
+      .signers([recovery])
+      .rpc();
+
+    const account = await program.account.privateState.fetch(idle.publicKey);
+    expect(account.authority.toString()).to.equal(newAuthority.publicKey.toString());
+
+    // The new authority can now update
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: idle.publicKey, authority: newAuthority.publicKey })
+      .signers([newAuthority])
+      .rpc();
+  });
+
+  it("Accepts a typed policy at initialize and set_policy", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .initialize(Array.from(c0), { strictSequentialWithCap: {} }, {
+        maxNonce: new anchor.BN(3),
+        minUpdateSlots: new anchor.BN(0),
+        hashAlgo: 0,
+        expirySlot: new anchor.BN(0),
+        feeLamports: new anchor.BN(0),
+        feeDestination: anchor.web3.PublicKey.default,
+        recovery: null,
+        inactivitySlots: new anchor.BN(0),
+      })
+      .accounts({
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([state, authority])
+      .rpc();
+
+    let account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.policy).to.equal(2);
+
+    await program.methods
+      .setPolicy({ allowSkips: {} })
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.policy).to.equal(1);
+
+    // The raw variant still validates the byte on-chain
+    try {
+      await program.methods
+        .setPolicyRaw(7)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidPolicy");
+    }
+  });
+
+  it("Asserts the nonce falls within a window", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(5), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const assertRange = (min: number, max: number) =>
+      program.methods
+        .assertStateRange(Array.from(c1), new anchor.BN(min), new anchor.BN(max))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+    // Both bounds are inclusive
+    await assertRange(5, 5);
+    await assertRange(3, 8);
+
+    for (const [min, max, code] of [
+      [6, 9, "NonceOutOfRange"],
+      [0, 4, "NonceOutOfRange"],
+      [8, 3, "InvalidRange"],
+    ] as [number, number, string][]) {
+      try {
+        await assertRange(min, max);
+        expect.fail("Should have thrown error");
+      } catch (error) {
+        expect(error.toString()).to.include(code);
+      }
+    }
+  });
+
+  it("Records the creation slot and keeps it across updates", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const before = await provider.connection.getSlot();
+    const state = await initState(authority, c0, 0);
+
+    const created = (await program.account.privateState.fetch(state.publicKey)).createdSlot;
+    expect(created.toNumber()).to.be.at.least(before);
+
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const view = await program.methods
+      .getState()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    expect(view.createdSlot.toNumber()).to.equal(created.toNumber());
+  });
+
+  it("Rejects frozen accounts in assert_state_strict only", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+
+    await program.methods
+      .assertStateStrict(Array.from(c0), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    await program.methods
+      .freeze(0)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    // The permissive assert keeps passing
+    await program.methods
+      .assertState(Array.from(c0), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    // Frozen is reported before the (also wrong) nonce
+    try {
+      await program.methods
+        .assertStateStrict(Array.from(c0), new anchor.BN(1))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateFrozen");
+    }
+  });
+
+  it("Merges concurrent device clocks and rejects stale ones", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("laptop").digest());
+    const c2 = Buffer.from(createHash("sha256").update("phone").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+    const [causalClock] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("causal_clock"), state.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const clock = (entries: [number, number][]) =>
+      entries.map(([device, counter]) => ({ device, counter: new anchor.BN(counter) }));
+    const updateCausal = (old: Buffer, next: Buffer, nonce: number, c: any[]) =>
+      program.methods
+        .updateCausal(Array.from(old), Array.from(next), new anchor.BN(nonce), c)
+        .accounts({
+          privateState: state.publicKey,
+          causalClock,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+    // Laptop (device 1) edits first
+    await updateCausal(c0, c1, 1, clock([[1, 1]]));
+
+    // Phone (device 2) edited concurrently, without seeing the laptop's edit
+    await updateCausal(c1, c2, 2, clock([[2, 1]]));
+
+    // Replaying the laptop's clock is stale
+    try {
+      await updateCausal(c2, c1, 3, clock([[1, 1]]));
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CausalConflict");
+    }
+
+    const stored = await program.account.causalClock.fetch(causalClock);
+    expect(stored.deviceClocks.map((c) => [c.device, c.counter.toNumber()])).to.deep.equal([
+      [1, 1],
+      [2, 1],
+    ]);
+  });
+
+  it("Rejects assert_state_restricted outside an allowed CPI", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const [allowedCallers] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("allowed_callers"), state.publicKey.toBuffer()],
+      program.programId
+    );
+    const licensed = anchor.web3.Keypair.generate().publicKey;
+
+    await program.methods
+      .setAllowedCallers([licensed])
+      .accounts({
+        privateState: state.publicKey,
+        allowedCallers,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const list = await program.account.callerAllowlist.fetch(allowedCallers);
+    expect(list.callers.map((c) => c.toString())).to.deep.equal([licensed.toString()]);
+
+    // A top-level call has no calling program, so it is never allowed
+    try {
+      await program.methods
+        .assertStateRestricted(Array.from(c0), new anchor.BN(0))
+        .accounts({
+          privateState: state.publicKey,
+          allowedCallers,
+          instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CallerNotAllowed");
+    }
+  });
+
+  it("Returns a snapshot matching the account", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 2, undefined, 10, 5, 1, 0);
+
+    const snapshot = await program.methods
+      .snapshot()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    const account = await program.account.privateState.fetch(state.publicKey);
+
+    expect(snapshot.version).to.equal(account.version);
+    expect(snapshot.authority.equals(account.authority)).to.be.true;
+    expect(Buffer.from(snapshot.commitment).equals(Buffer.from(account.commitment))).to.be.true;
+    expect(snapshot.nonce.eq(account.nonce)).to.be.true;
+    expect(snapshot.policy).to.equal(account.policy);
+    expect(snapshot.maxNonce.eq(account.maxNonce)).to.be.true;
+    expect(snapshot.minUpdateSlots.eq(account.minUpdateSlots)).to.be.true;
+    expect(snapshot.hashAlgo).to.equal(account.hashAlgo);
+    expect(snapshot.createdSlot.eq(account.createdSlot)).to.be.true;
+    expect(Buffer.from(snapshot.recent[0]).equals(commitment)).to.be.true;
+  });
+
+  it("Emits StateHealthWarning when an update nears the nonce cap", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 2, undefined, 1); // cap at nonce 1
+
+    let event: any = null;
+    const listener = program.addEventListener("stateHealthWarning", (e) => {
+      event = e;
+    });
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.removeEventListener(listener);
+
+    expect(event).to.not.be.null;
+    expect(event.account.toString()).to.equal(state.publicKey.toString());
+    expect(event.conditions & 1).to.equal(1); // HEALTH_NEAR_NONCE_CAP
+    expect(event.nonce.toNumber()).to.equal(1);
+    expect(event.maxNonce.toNumber()).to.equal(1);
+  });
+
+  it("Verifies a payload against the stored commitment", async () => {
+    await airdrop(authority.publicKey);
+
+    const payload = Buffer.from("encrypted payload");
+    const nonce = Buffer.alloc(8);
+    const commitment = Buffer.from(
+      createHash("sha256").update(Buffer.concat([nonce, payload])).digest()
+    );
+    const state = await initState(authority, commitment, 0);
+
+    await program.methods
+      .verifyPayload(payload, new anchor.BN(0))
+      .accounts({ privateState: state.publicKey })
+      .rpc();
+
+    try {
+      await program.methods
+        .verifyPayload(Buffer.from("tampered payload"), new anchor.BN(0))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CommitmentMismatch");
+    }
+  });
+
+  it("Releases authority from a program's PDA back to a wallet", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+    const consumer = anchor.web3.Keypair.generate();
+
+    await consumerProgram.methods
+      .initializeConsumer(state.publicKey, new anchor.BN(0), new anchor.BN(0), new anchor.BN(0))
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+      })
+      .signers([consumer, authority])
+      .rpc();
+
+    // Hand the PST account to the consumer's custody PDA
+    const seeds = [Buffer.from("custody"), consumer.publicKey.toBuffer()];
+    const [custody, bump] = anchor.web3.PublicKey.findProgramAddressSync(
+      seeds,
+      consumerProgram.programId
+    );
+    await program.methods
+      .transferAuthorityToPda(consumerProgram.programId, seeds, bump)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    let account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(custody.toString());
+
+    // Only the owning program can sign for the PDA to release it
+    await consumerProgram.methods
+      .releaseCustody(authority.publicKey)
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state.publicKey,
+        custody,
+        pstProgram: program.programId,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+    account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.authority.toString()).to.equal(authority.publicKey.toString());
+  });
+
+  it("Rejects update_checked when the current nonce differs", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+
+    try {
+      await program.methods
+        .updateChecked(Array.from(c0), Array.from(c1), new anchor.BN(5), new anchor.BN(3))
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CurrentNonceMismatch");
+    }
+
+    await program.methods
+      .updateChecked(Array.from(c0), Array.from(c1), new anchor.BN(5), new anchor.BN(0))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(5);
+  });
+
+  it("Gates on a nonce comparison chosen at call time", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    // nonce 0 >= 0 and 0 < 1
+    for (const [bound, op] of [[0, 1], [1, 4]]) {
+      await program.methods
+        .assertStateCmp(Array.from(commitment), new anchor.BN(bound), op)
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+    }
+
+    try {
+      await program.methods
+        .assertStateCmp(Array.from(commitment), new anchor.BN(0), 2) // gt
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceComparisonFailed");
+    }
+
+    try {
+      await program.methods
+        .assertStateCmp(Array.from(commitment), new anchor.BN(0), 9)
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidComparisonOp");
+    }
+  });
+
+  it("Reports the account size", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    const size = await program.methods
+      .stateSize()
+      .accounts({ privateState: state.publicKey })
+      .view();
+    const info = await provider.connection.getAccountInfo(state.publicKey);
+    expect(size.toNumber()).to.equal(info.data.length);
+  });
+
+  it("Initializes at a non-zero nonce", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = anchor.web3.Keypair.generate();
+    const options = {
+      maxNonce: new anchor.BN(0),
+      minUpdateSlots: new anchor.BN(0),
+      hashAlgo: 0,
+      expirySlot: new anchor.BN(0),
+      feeLamports: new anchor.BN(0),
+      feeDestination: anchor.web3.PublicKey.default,
+      recovery: null,
+      inactivitySlots: new anchor.BN(0),
+    };
+
+    await program.methods
+      .initializeAt(Array.from(c0), { strictSequential: {} }, new anchor.BN(500), options)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([state, authority])
+      .rpc();
+    let account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(500);
+
+    // StrictSequential still applies from the imported nonce
+    try {
+      await program.methods
+        .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceNotSequential");
+    }
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(501), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(501);
+  });
+
+  it("Gates a consumer action on progress since a checkpoint", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = anchor.web3.Keypair.generate();
+
+    await consumerProgram.methods
+      .initializeConsumer(state.publicKey, new anchor.BN(0), new anchor.BN(0), new anchor.BN(0))
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+      })
+      .signers([consumer, authority])
+      .rpc();
+    await consumerProgram.methods
+      .setCheckpoint()
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+
+    const gate = (commitment: Buffer, nonce: number) =>
+      consumerProgram.methods
+        .gatedOnProgress(Array.from(commitment), new anchor.BN(nonce))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState: state.publicKey,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+    try {
+      await gate(c0, 0);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NoProgress");
+    }
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await gate(c1, 1);
+
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.count.toNumber()).to.equal(1);
+  });
+
+  it("Gates on a commitment set with an optional nonce", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    const anyNonce = new anchor.BN("18446744073709551615"); // ANY_NONCE
+
+    for (const nonce of [new anchor.BN(0), anyNonce]) {
+      await program.methods
+        .assertStateInSet([Array.from(c1), Array.from(c0)], nonce)
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+    }
+
+    try {
+      await program.methods
+        .assertStateInSet([Array.from(c1)], anyNonce)
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CommitmentNotInSet");
+    }
+  });
+
+  it("Touches the state without changing the commitment", async () => {
+    await airdrop(authority.publicKey);
+
+    const commitment = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, commitment, 0);
+
+    let event: any = null;
+    const listener = program.addEventListener("stateTouched", (e) => {
+      event = e;
+    });
+
+    await program.methods
+      .touch()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.removeEventListener(listener);
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(1);
+    expect(Buffer.from(account.commitment).equals(commitment)).to.be.true;
+    expect(event).to.not.be.null;
+    expect(event.nonce.toNumber()).to.equal(1);
+    expect(event.slot.eq(account.lastUpdateSlot)).to.be.true;
+  });
+
+  it("Proves inclusion and rejects exclusion in a Merkle registry", async () => {
+    await airdrop(authority.publicKey);
+
+    const registry = anchor.web3.Keypair.generate();
+    await program.methods
+      .initializeRegistry()
+      .accounts({ registry: registry.publicKey, authority: authority.publicKey })
+      .signers([registry, authority])
+      .rpc();
+
+    const a = Buffer.alloc(32, 1);
+    const b = Buffer.alloc(32, 2);
+    for (const leaf of [a, b]) {
+      await program.methods
+        .registerLeaf(Array.from(leaf))
+        .accounts({ registry: registry.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    }
+
+    // a's siblings: b, then the roots of empty subtrees (MERKLE_DEPTH = 16)
+    const parent = (l: Buffer, r: Buffer) =>
+      Buffer.from(createHash("sha256").update(Buffer.concat([l, r])).digest());
+    const zeros = [Buffer.alloc(32)];
+    for (let i = 1; i < 16; i++) zeros.push(parent(zeros[i - 1], zeros[i - 1]));
+    const proof = [b, ...zeros.slice(1)];
+    const root = proof.reduce((node, sibling) => parent(node, sibling), a);
+
+    const account = await program.account.merkleRegistry.fetch(registry.publicKey);
+    expect(account.count.toNumber()).to.equal(2);
+    expect(Buffer.from(account.root).equals(root)).to.be.true;
+
+    const assertMembership = (leaf: Buffer, siblings: Buffer[], index: number) =>
+      program.methods
+        .assertMembership(
+          Array.from(leaf),
+          siblings.map((p) => Array.from(p)),
+          new anchor.BN(index)
+        )
+        .accounts({ registry: registry.publicKey })
+        .rpc();
+
+    await assertMembership(a, proof, 0);
+
+    for (const [leaf, index] of [[Buffer.alloc(32, 3), 0], [a, 5]] as [Buffer, number][]) {
+      try {
+        await assertMembership(leaf, proof, index);
+        expect.fail("Should have thrown error");
+      } catch (error) {
+        expect(error.toString()).to.include("LeafNotInRegistry");
+      }
+    }
+
+    try {
+      await assertMembership(a, proof.slice(1), 0);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidProofLength");
+    }
+  });
+
+  it("Binds update_bound to the exact transition", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+
+    // sha256(private_state || new_commitment || next_nonce LE)
+    const authTag = (nonce: bigint) => {
+      const nonceBuf = Buffer.alloc(8);
+      nonceBuf.writeBigUInt64LE(nonce);
+      return Buffer.from(
+        createHash("sha256")
+          .update(Buffer.concat([state.publicKey.toBuffer(), c1, nonceBuf]))
+          .digest()
+      );
+    };
+
+    try {
+      await program.methods
+        .updateBound(Array.from(c0), Array.from(c1), new anchor.BN(1), Array.from(authTag(2n)))
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("AuthTagMismatch");
+    }
+
+    await program.methods
+      .updateBound(Array.from(c0), Array.from(c1), new anchor.BN(1), Array.from(authTag(1n)))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(Buffer.from(account.commitment).equals(c1)).to.be.true;
+  });
+
+  it("Charges the update fee on the delegate and group paths", async () => {
+    const treasury = anchor.web3.Keypair.generate();
+    const bot = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+    await airdrop(treasury.publicKey);
+    await airdrop(bot.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const fee = 5000;
+    const state = await initState(
+      authority,
+      c0,
+      0,
+      undefined,
+      0,
+      0,
+      0,
+      0,
+      fee,
+      treasury.publicKey
+    );
+    await program.methods
+      .setDelegate(bot.publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    // Neither the delegate nor the authority can skip the fee via update_as_delegate
+    for (const updater of [bot, authority]) {
+      try {
+        await program.methods
+          .updateAsDelegate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+          .accounts({ privateState: state.publicKey, updater: updater.publicKey })
+          .signers([updater])
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (error) {
+        expect(error.toString()).to.include("InvalidFeeDestination");
+      }
+    }
+
+    let before = await provider.connection.getBalance(treasury.publicKey);
+    await program.methods
+      .updateAsDelegate(Array.from(c0), Array.from(c1), new anchor.BN(1))
+      .accounts({
+        privateState: state.publicKey,
+        updater: bot.publicKey,
+        feeDestination: treasury.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bot])
+      .rpc();
+    expect((await provider.connection.getBalance(treasury.publicKey)) - before).to.equal(fee);
+
+    const group = (accounts: any) =>
+      program.methods
+        .bumpGroup()
+        .accounts(accounts)
+        .remainingAccounts([{ pubkey: state.publicKey, isSigner: false, isWritable: true }])
+        .signers([authority])
+        .rpc();
+    try {
+      await group({ authority: authority.publicKey });
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidFeeDestination");
+    }
+
+    before = await provider.connection.getBalance(treasury.publicKey);
+    await group({
+      authority: authority.publicKey,
+      feeDestination: treasury.publicKey,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    });
+    expect((await provider.connection.getBalance(treasury.publicKey)) - before).to.equal(fee);
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(2);
+  });
+
+  it("Drops the recovery key when authority changes hands", async () => {
+    const recovery = anchor.web3.Keypair.generate();
+    const buyer = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(
+      authority,
+      c0,
+      0,
+      undefined,
+      0,
+      0,
+      0,
+      0,
+      0,
+      undefined,
+      recovery.publicKey,
+      2
+    );
+
+    await program.methods
+      .transferAuthority(buyer.publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    let account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.recovery).to.be.null;
+
+    // The seller's recovery key can't reclaim the idle account
+    await new Promise(resolve => setTimeout(resolve, 2000));
+    try {
+      await program.methods
+        .recoverAuthority(authority.publicKey)
+        .accounts({ privateState: state.publicKey, recovery: recovery.publicKey })
+        .signers([recovery])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("UnauthorizedRecovery");
+    }
+
+    // The buyer designates its own
+    await program.methods
+      .setRecovery(buyer.publicKey, new anchor.BN(1000))
+      .accounts({ privateState: state.publicKey, authority: buyer.publicKey })
+      .signers([buyer])
+      .rpc();
+    account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.recovery.toString()).to.equal(buyer.publicKey.toString());
+    expect(account.inactivitySlots.toNumber()).to.equal(1000);
+  });
+
+  it("Applies the consumer cooldown to every gated action", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = anchor.web3.Keypair.generate();
+
+    // A long cooldown: only the first action can get through
+    await consumerProgram.methods
+      .initializeConsumer(state.publicKey, new anchor.BN(0), new anchor.BN(1000), new anchor.BN(0))
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state.publicKey,
+        authority: authority.publicKey,
+      })
+      .signers([consumer, authority])
+      .rpc();
+
+    const accounts = {
+      consumer: consumer.publicKey,
+      privateState: state.publicKey,
+      pstProgram: program.programId,
+      authority: authority.publicKey,
+    };
+    const expectations = [{ commitment: Array.from(c0), nonce: new anchor.BN(0) }];
+    const remaining = [{ pubkey: state.publicKey, isSigner: false, isWritable: false }];
+
+    await consumerProgram.methods
+      .gatedAction(Array.from(c0), new anchor.BN(0))
+      .accounts(accounts)
+      .signers([authority])
+      .rpc();
+
+    const attempts = [
+      () =>
+        consumerProgram.methods
+          .gatedAction(Array.from(c0), new anchor.BN(0))
+          .accounts(accounts)
+          .signers([authority])
+          .rpc(),
+      () =>
+        consumerProgram.methods
+          .gatedActionMulti(expectations)
+          .accounts({
+            consumer: consumer.publicKey,
+            pstProgram: program.programId,
+            authority: authority.publicKey,
+          })
+          .remainingAccounts(remaining)
+          .signers([authority])
+          .rpc(),
+      () =>
+        consumerProgram.methods
+          .gatedActionAny(expectations)
+          .accounts({ consumer: consumer.publicKey, authority: authority.publicKey })
+          .remainingAccounts(remaining)
+          .signers([authority])
+          .rpc(),
+    ];
+    for (const attempt of attempts) {
+      try {
+        await attempt();
+        expect.fail("Should have thrown error");
+      } catch (error) {
+        expect(error.toString()).to.include("ActionCooldown");
+      }
+    }
+
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.count.toNumber()).to.equal(1);
+  });
+
+  it("Measures consumer progress from the nonce it was linked at", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 1); // AllowSkips
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(5), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const consumer = await initConsumer(authority, state.publicKey);
+    let account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.baselineNonce.toNumber()).to.equal(5);
+
+    await program.methods
+      .update(Array.from(c1), Array.from(c0), new anchor.BN(8), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    const progress = await consumerProgram.methods
+      .effectiveNonce()
+      .accounts({ consumer: consumer.publicKey, privateState: state.publicKey })
+      .view();
+    expect(progress.toNumber()).to.equal(3);
+
+    // A different PST account is not this consumer's link
+    const other = await initState(authority, c0, 0);
+    try {
+      await consumerProgram.methods
+        .effectiveNonce()
+        .accounts({ consumer: consumer.publicKey, privateState: other.publicKey })
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidPrivateState");
+    }
+  });
+
+  it("Gates a consumer action on its count range", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = await initConsumer(authority, state.publicKey);
+
+    const ranged = (min: number, max: number) =>
+      consumerProgram.methods
+        .gatedActionRanged(Array.from(c0), new anchor.BN(0), new anchor.BN(min), new anchor.BN(max))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState: state.publicKey,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+    for (const [min, max, error] of [
+      [1, 5, "CountOutOfRange"],
+      [3, 2, "InvalidRange"],
+    ] as [number, number, string][]) {
+      try {
+        await ranged(min, max);
+        expect.fail("Should have thrown error");
+      } catch (e) {
+        expect(e.toString()).to.include(error);
+      }
+    }
+
+    // The bounds apply to the count before the increment
+    await ranged(0, 0);
+    await ranged(1, 1);
+    try {
+      await ranged(0, 1);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("CountOutOfRange");
+    }
+
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.count.toNumber()).to.equal(2);
+  });
+
+  it("Gates a consumer action on several PST states at once", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const first = await initState(authority, c0, 0);
+    const second = await initState(authority, c1, 0);
+    const consumer = await initConsumer(authority, first.publicKey);
+
+    const multi = (expectations: any[], states: anchor.web3.PublicKey[]) =>
+      consumerProgram.methods
+        .gatedActionMulti(expectations)
+        .accounts({
+          consumer: consumer.publicKey,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .remainingAccounts(
+          states.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }))
+        )
+        .signers([authority])
+        .rpc();
+    const expect0 = { commitment: Array.from(c0), nonce: new anchor.BN(0) };
+    const expect1 = { commitment: Array.from(c1), nonce: new anchor.BN(0) };
+
+    for (const [expectations, states, error] of [
+      [[expect0, expect1], [first.publicKey], "MismatchedInputLengths"],
+      [[], [], "NoExpectations"],
+      // Every state must match (AND)
+      [[expect0, expect0], [first.publicKey, second.publicKey], "CommitmentMismatch"],
+    ] as [any[], anchor.web3.PublicKey[], string][]) {
+      try {
+        await multi(expectations, states);
+        expect.fail("Should have thrown error");
+      } catch (e) {
+        expect(e.toString()).to.include(error);
+      }
+    }
+
+    await multi([expect0, expect1], [first.publicKey, second.publicKey]);
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.count.toNumber()).to.equal(1);
+  });
+
+  it("Gates a consumer action on any one of several PST states", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const first = await initState(authority, c0, 0);
+    const second = await initState(authority, c1, 0);
+    const consumer = await initConsumer(authority, first.publicKey);
+
+    const any = (expectations: any[], states: anchor.web3.PublicKey[]) =>
+      consumerProgram.methods
+        .gatedActionAny(expectations)
+        .accounts({ consumer: consumer.publicKey, authority: authority.publicKey })
+        .remainingAccounts(
+          states.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }))
+        )
+        .signers([authority])
+        .rpc();
+    const wrong = { commitment: Array.from(c1), nonce: new anchor.BN(0) };
+    const right = { commitment: Array.from(c1), nonce: new anchor.BN(0) };
+    const stale = { commitment: Array.from(c0), nonce: new anchor.BN(3) };
+
+    for (const [expectations, states, error] of [
+      [[wrong], [], "MismatchedInputLengths"],
+      [[wrong, stale], [first.publicKey, second.publicKey], "NoValidState"],
+      // Accounts that aren't PST states simply don't count
+      [[right], [authority.publicKey], "NoValidState"],
+    ] as [any[], anchor.web3.PublicKey[], string][]) {
+      try {
+        await any(expectations, states);
+        expect.fail("Should have thrown error");
+      } catch (e) {
+        expect(e.toString()).to.include(error);
+      }
+    }
+
+    // One valid state is enough (OR)
+    await any([wrong, right], [first.publicKey, second.publicKey]);
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.count.toNumber()).to.equal(1);
+  });
+
+  it("Rejects a consumer action at a nonce below one already accepted", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const fresh = Buffer.from(createHash("sha256").update("fresh").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = await initConsumer(authority, state.publicKey);
+
+    const gate = (commitment: Buffer, nonce: number) =>
+      consumerProgram.methods
+        .gatedAction(Array.from(commitment), new anchor.BN(nonce))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState: state.publicKey,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await gate(c1, 1);
+
+    // After a PST reset the state is valid again at nonce 0, but the consumer
+    // has already seen nonce 1
+    await program.methods
+      .reset(Array.from(fresh))
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    try {
+      await gate(fresh, 0);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NonceRegression");
+    }
+
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.lastSeenNonce.toNumber()).to.equal(1);
+    expect(account.count.toNumber()).to.equal(1);
+  });
+
+  it("Meters consumer credit with gated_action_delta", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = await initConsumer(authority, state.publicKey, 5);
+
+    const spend = (delta: number) =>
+      consumerProgram.methods
+        .gatedActionDelta(Array.from(c0), new anchor.BN(0), new anchor.BN(delta))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState: state.publicKey,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+    try {
+      await spend(-6);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InsufficientBalance");
+    }
+
+    await spend(-5);
+    await spend(3);
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.balance.toNumber()).to.equal(3);
+    expect(account.count.toNumber()).to.equal(2);
+  });
+
+  it("Rejects consumer actions on accounts that aren't the linked PST state", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const other = await initState(authority, c0, 0);
+    const consumer = await initConsumer(authority, state.publicKey);
+
+    const gate = (privateState: anchor.web3.PublicKey) =>
+      consumerProgram.methods
+        .gatedAction(Array.from(c0), new anchor.BN(0))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+
+    // A valid PST account, but not the linked one
+    try {
+      await gate(other.publicKey);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidPrivateState");
+    }
+
+    // Once closed, the linked address is no longer owned by PST
+    await program.methods
+      .close()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    try {
+      await gate(state.publicKey);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidPrivateState");
+    }
+  });
+
+  it("Pins consumer actions to the PST program the link was made for", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = await initConsumer(authority, state.publicKey);
+
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.pstProgram.toString()).to.equal(program.programId.toString());
+
+    // Routing the CPI through any other program is refused before it is made
+    try {
+      await consumerProgram.methods
+        .gatedAction(Array.from(c0), new anchor.BN(0))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState: state.publicKey,
+          pstProgram: consumerProgram.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.match(/InvalidProgramId|InvalidPstProgram/);
+    }
+  });
+
+  it("Enforces the consumer quota until its authority resets the counter", async () => {
+    const stranger = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const consumer = await initConsumer(authority, state.publicKey, 0, 0, 2);
+
+    const gate = () =>
+      consumerProgram.methods
+        .gatedActionDelta(Array.from(c0), new anchor.BN(0), new anchor.BN(0))
+        .accounts({
+          consumer: consumer.publicKey,
+          privateState: state.publicKey,
+          pstProgram: program.programId,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+    const reset = (signer: anchor.web3.Keypair) =>
+      consumerProgram.methods
+        .resetCounter()
+        .accounts({ consumer: consumer.publicKey, authority: signer.publicKey })
+        .signers([signer])
+        .rpc();
+
+    await gate();
+    await consumerProgram.methods
+      .gatedAction(Array.from(c0), new anchor.BN(0))
+      .accounts({
+        consumer: consumer.publicKey,
+        privateState: state.publicKey,
+        pstProgram: program.programId,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+    try {
+      await gate();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("MaxCountReached");
+    }
+
+    // Only the consumer's authority may restore the quota
+    try {
+      await reset(stranger);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("Unauthorized");
+    }
+
+    await reset(authority);
+    await gate();
+    const account = await consumerProgram.account.consumerAccount.fetch(consumer.publicKey);
+    expect(account.count.toNumber()).to.equal(1);
+  });
+
+  it("Requires a quiet period in assert_quiescent", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    await new Promise(resolve => setTimeout(resolve, 2000));
+
+    const quiescent = (minQuietSlots: number) =>
+      program.methods
+        .assertQuiescent(new anchor.BN(minQuietSlots))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+    // Idle since initialization
+    await quiescent(2);
+
+    // Any update restarts the quiet period
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    try {
+      await quiescent(1000);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateNotQuiescent");
+    }
+  });
+
+  it("Counts fresh accounts in assert_k_of_n_fresh", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const stale = await initState(authority, c0, 0);
+    const fresh = await initState(authority, c0, 0);
+    await new Promise(resolve => setTimeout(resolve, 3000));
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: fresh.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    const kOfN = (k: number) =>
+      program.methods
+        .assertKOfNFresh(new anchor.BN(4), k)
+        .accounts({ privateState: stale.publicKey })
+        .remainingAccounts([{ pubkey: fresh.publicKey, isSigner: false, isWritable: false }]);
+
+    expect(await kOfN(1).view()).to.equal(1);
+    try {
+      await kOfN(2).rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("FreshnessThresholdNotMet");
+    }
+
+    // An account can't be counted twice
+    try {
+      await program.methods
+        .assertKOfNFresh(new anchor.BN(4), 2)
+        .accounts({ privateState: fresh.publicKey })
+        .remainingAccounts([{ pubkey: fresh.publicKey, isSigner: false, isWritable: false }])
+        .rpc();
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("DuplicateAccount");
+    }
+  });
+
+  it("Reads the recent-commitment ring oldest first", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const c2 = Buffer.from(createHash("sha256").update("last").digest());
+    const state = await initState(authority, c0, 0);
+    for (const [old, next, nonce] of [[c0, c1, 1], [c1, c2, 2]] as [Buffer, Buffer, number][]) {
+      await program.methods
+        .update(Array.from(old), Array.from(next), new anchor.BN(nonce), null)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    }
+
+    const history = Buffer.from(
+      await program.methods.readHistory().accounts({ privateState: state.publicKey }).view()
+    );
+    expect(history.length).to.equal(1 + 32 * 4);
+    expect(history[0]).to.equal(2); // recent_head
+    const entries = [0, 1, 2, 3].map((i) => history.subarray(1 + 32 * i, 33 + 32 * i));
+    expect(entries[0].equals(Buffer.alloc(32))).to.be.true;
+    expect(entries[1].equals(c0)).to.be.true;
+    expect(entries[2].equals(c1)).to.be.true;
+    expect(entries[3].equals(c2)).to.be.true;
+  });
+
+  it("Splits reclaimed lamports with the configured treasury on close_state_split", async () => {
+    const treasury = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+    await airdrop(treasury.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const setCloseSplit = (to: anchor.web3.PublicKey, bps: number) =>
+      program.methods
+        .setCloseSplit(to, bps)
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+    const closeSplit = (to: anchor.web3.PublicKey) =>
+      program.methods
+        .closeStateSplit()
+        .accounts({
+          privateState: state.publicKey,
+          authority: authority.publicKey,
+          treasury: to,
+        })
+        .signers([authority])
+        .rpc();
+
+    try {
+      await setCloseSplit(treasury.publicKey, 10_001);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidSplit");
+    }
+    await setCloseSplit(treasury.publicKey, 2_500);
+
+    try {
+      await closeSplit(authority.publicKey);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidTreasury");
+    }
+
+    const lamports = await provider.connection.getBalance(state.publicKey);
+    const before = await provider.connection.getBalance(treasury.publicKey);
+    await closeSplit(treasury.publicKey);
+
+    expect(await provider.connection.getAccountInfo(state.publicKey)).to.be.null;
+    const after = await provider.connection.getBalance(treasury.publicKey);
+    expect(after - before).to.equal(Math.floor((lamports * 2_500) / 10_000));
+  });
+
+  it("Reports frozen and sealed flags through status", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const status = () =>
+      program.methods.status().accounts({ privateState: state.publicKey }).view();
+
+    expect(await status()).to.equal(0);
+
+    await program.methods
+      .freeze(0)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    expect(await status()).to.equal(1);
+
+    await program.methods
+      .seal()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    expect(await status()).to.equal(1 | 2);
+  });
+
+  it("Records the freeze reason and clears it on unfreeze", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+
+    let event: any = null;
+    const listener = program.addEventListener("stateFrozen", (e) => {
+      event = e;
+    });
+
+    await program.methods
+      .freeze(3)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.removeEventListener(listener);
+
+    expect(event).to.not.be.null;
+    expect(event.account.toString()).to.equal(state.publicKey.toString());
+    expect(event.reason).to.equal(3);
+    let account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.freezeReason).to.equal(3);
+
+    await program.methods
+      .unfreeze()
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.freezeReason).to.equal(0);
+  });
+
+  it("Blocks set_guardians while frozen", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const [guardianSet] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("guardians"), state.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .freeze(0)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .setGuardians([anchor.web3.Keypair.generate().publicKey], 1)
+        .accounts({
+          privateState: state.publicKey,
+          guardianSet,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("StateFrozen");
+    }
+  });
+
+  it("Verifies a rotation step with assert_rotation", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const state = await initState(authority, c0, 0);
+    const assertRotation = (old: Buffer, next: Buffer) =>
+      program.methods
+        .assertRotation(Array.from(old), Array.from(next))
+        .accounts({ privateState: state.publicKey })
+        .rpc();
+
+    // No rotation has happened yet
+    try {
+      await assertRotation(Buffer.alloc(32), c0);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("RotationOldMismatch");
+    }
+
+    await program.methods
+      .update(Array.from(c0), Array.from(c1), new anchor.BN(1), null)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await assertRotation(c0, c1);
+
+    try {
+      await assertRotation(c1, c1);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("RotationOldMismatch");
+    }
+
+    try {
+      await assertRotation(c0, c0);
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("RotationNewMismatch");
+    }
+  });
+
+  it("Rejects no-op commitments on the delegate and batch paths too", async () => {
+    const authority = anchor.web3.Keypair.generate();
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const state = await initState(authority, c0, 0);
+    const bot = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .setDelegate(bot.publicKey)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .setRejectNoop(true)
+      .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .updateAsDelegate(Array.from(c0), Array.from(c0), new anchor.BN(1))
+        .accounts({ privateState: state.publicKey, updater: bot.publicKey })
+        .signers([bot])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NoOpUpdate");
+    }
+
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+
+    try {
+      await program.methods
+        .batchUpdate(Array.from(c0), [
+          { newCommitment: Array.from(c1), nonce: new anchor.BN(1) },
+          { newCommitment: Array.from(c1), nonce: new anchor.BN(2) },
+        ])
+        .accounts({ privateState: state.publicKey, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+      expect.fail("Should have thrown error");
+    } catch (error) {
+      expect(error.toString()).to.include("NoOpUpdate");
+    }
+
+    const account = await program.account.privateState.fetch(state.publicKey);
+    expect(account.nonce.toNumber()).to.equal(0);
+  });
+
+  it("Emits StateHealthWarning from touch and batch_update as well", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const touched = await initState(authority, c0, 2, undefined, 1); // cap at nonce 1
+    const batched = await initState(authority, c0, 2, undefined, 1);
+
+    const events: any[] = [];
+    const listener = program.addEventListener("stateHealthWarning", (e) => {
+      events.push(e);
+    });
+
+    await program.methods
+      .touch()
+      .accounts({ privateState: touched.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .batchUpdate(Array.from(c0), [
+        {
+          newCommitment: Array.from(createHash("sha256").update("next").digest()),
+          nonce: new anchor.BN(1),
+        },
+      ])
+      .accounts({ privateState: batched.publicKey, authority: authority.publicKey })
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await program.removeEventListener(listener);
+
+    const accounts = events.map((e) => e.account.toString());
+    expect(accounts).to.include(touched.publicKey.toString());
+    expect(accounts).to.include(batched.publicKey.toString());
+    events.forEach((e) => expect(e.conditions & 1).to.equal(1)); // HEALTH_NEAR_NONCE_CAP
+  });
+
+  it("Emits GatedActionPerformed from the multi-state gated actions", async () => {
+    await airdrop(authority.publicKey);
+
+    const c0 = Buffer.from(createHash("sha256").update("test").digest());
+    const c1 = Buffer.from(createHash("sha256").update("next").digest());
+    const first = await initState(authority, c0, 0);
+    const second = await initState(authority, c1, 0);
+    const consumer = await initConsumer(authority, first.publicKey);
+    const remaining = [first.publicKey, second.publicKey].map((pubkey) => ({
+      pubkey,
+      isSigner: false,
+      isWritable: false,
+    }));
+    const expect0 = { commitment: Array.from(c0), nonce: new anchor.BN(0) };
+    const expect1 = { commitment: Array.from(c1), nonce: new anchor.BN(0) };
+
+    const events: any[] = [];
+    const listener = consumerProgram.addEventListener("gatedActionPerformed", (e) => {
+      events.push(e);
+    });
+
+    await consumerProgram.methods
+      .gatedActionMulti([expect0, expect1])
+      .accounts({
+        consumer: consumer.publicKey,
+        pstProgram: program.programId,
+        authority: authority.publicKey,
+      })
+      .remainingAccounts(remaining)
+      .signers([authority])
+      .rpc();
+    // Only the second state matches
+    await consumerProgram.methods
+      .gatedActionAny([expect1, expect1])
+      .accounts({ consumer: consumer.publicKey, authority: authority.publicKey })
+      .remainingAccounts(remaining)
+      .signers([authority])
+      .rpc();
+    await new Promise(resolve => setTimeout(resolve, 1000));
+    await consumerProgram.removeEventListener(listener);
+
+    const ours = events.filter((e) => e.consumer.equals(consumer.publicKey));
+    expect(ours.map((e) => [e.privateState.toString(), e.count.toNumber()])).to.deep.equal([
+      [first.publicKey.toString(), 1],
+      [second.publicKey.toString(), 1],
+      [second.publicKey.toString(), 2],
+    ]);
+    expect(Buffer.from(ours[2].commitment).equals(c1)).to.be.true;
+  });
 });